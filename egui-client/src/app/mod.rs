@@ -19,6 +19,7 @@ pub struct App {
     module: Module,
     login: login::Login,
     home: home::Home,
+    stage: stage::Stage,
 }
 
 impl Default for App {
@@ -32,6 +33,7 @@ impl Default for App {
             module: Default::default(),
             login: Default::default(),
             home: Default::default(),
+            stage: Default::default(),
         }
     }
 }
@@ -39,6 +41,13 @@ impl Default for App {
 pub(crate) enum PendingType {
     Login,
     GetMenu,
+    StageDetail,
+    StagePicture(Option<usize>),
+    StagePhoto(usize),
+    StageCandidates(usize),
+    StageCommit(usize),
+    StageControl,
+    StageDraw,
 }
 
 #[derive(Clone)]
@@ -116,12 +125,27 @@ impl App {
 }
 
 impl eframe::App for App {
-    fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+    fn update(&mut self, ctx: &Context, frame: &mut Frame) {
         while let Ok(msg) = self.unbounded_channel.receiver.try_recv() {
             if let Some(pt) = self.pending.remove(&msg.serial) {
                 match pt {
                     PendingType::Login => login::login_callback(self, msg.res),
                     PendingType::GetMenu => home::get_menu_callback(self, msg.res),
+                    PendingType::StageDetail => stage::get_detail_callback(self, msg.res),
+                    PendingType::StagePicture(act_seq) => {
+                        stage::get_picture_callback(self, act_seq, msg.res)
+                    }
+                    PendingType::StagePhoto(cus_id) => {
+                        stage::get_photo_callback(self, cus_id, msg.res)
+                    }
+                    PendingType::StageCandidates(act_seq) => {
+                        stage::get_candidates_callback(self, act_seq, msg.res)
+                    }
+                    PendingType::StageCommit(act_seq) => {
+                        stage::commit_callback(self, act_seq, msg.res)
+                    }
+                    PendingType::StageControl => stage::control_callback(self, msg.res),
+                    PendingType::StageDraw => stage::draw_callback(self, msg.res),
                 }
             }
         }
@@ -129,6 +153,7 @@ impl eframe::App for App {
         match self.module {
             Module::Login => login::show(self, ctx),
             Module::Home => home::show(self, ctx),
+            Module::Stage => stage::show(self, ctx, frame),
         }
     }
 }
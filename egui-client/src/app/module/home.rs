@@ -11,7 +11,7 @@ use surf::http::Method;
 use surf::Request;
 use tracing::{error, warn};
 
-use crate::app::module::page;
+use crate::app::module::{page, stage};
 use crate::app::PendingType;
 use crate::App;
 
@@ -37,6 +37,8 @@ pub(crate) struct Home {
     pub(crate) menus: Arena<Menu>,
     pub(crate) active_node_id: Option<NodeId>,
     menu_map: HashMap<usize, NodeId>,
+    /// 大屏入口中输入的活动编号
+    pub(crate) stage_act_id: String,
 }
 
 impl Default for Home {
@@ -50,6 +52,7 @@ impl Default for Home {
             menus,
             menu_map,
             active_node_id: None,
+            stage_act_id: String::new(),
         }
     }
 }
@@ -128,6 +131,7 @@ pub(crate) fn show(app: &mut App, ctx: &Context) {
             for child_node_id in children {
                 show_menu(ui, app, child_node_id);
             }
+            stage::show_entry(app, ui);
         });
 
     egui::CentralPanel::default().show(ctx, |ui| {
@@ -1,10 +1,12 @@
 pub(crate) mod home;
 pub(crate) mod login;
 pub(crate) mod page;
+pub(crate) mod stage;
 
-#[derive(Default)]
+#[derive(Default, PartialEq, Eq)]
 pub(crate) enum Module {
     #[default]
     Login,
     Home,
+    Stage,
 }
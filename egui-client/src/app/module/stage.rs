@@ -0,0 +1,1002 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use async_channel::Sender;
+use async_channel::{unbounded, Receiver, TryRecvError};
+use eframe::egui::Context;
+use eframe::{egui, Frame};
+use egui::{Align2, Color32, FontFamily, FontId, Key, Pos2, Rect, Rounding, Ui, Vec2};
+use egui_extras::RetainedImage;
+#[cfg(not(target_arch = "wasm32"))]
+use futures::{AsyncBufReadExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use surf::http::Method;
+use surf::Request;
+use tracing::{error, info, warn};
+
+use crate::app::module::Module;
+use crate::app::PendingType;
+use crate::App;
+
+const BACKGROUND: Color32 = Color32::from_rgb(120, 10, 20);
+const GOLD: Color32 = Color32::from_rgb(255, 210, 90);
+/// 名单滚动速度，每秒切换的人数
+const ROLL_SPEED: f64 = 18.0;
+/// 中奖者依次出现的间隔秒数
+const REVEAL_INTERVAL: f64 = 0.4;
+/// 直播连接断开后重连的间隔秒数
+const RECONNECT_DELAY: f64 = 3.0;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct Plan {
+    pub act_seq: usize,
+    pub act_prize: Option<String>,
+    pub prize_amount: usize,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct Activity {
+    pub act_name: Option<String>,
+    pub plans: Vec<Plan>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct Candidate {
+    pub cus_id: usize,
+    pub cus_nickname: String,
+    pub cus_name: Option<String>,
+}
+
+impl Candidate {
+    fn display_name(&self) -> &str {
+        self.cus_name.as_deref().unwrap_or(&self.cus_nickname)
+    }
+}
+
+#[derive(Deserialize)]
+struct Preview {
+    remaining: usize,
+    list: Vec<Candidate>,
+}
+
+#[derive(Deserialize)]
+struct DrawResult {
+    plan: Plan,
+    winners: Vec<Candidate>,
+    seed: DrawSeed,
+}
+
+#[derive(Deserialize)]
+struct DrawSeed {
+    draw_id: usize,
+}
+
+/// 公布种子哈希的返回，只取展示用的字段
+#[derive(Deserialize)]
+struct Commitment {
+    seed_hash: String,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Phase {
+    #[default]
+    Idle,
+    Rolling,
+    Revealed,
+    Paused,
+}
+
+/// 服务端推送的现场状态，连接后首先收到
+#[derive(Debug, Deserialize)]
+struct LiveState {
+    phase: Phase,
+    act_seq: Option<usize>,
+    winners: Vec<Candidate>,
+    finished: Vec<usize>,
+}
+
+/// 服务端推送的抽奖事件
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum LiveEvent {
+    Rolling {
+        act_seq: usize,
+    },
+    WinnerRevealed {
+        act_seq: usize,
+        draw_id: usize,
+        winners: Vec<Candidate>,
+    },
+    TierFinished {
+        act_seq: usize,
+    },
+    Paused {
+        act_seq: Option<usize>,
+    },
+}
+
+#[derive(Debug)]
+enum Live {
+    State(LiveState),
+    Event(LiveEvent),
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Control {
+    Rolling { act_seq: usize },
+    Paused,
+}
+
+#[derive(Serialize)]
+struct DrawReq {
+    count: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct PreviewReq {
+    page: usize,
+    size: usize,
+}
+
+#[derive(Serialize)]
+struct PictureReq {
+    thumbnail: bool,
+}
+
+/// 大屏展示，用键盘或现场推送的事件驱动
+#[derive(Default)]
+pub(crate) struct Stage {
+    pub(crate) act_id: usize,
+    activity: Option<Activity>,
+    banner: Option<RetainedImage>,
+    prizes: HashMap<usize, RetainedImage>,
+    /// 中奖者照片，`None` 表示没有照片
+    photos: HashMap<usize, Option<RetainedImage>>,
+    plan_index: usize,
+    /// 活动信息加载前收到推送时，先记下推送中的奖项
+    wanted_act_seq: Option<usize>,
+    candidates: Vec<Candidate>,
+    remaining: usize,
+    phase: Phase,
+    winners: Vec<Candidate>,
+    draw_id: Option<usize>,
+    /// 当前奖项下一轮抽奖事先公布的种子哈希
+    seed_hash: Option<String>,
+    finished: Vec<usize>,
+    /// 每次抽取的人数，为空时抽完剩余名额
+    batch: Option<usize>,
+    reveal_start: Option<f64>,
+    live: Option<Receiver<Live>>,
+    reconnect_at: f64,
+    message: Option<String>,
+    fullscreen: bool,
+}
+
+impl Stage {
+    fn plan(&self) -> Option<&Plan> {
+        self.activity
+            .as_ref()
+            .and_then(|activity| activity.plans.get(self.plan_index))
+    }
+
+    fn act_seq(&self) -> Option<usize> {
+        self.plan().map(|plan| plan.act_seq)
+    }
+}
+
+/// 进入大屏
+pub(crate) fn enter(app: &mut App, act_id: usize) {
+    info!("进入活动{act_id}的大屏");
+    app.stage = Stage {
+        act_id,
+        ..Default::default()
+    };
+    app.module = Module::Stage;
+
+    get_detail(app);
+    get_picture(app, None);
+    subscribe(app);
+}
+
+fn leave(app: &mut App, frame: &mut Frame) {
+    info!("退出活动{}的大屏", app.stage.act_id);
+    //丢弃接收端后，推送任务会在下一个事件时结束
+    app.stage = Stage::default();
+    app.module = Module::Home;
+    set_fullscreen(frame, false);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn set_fullscreen(frame: &mut Frame, fullscreen: bool) {
+    frame.set_fullscreen(fullscreen);
+}
+
+#[cfg(target_arch = "wasm32")]
+fn set_fullscreen(_frame: &mut Frame, _fullscreen: bool) {}
+
+fn api_url(app: &App, path: &str) -> surf::Url {
+    app.base_url
+        .join(&format!("/api/activity/{}{path}", app.stage.act_id))
+        .unwrap()
+}
+
+fn get_detail(app: &mut App) {
+    let req = Request::new(Method::Get, api_url(app, ""));
+
+    let serial = app.next_serial();
+    app.pending.insert(serial, PendingType::StageDetail);
+    app.send(serial, req);
+}
+
+pub(crate) fn get_detail_callback(app: &mut App, res: surf::Result) {
+    if let Some(activity) = parse_json::<Activity>(app, res, "获取活动") {
+        let plans = activity
+            .plans
+            .iter()
+            .map(|plan| plan.act_seq)
+            .collect::<Vec<_>>();
+        //与推送的当前奖项保持一致
+        if let Some(index) = app
+            .stage
+            .wanted_act_seq
+            .take()
+            .and_then(|act_seq| plans.iter().position(|&seq| seq == act_seq))
+        {
+            app.stage.plan_index = index;
+        }
+        app.stage.activity = Some(activity);
+
+        for act_seq in plans {
+            get_picture(app, Some(act_seq));
+        }
+        get_candidates(app);
+    }
+}
+
+/// 获取活动横幅（`act_seq` 为空）或奖品图片
+fn get_picture(app: &mut App, act_seq: Option<usize>) {
+    let path = match act_seq {
+        Some(act_seq) => format!("/plan/{act_seq}/picture"),
+        None => "/picture".to_owned(),
+    };
+    let req = Request::new(Method::Get, api_url(app, &path));
+
+    let serial = app.next_serial();
+    app.pending
+        .insert(serial, PendingType::StagePicture(act_seq));
+    app.send(serial, req);
+}
+
+pub(crate) fn get_picture_callback(app: &mut App, act_seq: Option<usize>, res: surf::Result) {
+    let name = match act_seq {
+        Some(act_seq) => format!("prize-{act_seq}"),
+        None => "banner".to_owned(),
+    };
+    if let Some(image) = parse_image(&name, res) {
+        match act_seq {
+            Some(act_seq) => {
+                app.stage.prizes.insert(act_seq, image);
+            }
+            None => app.stage.banner = Some(image),
+        }
+    }
+}
+
+fn get_photo(app: &mut App, cus_id: usize) {
+    if app.stage.photos.contains_key(&cus_id) {
+        return;
+    }
+    app.stage.photos.insert(cus_id, None);
+
+    let url = app
+        .base_url
+        .join(&format!("/api/custom/{cus_id}/picture"))
+        .unwrap();
+    let mut req = Request::new(Method::Get, url);
+    req.set_query(&PictureReq { thumbnail: true }).unwrap();
+
+    let serial = app.next_serial();
+    app.pending.insert(serial, PendingType::StagePhoto(cus_id));
+    app.send(serial, req);
+}
+
+pub(crate) fn get_photo_callback(app: &mut App, cus_id: usize, res: surf::Result) {
+    if let Some(image) = parse_image(&format!("custom-{cus_id}"), res) {
+        app.stage.photos.insert(cus_id, Some(image));
+    }
+}
+
+/// 获取当前奖项的候选人，用于滚动名单
+fn get_candidates(app: &mut App) {
+    let Some(act_seq) = app.stage.act_seq() else {
+        return;
+    };
+    let mut req = Request::new(
+        Method::Get,
+        api_url(app, &format!("/draw/{act_seq}/eligible")),
+    );
+    req.set_query(&PreviewReq { page: 1, size: 500 }).unwrap();
+
+    let serial = app.next_serial();
+    app.pending
+        .insert(serial, PendingType::StageCandidates(act_seq));
+    app.send(serial, req);
+}
+
+pub(crate) fn get_candidates_callback(app: &mut App, act_seq: usize, res: surf::Result) {
+    if let Some(preview) = parse_json::<Preview>(app, res, "获取候选人") {
+        if app.stage.act_seq() == Some(act_seq) {
+            app.stage.candidates = preview.list;
+            app.stage.remaining = preview.remaining;
+            //还有名额时提前公布下一轮的种子哈希
+            if app.stage.remaining > 0 && app.stage.seed_hash.is_none() {
+                commit(app);
+            }
+        }
+    }
+}
+
+/// 公布当前奖项下一轮的种子哈希并展示在大屏上，已公布时服务端直接返回
+fn commit(app: &mut App) {
+    let Some(act_seq) = app.stage.act_seq() else {
+        return;
+    };
+    let req = Request::new(
+        Method::Post,
+        api_url(app, &format!("/draw/{act_seq}/commit")),
+    );
+
+    let serial = app.next_serial();
+    app.pending
+        .insert(serial, PendingType::StageCommit(act_seq));
+    app.send(serial, req);
+}
+
+pub(crate) fn commit_callback(app: &mut App, act_seq: usize, res: surf::Result) {
+    if let Some(commitment) = parse_json::<Commitment>(app, res, "公布种子哈希") {
+        if app.stage.act_seq() == Some(act_seq) {
+            app.stage.seed_hash = Some(commitment.seed_hash);
+        }
+    }
+}
+
+/// 种子哈希已经在大屏上公布后才能开始滚动
+fn start(app: &mut App) {
+    let Some(act_seq) = app.stage.act_seq() else {
+        return;
+    };
+    if app.stage.seed_hash.is_none() {
+        app.stage.message = Some("种子哈希还没有公布".to_owned());
+        return commit(app);
+    }
+    control(app, &Control::Rolling { act_seq });
+}
+
+fn control(app: &mut App, control: &Control) {
+    let mut req = Request::new(Method::Post, api_url(app, "/live"));
+    req.body_json(control).unwrap();
+
+    let serial = app.next_serial();
+    app.pending.insert(serial, PendingType::StageControl);
+    app.send(serial, req);
+}
+
+pub(crate) fn control_callback(app: &mut App, res: surf::Result) {
+    //现场状态以推送为准
+    parse_json::<serde_json::Value>(app, res, "控制现场");
+}
+
+fn draw(app: &mut App) {
+    let Some(act_seq) = app.stage.act_seq() else {
+        return;
+    };
+    let mut req = Request::new(Method::Post, api_url(app, &format!("/draw/{act_seq}")));
+    req.set_query(&DrawReq {
+        count: app.stage.batch.map(|batch| batch.min(app.stage.remaining)),
+    })
+    .unwrap();
+
+    let serial = app.next_serial();
+    app.pending.insert(serial, PendingType::StageDraw);
+    app.send(serial, req);
+}
+
+pub(crate) fn draw_callback(app: &mut App, res: surf::Result) {
+    //推送断开时也能直接展示抽奖结果
+    if let Some(result) = parse_json::<DrawResult>(app, res, "抽奖") {
+        reveal(
+            app,
+            result.plan.act_seq,
+            result.seed.draw_id,
+            result.winners,
+        );
+    }
+}
+
+fn reveal(app: &mut App, act_seq: usize, draw_id: usize, winners: Vec<Candidate>) {
+    if app.stage.draw_id == Some(draw_id) {
+        return;
+    }
+    select_plan(app, act_seq);
+    app.stage.phase = Phase::Revealed;
+    app.stage.draw_id = Some(draw_id);
+    //本轮的种子已经用掉，下一轮重新公布
+    app.stage.seed_hash = None;
+    app.stage.reveal_start = None;
+    for winner in &winners {
+        get_photo(app, winner.cus_id);
+    }
+    app.stage.winners = winners;
+    get_candidates(app);
+}
+
+/// 切换到指定奖项
+fn select_plan(app: &mut App, act_seq: usize) {
+    if app.stage.act_seq() == Some(act_seq) {
+        return;
+    }
+    let Some(activity) = &app.stage.activity else {
+        app.stage.wanted_act_seq = Some(act_seq);
+        return;
+    };
+    match activity.plans.iter().position(|p| p.act_seq == act_seq) {
+        Some(index) => move_to(app, index),
+        None => warn!("活动{}没有奖项{act_seq}", app.stage.act_id),
+    }
+}
+
+fn move_to(app: &mut App, index: usize) {
+    app.stage.plan_index = index;
+    app.stage.phase = Phase::Idle;
+    app.stage.winners.clear();
+    app.stage.candidates.clear();
+    app.stage.remaining = 0;
+    app.stage.seed_hash = None;
+    get_candidates(app);
+}
+
+/// 订阅现场推送（sse），连接断开后由 `show` 定时重连
+#[cfg(not(target_arch = "wasm32"))]
+fn subscribe(app: &mut App) {
+    let req = Request::new(Method::Get, api_url(app, "/live"));
+    let client = app.client.clone();
+    let (sender, receiver) = unbounded();
+    app.stage.live = Some(receiver);
+
+    let task = async move {
+        match client.send(req).await {
+            Ok(response) if response.status().is_success() => read_events(response, sender).await,
+            Ok(response) => error!("订阅现场推送失败： {response:?}"),
+            Err(e) => error!("订阅现场推送异常： {e}"),
+        }
+    };
+    async_global_executor::spawn(task).detach();
+}
+
+/// 按行解析 sse，空行表示一个事件结束
+#[cfg(not(target_arch = "wasm32"))]
+async fn read_events(response: surf::Response, sender: Sender<Live>) {
+    let mut lines = futures::io::BufReader::new(response).lines();
+    let mut name = String::new();
+    let mut data = String::new();
+
+    while let Some(Ok(line)) = lines.next().await {
+        if let Some(value) = line.strip_prefix("event:") {
+            name = value.trim().to_owned();
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push_str(value.trim());
+        } else if line.is_empty() && !data.is_empty() {
+            if let Some(live) = parse_event(&name, &data) {
+                if sender.send(live).await.is_err() {
+                    return;
+                }
+            }
+            data.clear();
+        }
+    }
+    info!("现场推送已断开");
+}
+
+/// 浏览器中 surf 会等整个响应结束才返回，sse 只能用 EventSource 接收
+///
+/// EventSource 自带的重连拿不到最新状态，出错时直接关闭，由 `show` 重新订阅。
+#[cfg(target_arch = "wasm32")]
+fn subscribe(app: &mut App) {
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::JsCast;
+    use web_sys::{Event, EventSource, EventSourceInit, MessageEvent};
+
+    let (sender, receiver) = unbounded();
+    app.stage.live = Some(receiver);
+
+    let init = EventSourceInit::new();
+    init.set_with_credentials(true);
+    let source = match EventSource::new_with_event_source_init_dict(
+        api_url(app, "/live").as_str(),
+        &init,
+    ) {
+        Ok(source) => source,
+        Err(e) => {
+            error!("订阅现场推送异常： {e:?}");
+            sender.close();
+            return;
+        }
+    };
+
+    for name in [
+        "state",
+        "rolling",
+        "winner_revealed",
+        "tier_finished",
+        "paused",
+    ] {
+        let listener = {
+            let (sender, source) = (sender.clone(), source.clone());
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                let Some(data) = event.data().as_string() else {
+                    return;
+                };
+                if let Some(live) = parse_event(name, &data) {
+                    //接收端已丢弃，说明已经退出大屏或重新订阅
+                    if sender.try_send(live).is_err() {
+                        source.close();
+                    }
+                }
+            })
+        };
+        if let Err(e) =
+            source.add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())
+        {
+            error!("订阅现场推送异常： {e:?}");
+        }
+        listener.forget();
+    }
+
+    let on_error = {
+        let source = source.clone();
+        Closure::<dyn FnMut(Event)>::new(move |_: Event| {
+            info!("现场推送已断开");
+            source.close();
+            sender.close();
+        })
+    };
+    source.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+    on_error.forget();
+}
+
+fn parse_event(name: &str, data: &str) -> Option<Live> {
+    let live = match name {
+        "state" => serde_json::from_str(data).map(Live::State),
+        _ => serde_json::from_str(data).map(Live::Event),
+    };
+    live.map_err(|e| warn!("解析现场推送失败 {name}: {e}")).ok()
+}
+
+fn apply(app: &mut App, live: Live) {
+    match live {
+        Live::State(state) => {
+            if let Some(act_seq) = state.act_seq {
+                select_plan(app, act_seq);
+            }
+            app.stage.phase = state.phase;
+            app.stage.finished = state.finished;
+            app.stage.reveal_start = None;
+            for winner in &state.winners {
+                get_photo(app, winner.cus_id);
+            }
+            app.stage.winners = state.winners;
+        }
+        Live::Event(LiveEvent::Rolling { act_seq }) => {
+            select_plan(app, act_seq);
+            app.stage.phase = Phase::Rolling;
+            app.stage.winners.clear();
+        }
+        Live::Event(LiveEvent::WinnerRevealed {
+            act_seq,
+            draw_id,
+            winners,
+        }) => reveal(app, act_seq, draw_id, winners),
+        Live::Event(LiveEvent::TierFinished { act_seq }) => {
+            if !app.stage.finished.contains(&act_seq) {
+                app.stage.finished.push(act_seq);
+            }
+        }
+        Live::Event(LiveEvent::Paused { act_seq }) => {
+            if let Some(act_seq) = act_seq {
+                select_plan(app, act_seq);
+            }
+            app.stage.phase = Phase::Paused;
+        }
+    }
+}
+
+fn parse_json<T: serde::de::DeserializeOwned>(
+    app: &mut App,
+    res: surf::Result,
+    action: &str,
+) -> Option<T> {
+    match res {
+        Ok(mut response) => {
+            if response.status().is_success() {
+                match futures::executor::block_on(response.body_json::<T>()) {
+                    Ok(value) => return Some(value),
+                    Err(e) => error!("{action}解析失败: {e}"),
+                }
+            } else {
+                let message =
+                    futures::executor::block_on(response.body_string()).unwrap_or_default();
+                error!("{action}失败： {response:?} {message}");
+                app.stage.message = Some(format!("{action}失败 {message}"));
+            }
+        }
+        Err(e) => {
+            error!("{action}异常： {e}");
+            app.stage.message = Some(format!("{action}异常"));
+        }
+    }
+    None
+}
+
+/// 没有图片时返回 404，直接忽略
+fn parse_image(name: &str, res: surf::Result) -> Option<RetainedImage> {
+    match res {
+        Ok(mut response) if response.status().is_success() => {
+            let bytes = futures::executor::block_on(response.body_bytes()).ok()?;
+            RetainedImage::from_image_bytes(name, &bytes)
+                .map_err(|e| error!("图片{name}解析失败: {e}"))
+                .ok()
+        }
+        Ok(_) => None,
+        Err(e) => {
+            error!("获取图片{name}异常： {e}");
+            None
+        }
+    }
+}
+
+fn handle_keys(app: &mut App, ctx: &Context, frame: &mut Frame) {
+    let keys = ctx.input(|i| {
+        i.events
+            .iter()
+            .filter_map(|event| match event {
+                egui::Event::Key {
+                    key, pressed: true, ..
+                } => Some(*key),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    });
+
+    for key in keys {
+        match key {
+            Key::Escape => return leave(app, frame),
+            Key::F11 => {
+                app.stage.fullscreen = !app.stage.fullscreen;
+                set_fullscreen(frame, app.stage.fullscreen);
+            }
+            Key::Space | Key::Enter => match app.stage.phase {
+                Phase::Rolling => draw(app),
+                _ => start(app),
+            },
+            Key::P => control(app, &Control::Paused),
+            Key::ArrowLeft | Key::ArrowUp => {
+                if app.stage.plan_index > 0 {
+                    move_to(app, app.stage.plan_index - 1);
+                }
+            }
+            Key::ArrowRight | Key::ArrowDown => {
+                let plans = app.stage.activity.as_ref().map_or(0, |a| a.plans.len());
+                if app.stage.plan_index + 1 < plans {
+                    move_to(app, app.stage.plan_index + 1);
+                }
+            }
+            _ => {
+                if let Some(batch) = digit(key) {
+                    app.stage.batch = (batch > 0).then_some(batch);
+                }
+            }
+        }
+    }
+}
+
+fn digit(key: Key) -> Option<usize> {
+    [
+        Key::Num0,
+        Key::Num1,
+        Key::Num2,
+        Key::Num3,
+        Key::Num4,
+        Key::Num5,
+        Key::Num6,
+        Key::Num7,
+        Key::Num8,
+        Key::Num9,
+    ]
+    .iter()
+    .position(|&k| k == key)
+}
+
+pub(crate) fn show(app: &mut App, ctx: &Context, frame: &mut Frame) {
+    let now = ctx.input(|i| i.time);
+
+    //处理现场推送，断开后定时重连
+    loop {
+        let received = match &app.stage.live {
+            Some(live) => live.try_recv(),
+            None => Err(TryRecvError::Closed),
+        };
+        match received {
+            Ok(live) => apply(app, live),
+            Err(TryRecvError::Empty) => break,
+            Err(TryRecvError::Closed) => {
+                if app.stage.live.take().is_some() {
+                    app.stage.reconnect_at = now + RECONNECT_DELAY;
+                } else if now >= app.stage.reconnect_at {
+                    subscribe(app);
+                }
+                break;
+            }
+        }
+    }
+
+    handle_keys(app, ctx, frame);
+    if app.module != Module::Stage {
+        return;
+    }
+    if app.stage.reveal_start.is_none() && !app.stage.winners.is_empty() {
+        app.stage.reveal_start = Some(now);
+    }
+
+    egui::CentralPanel::default()
+        .frame(egui::Frame::none().fill(BACKGROUND))
+        .show(ctx, |ui| {
+            let rect = ui.max_rect();
+            let height = rect.height();
+
+            //横幅
+            let banner_rect = Rect::from_min_size(rect.min, Vec2::new(rect.width(), height * 0.2));
+            show_banner(app, ui, banner_rect);
+
+            //奖项
+            let title_rect = Rect::from_min_size(
+                Pos2::new(rect.left(), banner_rect.bottom()),
+                Vec2::new(rect.width(), height * 0.1),
+            );
+            show_title(app, ui, title_rect);
+
+            let main_rect = Rect::from_min_max(
+                Pos2::new(rect.left(), title_rect.bottom()),
+                Pos2::new(rect.right(), rect.bottom() - height * 0.06),
+            );
+            match app.stage.phase {
+                Phase::Rolling | Phase::Paused => show_rolling(app, ui, main_rect, now),
+                Phase::Revealed => show_winners(app, ui, main_rect, now),
+                Phase::Idle => show_prize(app, ui, main_rect),
+            }
+
+            show_footer(app, ui, rect);
+        });
+
+    //滚动和揭晓动画需要持续刷新，同时及时处理推送
+    ctx.request_repaint_after(Duration::from_millis(30));
+}
+
+fn font(size: f32) -> FontId {
+    FontId::new(size, FontFamily::Monospace)
+}
+
+fn show_banner(app: &App, ui: &mut Ui, rect: Rect) {
+    if let Some(banner) = &app.stage.banner {
+        let size = fit(banner.size_vec2(), rect.size());
+        let image_rect = Rect::from_center_size(rect.center(), size);
+        ui.painter().image(
+            banner.texture_id(ui.ctx()),
+            image_rect,
+            Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+            Color32::WHITE,
+        );
+    } else {
+        let act_name = app
+            .stage
+            .activity
+            .as_ref()
+            .and_then(|activity| activity.act_name.clone())
+            .unwrap_or_default();
+        ui.painter().text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            act_name,
+            font(rect.height() * 0.45),
+            GOLD,
+        );
+    }
+}
+
+fn show_title(app: &App, ui: &mut Ui, rect: Rect) {
+    let Some(plan) = app.stage.plan() else {
+        return;
+    };
+    let mut title = format!(
+        "{}  共{}名",
+        plan.act_prize.as_deref().unwrap_or_default(),
+        plan.prize_amount
+    );
+    if app.stage.finished.contains(&plan.act_seq) {
+        title.push_str("  已抽完");
+    } else {
+        title.push_str(&format!("  剩余{}名", app.stage.remaining));
+    }
+    ui.painter().text(
+        rect.center(),
+        Align2::CENTER_CENTER,
+        title,
+        font(rect.height() * 0.5),
+        Color32::WHITE,
+    );
+}
+
+fn show_prize(app: &App, ui: &mut Ui, rect: Rect) {
+    let Some(act_seq) = app.stage.act_seq() else {
+        return;
+    };
+    if let Some(prize) = app.stage.prizes.get(&act_seq) {
+        let size = fit(prize.size_vec2(), rect.shrink(rect.height() * 0.05).size());
+        ui.painter().image(
+            prize.texture_id(ui.ctx()),
+            Rect::from_center_size(rect.center(), size),
+            Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+            Color32::WHITE,
+        );
+    }
+}
+
+fn show_rolling(app: &App, ui: &mut Ui, rect: Rect, now: f64) {
+    let candidates = &app.stage.candidates;
+    if candidates.is_empty() {
+        return;
+    }
+
+    //同时滚动多个名字，暂停时停在当前位置
+    let rows = app.stage.batch.unwrap_or(app.stage.remaining).clamp(1, 10);
+    let offset = (now * ROLL_SPEED) as usize;
+    let row_height = rect.height() / rows as f32;
+    for row in 0..rows {
+        let candidate = &candidates[(offset + row * 7) % candidates.len()];
+        let center = Pos2::new(
+            rect.center().x,
+            rect.top() + row_height * (row as f32 + 0.5),
+        );
+        ui.painter().text(
+            center,
+            Align2::CENTER_CENTER,
+            candidate.display_name(),
+            font((row_height * 0.6).min(rect.height() * 0.25)),
+            GOLD,
+        );
+    }
+
+    if app.stage.phase == Phase::Paused {
+        ui.painter()
+            .rect_filled(rect, Rounding::none(), Color32::from_black_alpha(120));
+        ui.painter().text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            "暂停",
+            font(rect.height() * 0.2),
+            Color32::WHITE,
+        );
+    }
+}
+
+fn show_winners(app: &App, ui: &mut Ui, rect: Rect, now: f64) {
+    let winners = &app.stage.winners;
+    let start = app.stage.reveal_start.unwrap_or(now);
+
+    //按网格排列，每张卡片依次放大淡入
+    let columns = (winners.len() as f32).sqrt().ceil().max(1.0) as usize;
+    let rows = winners.len().div_ceil(columns);
+    let cell = Vec2::new(
+        rect.width() / columns as f32,
+        rect.height() / rows.max(1) as f32,
+    );
+    for (i, winner) in winners.iter().enumerate() {
+        let progress = ((now - start - i as f64 * REVEAL_INTERVAL) / 0.6).clamp(0.0, 1.0) as f32;
+        if progress <= 0.0 {
+            continue;
+        }
+        let eased = 1.0 - (1.0 - progress).powi(3);
+        let center = Pos2::new(
+            rect.left() + cell.x * ((i % columns) as f32 + 0.5),
+            rect.top() + cell.y * ((i / columns) as f32 + 0.5),
+        );
+        let card = Rect::from_center_size(center, cell * 0.85 * eased);
+        let alpha = (255.0 * eased) as u8;
+        ui.painter().rect_filled(
+            card,
+            Rounding::same(card.height() * 0.08),
+            Color32::from_rgba_unmultiplied(255, 210, 90, alpha / 4),
+        );
+
+        let photo = app
+            .stage
+            .photos
+            .get(&winner.cus_id)
+            .and_then(Option::as_ref);
+        let name_center = match photo {
+            Some(photo) => {
+                let photo_rect = Rect::from_center_size(
+                    Pos2::new(card.center().x, card.top() + card.height() * 0.35),
+                    fit(photo.size_vec2(), card.size() * 0.55),
+                );
+                ui.painter().image(
+                    photo.texture_id(ui.ctx()),
+                    photo_rect,
+                    Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                    Color32::from_white_alpha(alpha),
+                );
+                Pos2::new(card.center().x, card.top() + card.height() * 0.8)
+            }
+            None => card.center(),
+        };
+        ui.painter().text(
+            name_center,
+            Align2::CENTER_CENTER,
+            winner.display_name(),
+            font((card.height() * 0.22).min(card.width() * 0.18).max(1.0)),
+            Color32::from_rgba_unmultiplied(255, 210, 90, alpha),
+        );
+    }
+}
+
+fn show_footer(app: &App, ui: &mut Ui, rect: Rect) {
+    let batch = match app.stage.batch {
+        Some(batch) => format!("每次{batch}人"),
+        None => "抽完剩余".to_owned(),
+    };
+    let hint =
+        format!("空格 开始/抽取  ←→ 切换奖项  数字 每次人数({batch})  P 暂停  F11 全屏  Esc 退出");
+    let text = match &app.stage.message {
+        Some(message) => format!("{message}    {hint}"),
+        None => hint,
+    };
+    let text = match &app.stage.seed_hash {
+        Some(seed_hash) => format!("种子哈希 {seed_hash}\n{text}"),
+        None => text,
+    };
+    ui.painter().text(
+        Pos2::new(rect.center().x, rect.bottom() - rect.height() * 0.03),
+        Align2::CENTER_CENTER,
+        text,
+        font(rect.height() * 0.022),
+        Color32::from_white_alpha(160),
+    );
+}
+
+/// 按比例缩放到不超过 `max`
+fn fit(size: Vec2, max: Vec2) -> Vec2 {
+    let scale = (max.x / size.x.max(1.0)).min(max.y / size.y.max(1.0));
+    size * scale
+}
+
+/// 首页中的大屏入口
+pub(crate) fn show_entry(app: &mut App, ui: &mut Ui) {
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.label("活动：");
+        egui::TextEdit::singleline(&mut app.home.stage_act_id)
+            .desired_width(40.0)
+            .show(ui);
+    });
+    let act_id = app.home.stage_act_id.trim().parse::<usize>();
+    if ui
+        .add_enabled(act_id.is_ok(), egui::Button::new("进入大屏"))
+        .clicked()
+    {
+        if let Ok(act_id) = act_id {
+            enter(app, act_id);
+        }
+    }
+}
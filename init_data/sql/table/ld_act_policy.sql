@@ -0,0 +1,15 @@
+drop table ld_act_policy;
+create table ld_act_policy
+(
+    act_id          integer not null
+        constraint ld_act_policy_pk primary key
+        constraint ld_act_policy_ld_activity_act_id_fk references ld_activity,
+    max_wins        integer default 1 not null,
+    recent_acts     integer,
+    recent_days     integer,
+    top_tier        integer,
+    require_checkin integer default 0 not null,
+    checkin_from    integer,
+    checkin_to      integer,
+    self_checkin    integer default 0 not null
+);
@@ -5,5 +5,9 @@ create table ld_activity
         constraint ld_activity_pk primary key autoincrement,
     act_name        TEXT,
     act_picture     BLOB,
-    act_description TEXT
-);
\ No newline at end of file
+    act_thumbnail   BLOB,
+    act_description TEXT,
+    act_status      integer default 0 not null,
+    create_time     integer,
+    status_time     integer
+);
@@ -0,0 +1,12 @@
+drop table ld_activity_status;
+create table ld_activity_status
+(
+    act_id      integer not null
+        constraint ld_activity_status_ld_activity_act_id_fk references ld_activity,
+    from_status integer not null,
+    to_status   integer not null,
+    user_id     integer,
+    change_time integer not null
+);
+
+create index ld_activity_status_act_id_index on ld_activity_status (act_id);
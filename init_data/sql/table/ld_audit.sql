@@ -0,0 +1,26 @@
+drop table ld_audit;
+create table ld_audit
+(
+    audit_id   integer not null
+        constraint ld_audit_pk primary key,
+    event      TEXT    not null,
+    act_id     integer not null,
+    event_time integer not null,
+    payload    TEXT    not null,
+    prev_hash  TEXT    not null,
+    entry_hash TEXT    not null
+);
+
+create index ld_audit_act_id_index on ld_audit (act_id);
+
+create trigger ld_audit_no_update
+    before update on ld_audit
+begin
+    select raise(abort, '审计记录只能追加');
+end;
+
+create trigger ld_audit_no_delete
+    before delete on ld_audit
+begin
+    select raise(abort, '审计记录只能追加');
+end;
@@ -0,0 +1,14 @@
+drop table ld_checkin;
+create table ld_checkin
+(
+    act_id       integer not null
+        constraint ld_checkin_ld_activity_act_id_fk references ld_activity,
+    cus_id       integer not null
+        constraint ld_checkin_ld_custom_cus_id_fk references ld_custom,
+    token        TEXT    not null,
+    checkin_time integer,
+    checkin_user integer
+);
+
+create unique index ld_checkin_act_id_cus_id_uindex on ld_checkin (act_id, cus_id);
+create unique index ld_checkin_token_uindex on ld_checkin (token);
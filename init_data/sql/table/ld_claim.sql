@@ -0,0 +1,17 @@
+drop table ld_claim;
+create table ld_claim
+(
+    win_id       integer not null
+        constraint ld_claim_pk primary key
+        constraint ld_claim_ld_win_list_win_id_fk references ld_win_list,
+    redeem_code  TEXT    not null,
+    claim_status integer default 0 not null,
+    deadline     integer not null,
+    notify_time  integer,
+    claim_time   integer,
+    claim_user   integer,
+    ship_time    integer,
+    ship_note    TEXT
+);
+
+create unique index ld_claim_redeem_code_uindex on ld_claim (redeem_code);
@@ -0,0 +1,12 @@
+drop table ld_custom_weight;
+create table ld_custom_weight
+(
+    act_id      integer not null
+        constraint ld_custom_weight_ld_activity_act_id_fk references ld_activity,
+    cus_id      integer not null
+        constraint ld_custom_weight_ld_custom_cus_id_fk references ld_custom,
+    weight      integer not null,
+    weight_note TEXT
+);
+
+create unique index ld_custom_weight_act_id_cus_id_uindex on ld_custom_weight (act_id, cus_id);
@@ -0,0 +1,27 @@
+drop table ld_draw;
+create table ld_draw
+(
+    draw_id      integer not null
+        constraint ld_draw_pk primary key autoincrement,
+    act_id       integer not null
+        constraint ld_draw_ld_activity_act_id_fk references ld_activity,
+    act_seq      integer not null,
+    seed_hash    TEXT    not null,
+    input_hash   TEXT,
+    inputs       TEXT,
+    seed         TEXT    not null,
+    algorithm    TEXT    not null,
+    draw_count   integer,
+    win_count    integer,
+    participants TEXT,
+    weights      TEXT,
+    commit_time  integer not null,
+    draw_time    integer,
+    run_id       integer
+        constraint ld_draw_ld_schedule_run_run_id_fk references ld_schedule_run,
+    split        TEXT,
+    quota        TEXT
+);
+
+create index ld_draw_act_id_act_seq_index on ld_draw (act_id, act_seq);
+create unique index ld_draw_act_id_act_seq_pending_uindex on ld_draw (act_id, act_seq) where draw_time is null;
@@ -0,0 +1,14 @@
+drop table ld_instant;
+create table ld_instant
+(
+    act_id     integer not null
+        constraint ld_instant_pk primary key
+        constraint ld_instant_ld_activity_act_id_fk references ld_activity,
+    odds_mode  integer default 0 not null,
+    max_plays  integer default 1 not null,
+    start_time integer,
+    end_time   integer,
+    pool_size  integer,
+    seed       TEXT,
+    seed_hash  TEXT
+);
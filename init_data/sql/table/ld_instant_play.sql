@@ -0,0 +1,19 @@
+drop table ld_instant_play;
+create table ld_instant_play
+(
+    play_id   integer not null
+        constraint ld_instant_play_pk primary key autoincrement,
+    act_id    integer not null
+        constraint ld_instant_play_ld_activity_act_id_fk references ld_activity,
+    cus_id    integer not null
+        constraint ld_instant_play_ld_custom_cus_id_fk references ld_custom,
+    seed      TEXT    not null,
+    roll      integer,
+    ticket_no integer,
+    act_seq   integer,
+    win_id    integer
+        constraint ld_instant_play_ld_win_list_win_id_fk references ld_win_list,
+    play_time integer not null
+);
+
+create index ld_instant_play_act_id_cus_id_index on ld_instant_play (act_id, cus_id);
@@ -0,0 +1,10 @@
+drop table ld_instant_prize;
+create table ld_instant_prize
+(
+    act_id  integer not null
+        constraint ld_instant_prize_ld_activity_act_id_fk references ld_activity,
+    act_seq integer not null,
+    odds    integer default 0 not null
+);
+
+create unique index ld_instant_prize_act_id_act_seq_uindex on ld_instant_prize (act_id, act_seq);
@@ -0,0 +1,14 @@
+drop table ld_instant_ticket;
+create table ld_instant_ticket
+(
+    ticket_id integer not null
+        constraint ld_instant_ticket_pk primary key autoincrement,
+    act_id    integer not null
+        constraint ld_instant_ticket_ld_activity_act_id_fk references ld_activity,
+    ticket_no integer not null,
+    act_seq   integer,
+    play_id   integer
+        constraint ld_instant_ticket_ld_instant_play_play_id_fk references ld_instant_play
+);
+
+create unique index ld_instant_ticket_act_id_ticket_no_uindex on ld_instant_ticket (act_id, ticket_no);
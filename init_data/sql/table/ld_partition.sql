@@ -0,0 +1,20 @@
+drop table ld_partition;
+create table ld_partition
+(
+    part_id       integer not null
+        constraint ld_partition_pk primary key autoincrement,
+    act_id        integer not null
+        constraint ld_partition_ld_activity_act_id_fk references ld_activity,
+    part_name     TEXT    not null,
+    act_seq       integer,
+    keep_together TEXT    not null,
+    keep_apart    TEXT    not null,
+    seed          TEXT,
+    seed_hash     TEXT,
+    commit_time   integer,
+    algorithm     TEXT,
+    layout        TEXT,
+    run_time      integer
+);
+
+create unique index ld_partition_act_id_part_name_uindex on ld_partition (act_id, part_name);
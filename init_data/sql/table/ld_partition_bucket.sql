@@ -0,0 +1,11 @@
+drop table ld_partition_bucket;
+create table ld_partition_bucket
+(
+    part_id     integer not null
+        constraint ld_partition_bucket_ld_partition_part_id_fk references ld_partition,
+    bucket_idx  integer not null,
+    bucket_name TEXT    not null,
+    capacity    integer not null
+);
+
+create unique index ld_partition_bucket_part_id_bucket_idx_uindex on ld_partition_bucket (part_id, bucket_idx);
@@ -0,0 +1,11 @@
+drop table ld_partition_seat;
+create table ld_partition_seat
+(
+    part_id    integer not null
+        constraint ld_partition_seat_ld_partition_part_id_fk references ld_partition,
+    cus_id     integer not null
+        constraint ld_partition_seat_ld_custom_cus_id_fk references ld_custom,
+    bucket_idx integer not null
+);
+
+create unique index ld_partition_seat_part_id_cus_id_uindex on ld_partition_seat (part_id, cus_id);
@@ -0,0 +1,14 @@
+drop table ld_plan_quota;
+create table ld_plan_quota
+(
+    act_id      integer not null
+        constraint ld_plan_quota_ld_activity_act_id_fk
+            references ld_activity,
+    act_seq     integer not null,
+    cus_flag    TEXT    not null,
+    min_count   integer default 0 not null,
+    max_count   integer,
+    max_percent integer
+);
+
+create unique index ld_plan_quota_act_id_act_seq_cus_flag_uindex on ld_plan_quota (act_id, act_seq, cus_flag);
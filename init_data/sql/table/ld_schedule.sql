@@ -0,0 +1,20 @@
+drop table ld_schedule;
+create table ld_schedule
+(
+    sch_id       integer not null
+        constraint ld_schedule_pk primary key autoincrement,
+    act_id       integer not null
+        constraint ld_schedule_ld_activity_act_id_fk references ld_activity,
+    act_seq      integer,
+    draw_count   integer,
+    run_at       integer,
+    cron         TEXT,
+    timezone     TEXT    not null,
+    enabled      integer default 1 not null,
+    next_run     integer,
+    max_attempts integer default 3 not null,
+    retry_delay  integer default 60 not null,
+    create_time  integer not null
+);
+
+create index ld_schedule_act_id_index on ld_schedule (act_id);
@@ -0,0 +1,17 @@
+drop table ld_schedule_run;
+create table ld_schedule_run
+(
+    run_id       integer not null
+        constraint ld_schedule_run_pk primary key autoincrement,
+    sch_id       integer not null
+        constraint ld_schedule_run_ld_schedule_sch_id_fk references ld_schedule,
+    fire_time    integer not null,
+    run_status   integer default 0 not null,
+    attempts     integer default 0 not null,
+    next_attempt integer not null,
+    start_time   integer,
+    end_time     integer,
+    message      TEXT
+);
+
+create unique index ld_schedule_run_sch_id_fire_time_uindex on ld_schedule_run (sch_id, fire_time);
@@ -0,0 +1,13 @@
+drop table ld_team;
+create table ld_team
+(
+    team_id   integer not null
+        constraint ld_team_pk primary key autoincrement,
+    act_id    integer not null
+        constraint ld_team_ld_activity_act_id_fk references ld_activity,
+    team_name TEXT    not null,
+    cus_flag  TEXT,
+    weight    integer default 1 not null
+);
+
+create unique index ld_team_act_id_team_name_uindex on ld_team (act_id, team_name);
@@ -0,0 +1,10 @@
+drop table ld_team_member;
+create table ld_team_member
+(
+    team_id integer not null
+        constraint ld_team_member_ld_team_team_id_fk references ld_team,
+    cus_id  integer not null
+        constraint ld_team_member_ld_custom_cus_id_fk references ld_custom
+);
+
+create unique index ld_team_member_team_id_cus_id_uindex on ld_team_member (team_id, cus_id);
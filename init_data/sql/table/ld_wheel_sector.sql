@@ -0,0 +1,12 @@
+drop table ld_wheel_sector;
+create table ld_wheel_sector
+(
+    act_id     integer not null
+        constraint ld_wheel_sector_ld_activity_act_id_fk references ld_activity,
+    sector_idx integer not null,
+    label      TEXT    not null,
+    act_seq    integer,
+    weight     integer not null
+);
+
+create unique index ld_wheel_sector_act_id_sector_idx_uindex on ld_wheel_sector (act_id, sector_idx);
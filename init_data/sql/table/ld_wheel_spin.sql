@@ -0,0 +1,20 @@
+drop table ld_wheel_spin;
+create table ld_wheel_spin
+(
+    spin_id    integer not null
+        constraint ld_wheel_spin_pk primary key autoincrement,
+    act_id     integer not null
+        constraint ld_wheel_spin_ld_activity_act_id_fk references ld_activity,
+    cus_id     integer not null
+        constraint ld_wheel_spin_ld_custom_cus_id_fk references ld_custom,
+    seed       TEXT    not null,
+    weights    TEXT    not null,
+    landed     integer not null,
+    sector_idx integer not null,
+    act_seq    integer,
+    win_id     integer
+        constraint ld_wheel_spin_ld_win_list_win_id_fk references ld_win_list,
+    spin_time  integer not null
+);
+
+create unique index ld_wheel_spin_act_id_cus_id_uindex on ld_wheel_spin (act_id, cus_id);
@@ -1,8 +1,24 @@
 drop table ld_win_list;
 create table ld_win_list
 (
-    act_id  integer not null
+    win_id         integer not null
+        constraint ld_win_list_pk primary key autoincrement,
+    act_id         integer not null
         constraint ld_win_list_ld_activity_act_id_fk references ld_activity,
-    act_seq integer not null,
-    cus_id  integer not null
-);
\ No newline at end of file
+    act_seq        integer not null,
+    cus_id         integer not null,
+    win_time       integer,
+    draw_id        integer
+        constraint ld_win_list_ld_draw_draw_id_fk references ld_draw,
+    win_status     integer default 0 not null,
+    forfeit_reason TEXT,
+    forfeit_time   integer,
+    forfeit_user   integer,
+    replaces       integer
+        constraint ld_win_list_ld_win_list_win_id_fk references ld_win_list,
+    amount         integer,
+    team_id        integer
+        constraint ld_win_list_ld_team_team_id_fk references ld_team
+);
+
+create index ld_win_list_act_id_act_seq_index on ld_win_list (act_id, act_seq);
@@ -0,0 +1,199 @@
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::activity::plan::PlanDetail;
+use crate::activity::state::ActStatus;
+use crate::error::BizError;
+
+pub(crate) mod plan;
+pub(crate) mod policy;
+pub(crate) mod state;
+pub(crate) mod team;
+
+/// 活动，对应 ld_activity 的一行（不含图片）
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Activity {
+    pub act_id: usize,
+    pub act_name: Option<String>,
+    pub act_description: Option<String>,
+    pub act_status: ActStatus,
+    pub create_time: Option<i64>,
+    pub status_time: Option<i64>,
+}
+
+/// 活动详情，包含按 act_seq 排序的奖项及其参与规则
+#[derive(Debug, Serialize)]
+pub(crate) struct ActivityDetail {
+    #[serde(flatten)]
+    pub activity: Activity,
+    pub plans: Vec<PlanDetail>,
+}
+
+/// 新建或修改活动的参数
+#[derive(Debug, Deserialize)]
+pub(crate) struct ActivityArgs {
+    pub act_name: String,
+    #[serde(default)]
+    pub act_description: Option<String>,
+}
+
+impl ActivityArgs {
+    fn validate(&self) -> Result<()> {
+        if self.act_name.trim().is_empty() {
+            return Err(BizError::Invalid("活动名称不能为空".to_owned()).into());
+        }
+        Ok(())
+    }
+}
+
+const SELECT_ACTIVITY: &str =
+    "select act_id,act_name,act_description,act_status,create_time,status_time from ld_activity";
+
+impl Activity {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Activity {
+            act_id: row.get(0)?,
+            act_name: row.get(1)?,
+            act_description: row.get(2)?,
+            act_status: ActStatus::try_from(row.get::<_, i64>(3)?)?,
+            create_time: row.get(4)?,
+            status_time: row.get(5)?,
+        })
+    }
+}
+
+pub(crate) fn list(conn: &Connection) -> Result<Vec<Activity>> {
+    let sql = format!("{SELECT_ACTIVITY} order by act_id desc");
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([])?;
+
+    let mut activities = Vec::new();
+    while let Some(row) = rows.next()? {
+        activities.push(Activity::from_row(row)?);
+    }
+
+    Ok(activities)
+}
+
+pub(crate) fn get(conn: &Connection, act_id: usize) -> Result<Activity> {
+    let sql = format!("{SELECT_ACTIVITY} where act_id=?");
+    let activity = conn
+        .query_row(&sql, [act_id], |row| Ok(Activity::from_row(row)))
+        .optional()?;
+
+    match activity {
+        Some(activity) => activity,
+        None => Err(BizError::NotFound(format!("活动{act_id}不存在")).into()),
+    }
+}
+
+pub(crate) fn detail(conn: &Connection, act_id: usize) -> Result<ActivityDetail> {
+    Ok(ActivityDetail {
+        activity: get(conn, act_id)?,
+        plans: plan::list(conn, act_id)?,
+    })
+}
+
+pub(crate) fn create(conn: &Connection, args: &ActivityArgs) -> Result<Activity> {
+    args.validate()?;
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    conn.execute(
+        "insert into ld_activity (act_name, act_description, act_status, create_time, status_time)
+         values (?, ?, ?, ?, ?)",
+        (
+            args.act_name.trim(),
+            &args.act_description,
+            ActStatus::Draft as i64,
+            now,
+            now,
+        ),
+    )?;
+    let act_id = conn.last_insert_rowid() as usize;
+    info!("新建活动{act_id}: {}", args.act_name);
+
+    get(conn, act_id)
+}
+
+pub(crate) fn update(conn: &Connection, act_id: usize, args: &ActivityArgs) -> Result<Activity> {
+    args.validate()?;
+    state::ensure(
+        conn,
+        act_id,
+        &[
+            ActStatus::Draft,
+            ActStatus::Published,
+            ActStatus::RegistrationOpen,
+            ActStatus::Drawing,
+            ActStatus::Closed,
+        ],
+        "修改",
+    )?;
+
+    conn.execute(
+        "update ld_activity set act_name=?, act_description=? where act_id=?",
+        (args.act_name.trim(), &args.act_description, act_id),
+    )?;
+
+    get(conn, act_id)
+}
+
+/// 删除活动及其奖项配置，只有草稿状态的活动可以删除
+pub(crate) fn remove(conn: &mut Connection, act_id: usize) -> Result<()> {
+    let tx = conn.transaction()?;
+    state::ensure(&tx, act_id, &[ActStatus::Draft], "删除")?;
+
+    let winners: usize = tx.query_row(
+        "select count(*) from ld_win_list where act_id=?",
+        [act_id],
+        |row| row.get(0),
+    )?;
+    if winners > 0 {
+        return Err(BizError::Conflict(format!("活动{act_id}已有中奖记录，不能删除")).into());
+    }
+
+    tx.execute(
+        "delete from ld_schedule_run where sch_id in (select sch_id from ld_schedule where act_id=?)",
+        [act_id],
+    )?;
+    tx.execute(
+        "delete from ld_team_member where team_id in (select team_id from ld_team where act_id=?)",
+        [act_id],
+    )?;
+    for table in ["ld_partition_seat", "ld_partition_bucket"] {
+        tx.execute(
+            &format!(
+                "delete from {table} where part_id in (select part_id from ld_partition where act_id=?)"
+            ),
+            [act_id],
+        )?;
+    }
+    for table in [
+        "ld_schedule",
+        "ld_plan_range",
+        "ld_plan_quota",
+        "ld_plan",
+        "ld_custom_weight",
+        "ld_team",
+        "ld_act_policy",
+        "ld_checkin",
+        "ld_wheel_spin",
+        "ld_wheel_sector",
+        "ld_instant_play",
+        "ld_instant_ticket",
+        "ld_instant_prize",
+        "ld_instant",
+        "ld_partition",
+        "ld_draw",
+        "ld_activity_status",
+        "ld_activity",
+    ] {
+        tx.execute(&format!("delete from {table} where act_id=?"), [act_id])?;
+    }
+    tx.commit()?;
+    info!("删除活动{act_id}");
+
+    Ok(())
+}
@@ -0,0 +1,463 @@
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{Connection, ErrorCode};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::activity::{policy, state};
+use crate::draw::engine::Split;
+use crate::draw::quota::{self, Quota};
+use crate::draw::rule::FlagType;
+use crate::draw::{load_plan, DrawMode, DrawUnit, Envelope, Plan, PrizeType};
+use crate::error::BizError;
+use crate::instant::OddsMode;
+
+/// 奖项的一条参与规则，对应 ld_plan_range 的一行
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Range {
+    pub cus_flag: String,
+    pub flag_type: FlagType,
+}
+
+/// 奖项及其参与规则和分组配额
+#[derive(Debug, Serialize)]
+pub(crate) struct PlanDetail {
+    #[serde(flatten)]
+    pub plan: Plan,
+    pub ranges: Vec<Range>,
+    pub quotas: Vec<Quota>,
+}
+
+/// 新建或修改奖项的参数
+#[derive(Debug, Deserialize)]
+pub(crate) struct PlanArgs {
+    pub act_seq: usize,
+    #[serde(default)]
+    pub act_prize: Option<String>,
+    pub prize_amount: usize,
+    #[serde(default)]
+    pub draw_mode: DrawMode,
+    #[serde(default)]
+    pub prize_type: PrizeType,
+    /// 红包奖项必须指定
+    #[serde(default)]
+    pub envelope: Option<Envelope>,
+    #[serde(default)]
+    pub draw_unit: DrawUnit,
+}
+
+impl PlanArgs {
+    fn validate(&self) -> Result<()> {
+        if self.act_seq == 0 {
+            return Err(BizError::Invalid("奖项序号必须大于0".to_owned()).into());
+        }
+        if self.prize_amount == 0 {
+            return Err(BizError::Invalid("奖品数量必须大于0".to_owned()).into());
+        }
+        match (self.prize_type, &self.envelope) {
+            (PrizeType::RedEnvelope, Some(envelope)) => {
+                let split = Split {
+                    budget: envelope.budget,
+                    shares: self.prize_amount,
+                    share_min: envelope.share_min,
+                    share_max: envelope.share_max,
+                };
+                if !split.feasible() {
+                    return Err(BizError::Invalid(format!(
+                        "红包总金额{}分无法拆成{}份{}~{}分",
+                        envelope.budget, self.prize_amount, envelope.share_min, envelope.share_max
+                    ))
+                    .into());
+                }
+            }
+            (PrizeType::RedEnvelope, None) => {
+                return Err(BizError::Invalid("红包奖项必须指定金额配置".to_owned()).into());
+            }
+            (PrizeType::Item, Some(_)) => {
+                return Err(BizError::Invalid("只有红包奖项可以指定金额配置".to_owned()).into());
+            }
+            (PrizeType::Item, None) => {}
+        }
+        if self.draw_unit == DrawUnit::Team && self.prize_type == PrizeType::RedEnvelope {
+            return Err(BizError::Invalid("红包奖项不能按团体抽奖".to_owned()).into());
+        }
+        Ok(())
+    }
+
+    /// budget、share_min、share_max 三列的值
+    fn envelope_columns(&self) -> (Option<i64>, Option<i64>, Option<i64>) {
+        match &self.envelope {
+            Some(envelope) => (
+                Some(envelope.budget),
+                Some(envelope.share_min),
+                Some(envelope.share_max),
+            ),
+            None => (None, None, None),
+        }
+    }
+}
+
+/// 活动的全部奖项，按 act_seq 排序
+pub(crate) fn list(conn: &Connection, act_id: usize) -> Result<Vec<PlanDetail>> {
+    let mut stmt = conn.prepare("select act_seq from ld_plan where act_id=? order by act_seq")?;
+    let seqs = stmt
+        .query_map([act_id], |row| row.get(0))?
+        .collect::<Result<Vec<usize>, _>>()?;
+
+    let mut plans = Vec::with_capacity(seqs.len());
+    for act_seq in seqs {
+        plans.push(get(conn, act_id, act_seq)?);
+    }
+
+    Ok(plans)
+}
+
+pub(crate) fn get(conn: &Connection, act_id: usize, act_seq: usize) -> Result<PlanDetail> {
+    Ok(PlanDetail {
+        plan: load_plan(conn, act_id, act_seq)?,
+        ranges: ranges(conn, act_id, act_seq)?,
+        quotas: quota::load(conn, act_id, act_seq)?,
+    })
+}
+
+pub(crate) fn create(conn: &Connection, act_id: usize, args: &PlanArgs) -> Result<PlanDetail> {
+    args.validate()?;
+    state::ensure_plan_editable(conn, act_id)?;
+
+    let (budget, share_min, share_max) = args.envelope_columns();
+    let res = conn.execute(
+        "insert into ld_plan (act_id, act_seq, act_prize, prize_amount, draw_mode, prize_type,
+                              budget, share_min, share_max, draw_unit)
+         values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        (
+            act_id,
+            args.act_seq,
+            &args.act_prize,
+            args.prize_amount,
+            args.draw_mode as i64,
+            args.prize_type as i64,
+            budget,
+            share_min,
+            share_max,
+            args.draw_unit as i64,
+        ),
+    );
+    match res {
+        Err(e) if e.sqlite_error_code() == Some(ErrorCode::ConstraintViolation) => {
+            return Err(
+                BizError::Conflict(format!("活动{act_id}已存在第{}个奖项", args.act_seq)).into(),
+            );
+        }
+        res => res?,
+    };
+    info!("活动{act_id}新建奖项{}", args.act_seq);
+
+    get(conn, act_id, args.act_seq)
+}
+
+/// 按奖项序号关联的抽奖结果，有结果的奖项不能再调整序号或删除
+const RECORD_TABLES: [(&str, &str); 4] = [
+    ("ld_draw", "抽奖记录"),
+    ("ld_win_list", "中奖记录"),
+    ("ld_wheel_spin", "转盘记录"),
+    ("ld_instant_play", "即时抽奖记录"),
+];
+
+/// 按奖项序号关联的配置，调整序号时一起修改，有配置的奖项不能删除
+const CONFIG_TABLES: [(&str, &str); 5] = [
+    ("ld_schedule", "定时抽奖"),
+    ("ld_wheel_sector", "转盘扇区"),
+    ("ld_instant_prize", "即时抽奖奖项"),
+    ("ld_instant_ticket", "即时抽奖奖池"),
+    ("ld_partition", "分组"),
+];
+
+fn referenced(conn: &Connection, table: &str, act_id: usize, act_seq: usize) -> Result<bool> {
+    let exists = conn.query_row(
+        &format!("select exists(select 1 from {table} where act_id=? and act_seq=?)"),
+        [act_id, act_seq],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
+/// 调整序号时保持 top_tier 对奖项的划分不变
+///
+/// top_tier 就是被调整的奖项时随之改为新序号；调整后有奖项从 top_tier 的一侧移到另一侧时返回冲突。
+fn renumber_top_tier(
+    conn: &Connection,
+    act_id: usize,
+    act_seq: usize,
+    new_seq: usize,
+) -> Result<()> {
+    let Some(top_tier) = policy::get(conn, act_id)?.top_tier else {
+        return Ok(());
+    };
+    let new_top = if top_tier == act_seq {
+        new_seq
+    } else {
+        top_tier
+    };
+
+    let mut moves = vec![(act_seq, new_seq)];
+    let mut stmt = conn.prepare("select act_seq from ld_plan where act_id=? and act_seq<>?")?;
+    let others = stmt
+        .query_map([act_id, act_seq], |row| row.get(0))?
+        .collect::<Result<Vec<usize>, _>>()?;
+    moves.extend(others.into_iter().map(|seq| (seq, seq)));
+    if moves
+        .iter()
+        .any(|&(old, new)| (old <= top_tier) != (new <= new_top))
+    {
+        return Err(BizError::Conflict(format!(
+            "中奖限制按奖项{top_tier}划分高低奖项，调整奖项{act_seq}的序号会改变划分，请先修改中奖限制"
+        ))
+        .into());
+    }
+
+    if new_top != top_tier {
+        conn.execute(
+            "update ld_act_policy set top_tier=? where act_id=?",
+            [new_top, act_id],
+        )?;
+        info!("活动{act_id}的中奖限制 top_tier 随奖项调整为{new_top}");
+    }
+    Ok(())
+}
+
+/// 奖项被 `tables` 中任一张表引用时返回冲突
+fn ensure_unreferenced(
+    conn: &Connection,
+    tables: &[(&str, &str)],
+    act_id: usize,
+    act_seq: usize,
+    action: &str,
+) -> Result<()> {
+    for &(table, name) in tables {
+        if referenced(conn, table, act_id, act_seq)? {
+            return Err(
+                BizError::Conflict(format!("奖项{act_seq}已有{name}，不能{action}")).into(),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 修改奖项，`args.act_seq` 可以与原序号不同，用于调整奖项顺序
+///
+/// 调整序号时定时抽奖、转盘、即时抽奖和分组的配置跟着改到新序号；已有抽奖结果时不能调整。
+pub(crate) fn update(
+    conn: &mut Connection,
+    act_id: usize,
+    act_seq: usize,
+    args: &PlanArgs,
+) -> Result<PlanDetail> {
+    args.validate()?;
+    let tx = conn.transaction()?;
+    state::ensure_plan_editable(&tx, act_id)?;
+    let plan = load_plan(&tx, act_id, act_seq)?;
+    quota::validate(&quota::load(&tx, act_id, act_seq)?, args.prize_amount)?;
+    //转盘、即时抽奖和分组配额只能按个人开奖
+    if args.draw_unit == DrawUnit::Team && plan.draw_unit != DrawUnit::Team {
+        for (table, name) in [
+            ("ld_wheel_sector", "已用于转盘"),
+            ("ld_instant_prize", "已用于即时抽奖"),
+            ("ld_plan_quota", "已配置分组配额"),
+        ] {
+            if referenced(&tx, table, act_id, act_seq)? {
+                return Err(
+                    BizError::Invalid(format!("奖项{act_seq}{name}，不能改为团体奖项")).into(),
+                );
+            }
+        }
+    }
+    //奖池在保存即时抽奖配置时按名额生成中奖券
+    if args.prize_amount != plan.prize_amount {
+        let pooled: bool = tx.query_row(
+            "select exists(select 1 from ld_instant_prize p join ld_instant i on i.act_id=p.act_id
+                            where p.act_id=? and p.act_seq=? and i.odds_mode=?)",
+            (act_id, act_seq, OddsMode::Pool as i64),
+            |row| row.get(0),
+        )?;
+        if pooled {
+            return Err(BizError::Invalid(format!(
+                "奖项{act_seq}已用于即时抽奖的奖池，不能修改名额，请先从即时抽奖中移除"
+            ))
+            .into());
+        }
+    }
+
+    if args.act_seq != act_seq {
+        let exists: bool = tx.query_row(
+            "select exists(select 1 from ld_plan where act_id=? and act_seq=?)",
+            [act_id, args.act_seq],
+            |row| row.get(0),
+        )?;
+        if exists {
+            return Err(
+                BizError::Conflict(format!("活动{act_id}已存在第{}个奖项", args.act_seq)).into(),
+            );
+        }
+        ensure_unreferenced(&tx, &RECORD_TABLES, act_id, act_seq, "调整序号")?;
+        renumber_top_tier(&tx, act_id, act_seq, args.act_seq)?;
+        for table in ["ld_plan_range", "ld_plan_quota"]
+            .into_iter()
+            .chain(CONFIG_TABLES.map(|(table, _)| table))
+        {
+            tx.execute(
+                &format!("update {table} set act_seq=? where act_id=? and act_seq=?"),
+                [args.act_seq, act_id, act_seq],
+            )?;
+        }
+    }
+
+    let (budget, share_min, share_max) = args.envelope_columns();
+    tx.execute(
+        "update ld_plan set act_seq=?, act_prize=?, prize_amount=?, draw_mode=?, prize_type=?,
+                            budget=?, share_min=?, share_max=?, draw_unit=?
+          where act_id=? and act_seq=?",
+        (
+            args.act_seq,
+            &args.act_prize,
+            args.prize_amount,
+            args.draw_mode as i64,
+            args.prize_type as i64,
+            budget,
+            share_min,
+            share_max,
+            args.draw_unit as i64,
+            act_id,
+            act_seq,
+        ),
+    )?;
+    let plan = get(&tx, act_id, args.act_seq)?;
+    tx.commit()?;
+
+    Ok(plan)
+}
+
+/// 删除奖项，奖项已有抽奖结果或被其他配置引用时不能删除
+pub(crate) fn remove(conn: &mut Connection, act_id: usize, act_seq: usize) -> Result<()> {
+    let tx = conn.transaction()?;
+    state::ensure_plan_editable(&tx, act_id)?;
+    load_plan(&tx, act_id, act_seq)?;
+    ensure_unreferenced(&tx, &RECORD_TABLES, act_id, act_seq, "删除")?;
+    ensure_unreferenced(&tx, &CONFIG_TABLES, act_id, act_seq, "删除")?;
+
+    tx.execute(
+        "delete from ld_plan_range where act_id=? and act_seq=?",
+        [act_id, act_seq],
+    )?;
+    tx.execute(
+        "delete from ld_plan_quota where act_id=? and act_seq=?",
+        [act_id, act_seq],
+    )?;
+    tx.execute(
+        "delete from ld_plan where act_id=? and act_seq=?",
+        [act_id, act_seq],
+    )?;
+    tx.commit()?;
+    info!("活动{act_id}删除奖项{act_seq}");
+
+    Ok(())
+}
+
+pub(crate) fn ranges(conn: &Connection, act_id: usize, act_seq: usize) -> Result<Vec<Range>> {
+    let mut stmt = conn.prepare(
+        "select cus_flag,flag_type from ld_plan_range
+          where act_id=? and act_seq=? order by flag_type,rowid",
+    )?;
+    let mut rows = stmt.query([act_id, act_seq])?;
+
+    let mut ranges = Vec::new();
+    while let Some(row) = rows.next()? {
+        let cus_flag: Option<String> = row.get(0)?;
+        let flag_type: Option<i64> = row.get(1)?;
+        ranges.push(Range {
+            cus_flag: cus_flag.unwrap_or_default(),
+            flag_type: FlagType::try_from(flag_type.unwrap_or_default())?,
+        });
+    }
+
+    Ok(ranges)
+}
+
+/// 整体替换奖项的参与规则
+pub(crate) fn save_ranges(
+    conn: &mut Connection,
+    act_id: usize,
+    act_seq: usize,
+    ranges: &[Range],
+) -> Result<Vec<Range>> {
+    if ranges.iter().any(|range| range.cus_flag.trim().is_empty()) {
+        return Err(BizError::Invalid("规则标签不能为空".to_owned()).into());
+    }
+
+    let tx = conn.transaction()?;
+    state::ensure_plan_editable(&tx, act_id)?;
+    load_plan(&tx, act_id, act_seq)?;
+
+    tx.execute(
+        "delete from ld_plan_range where act_id=? and act_seq=?",
+        [act_id, act_seq],
+    )?;
+    {
+        let mut stmt = tx.prepare(
+            "insert into ld_plan_range (act_id, act_seq, cus_flag, flag_type) values (?, ?, ?, ?)",
+        )?;
+        for range in ranges {
+            stmt.execute((
+                act_id,
+                act_seq,
+                range.cus_flag.trim(),
+                range.flag_type as i64,
+            ))?;
+        }
+    }
+    let ranges = self::ranges(&tx, act_id, act_seq)?;
+    tx.commit()?;
+    info!("活动{act_id}奖项{act_seq}保存{}条规则", ranges.len());
+
+    Ok(ranges)
+}
+
+/// 整体替换奖项的分组配额，例如每个办公室至少一人、总部不超过 30%
+pub(crate) fn save_quotas(
+    conn: &mut Connection,
+    act_id: usize,
+    act_seq: usize,
+    quotas: &[Quota],
+) -> Result<Vec<Quota>> {
+    let tx = conn.transaction()?;
+    state::ensure_plan_editable(&tx, act_id)?;
+    let plan = load_plan(&tx, act_id, act_seq)?;
+    if plan.draw_unit == DrawUnit::Team && !quotas.is_empty() {
+        return Err(BizError::Invalid("团体奖项不支持分组配额".to_owned()).into());
+    }
+    quota::validate(quotas, plan.prize_amount)?;
+
+    tx.execute(
+        "delete from ld_plan_quota where act_id=? and act_seq=?",
+        [act_id, act_seq],
+    )?;
+    {
+        let mut stmt = tx.prepare(
+            "insert into ld_plan_quota (act_id, act_seq, cus_flag, min_count, max_count, max_percent)
+             values (?, ?, ?, ?, ?, ?)",
+        )?;
+        for quota in quotas {
+            stmt.execute((
+                act_id,
+                act_seq,
+                quota.cus_flag.trim(),
+                quota.min_count,
+                quota.max_count,
+                quota.max_percent,
+            ))?;
+        }
+    }
+    let quotas = quota::load(&tx, act_id, act_seq)?;
+    tx.commit()?;
+    info!("活动{act_id}奖项{act_seq}保存{}条配额", quotas.len());
+
+    Ok(quotas)
+}
@@ -0,0 +1,245 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{Connection, OptionalExtension, Params};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::activity::{self, state};
+use crate::error::BizError;
+use crate::winner::WinStatus;
+
+/// 活动的参与和中奖限制，对应 ld_act_policy 的一行，没有配置时每人每个活动只能中奖一次
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Policy {
+    /// 本活动中每人最多的中奖次数，中奖和候补都计入，作废的不计入
+    pub max_wins: usize,
+    /// 排除在之前 K 个活动中中过奖的客户
+    #[serde(default)]
+    pub recent_acts: Option<usize>,
+    /// 排除最近 K 天内在其他活动中中过奖的客户
+    #[serde(default)]
+    pub recent_days: Option<usize>,
+    /// 抽取该序号之后的奖项时，排除已中过该奖项或更高奖项（序号更小）的客户
+    #[serde(default)]
+    pub top_tier: Option<usize>,
+    /// 只有签到的客户才能参与抽奖
+    #[serde(default)]
+    pub require_checkin: bool,
+    /// 签到时间窗口的开始，为空时不限制
+    #[serde(default)]
+    pub checkin_from: Option<i64>,
+    /// 签到时间窗口的结束，为空时不限制
+    #[serde(default)]
+    pub checkin_to: Option<i64>,
+    /// 允许客户通过公开链接自助签到
+    #[serde(default)]
+    pub self_checkin: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            max_wins: 1,
+            recent_acts: None,
+            recent_days: None,
+            top_tier: None,
+            require_checkin: false,
+            checkin_from: None,
+            checkin_to: None,
+            self_checkin: false,
+        }
+    }
+}
+
+impl Policy {
+    fn validate(&self) -> Result<()> {
+        if self.max_wins == 0 {
+            return Err(BizError::Invalid("每人中奖次数上限必须大于0".to_owned()).into());
+        }
+        if self.recent_acts == Some(0) || self.recent_days == Some(0) {
+            return Err(BizError::Invalid("排除的活动数和天数必须大于0".to_owned()).into());
+        }
+        if self.top_tier == Some(0) {
+            return Err(BizError::Invalid("奖项序号必须大于0".to_owned()).into());
+        }
+        if let (Some(from), Some(to)) = (self.checkin_from, self.checkin_to) {
+            if from > to {
+                return Err(
+                    BizError::Invalid("签到窗口的开始时间不能晚于结束时间".to_owned()).into(),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn get(conn: &Connection, act_id: usize) -> Result<Policy> {
+    activity::get(conn, act_id)?;
+    let policy = conn
+        .query_row(
+            "select max_wins,recent_acts,recent_days,top_tier,require_checkin,checkin_from,
+                    checkin_to,self_checkin
+               from ld_act_policy where act_id=?",
+            [act_id],
+            |row| {
+                Ok(Policy {
+                    max_wins: row.get(0)?,
+                    recent_acts: row.get(1)?,
+                    recent_days: row.get(2)?,
+                    top_tier: row.get(3)?,
+                    require_checkin: row.get(4)?,
+                    checkin_from: row.get(5)?,
+                    checkin_to: row.get(6)?,
+                    self_checkin: row.get(7)?,
+                })
+            },
+        )
+        .optional()?;
+
+    Ok(policy.unwrap_or_default())
+}
+
+/// 保存参与和中奖限制，开始抽奖后不能修改
+pub(crate) fn save(conn: &Connection, act_id: usize, policy: &Policy) -> Result<Policy> {
+    policy.validate()?;
+    state::ensure_plan_editable(conn, act_id)?;
+
+    conn.execute(
+        "insert or replace into ld_act_policy (act_id, max_wins, recent_acts, recent_days, top_tier,
+                                               require_checkin, checkin_from, checkin_to,
+                                               self_checkin)
+         values (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        (
+            act_id,
+            policy.max_wins,
+            policy.recent_acts,
+            policy.recent_days,
+            policy.top_tier,
+            policy.require_checkin,
+            policy.checkin_from,
+            policy.checkin_to,
+            policy.self_checkin,
+        ),
+    )?;
+    info!("活动{act_id}参与和中奖限制: {policy:?}");
+
+    get(conn, act_id)
+}
+
+/// 按中奖限制被排除的客户，一个客户只记在第一个命中的原因下
+#[derive(Debug, Default)]
+pub(crate) struct Exclusions {
+    /// 本奖项已有记录（包括作废和候补），或本活动中奖和候补次数已达上限
+    pub already_won: HashSet<usize>,
+    /// 已中过 top_tier 及以上的奖项（包括候补）
+    pub top_tier: HashSet<usize>,
+    /// 近期在其他活动中中过奖
+    pub recent: HashSet<usize>,
+    /// 要求签到时，没有在签到窗口内签到
+    pub absent: HashSet<usize>,
+}
+
+/// 本活动中每个客户计入 max_wins 的次数
+///
+/// 与 top_tier 一样候补也计入，避免候补递补后超过上限。
+pub(crate) fn win_counts(conn: &Connection, act_id: usize) -> Result<HashMap<usize, usize>> {
+    let mut stmt = conn.prepare(
+        "select cus_id,count(*) from ld_win_list
+          where act_id=?1 and win_status in (?2, ?3) group by cus_id",
+    )?;
+    let mut rows = stmt.query((act_id, WinStatus::Won as i64, WinStatus::Alternate as i64))?;
+    let mut counts = HashMap::new();
+    while let Some(row) = rows.next()? {
+        counts.insert(row.get(0)?, row.get(1)?);
+    }
+
+    Ok(counts)
+}
+
+impl Exclusions {
+    /// 计算抽取 `act_seq` 奖项时需要排除的客户
+    pub(crate) fn load(
+        conn: &Connection,
+        act_id: usize,
+        act_seq: usize,
+        policy: &Policy,
+    ) -> Result<Self> {
+        //本奖项已有记录（包括作废），或中奖次数已达上限
+        let mut exclusions = Exclusions {
+            already_won: query_ids(
+                conn,
+                "select distinct cus_id from ld_win_list where act_id=?1 and act_seq=?2",
+                (act_id, act_seq),
+            )?,
+            ..Default::default()
+        };
+        exclusions.already_won.extend(
+            win_counts(conn, act_id)?
+                .into_iter()
+                .filter(|(_, count)| *count >= policy.max_wins)
+                .map(|(cus_id, _)| cus_id),
+        );
+
+        if let Some(top_tier) = policy.top_tier.filter(|top_tier| act_seq > *top_tier) {
+            exclusions.top_tier = query_ids(
+                conn,
+                "select distinct cus_id from ld_win_list
+                  where act_id=?1 and act_seq<=?2 and win_status in (?3, ?4)",
+                (
+                    act_id,
+                    top_tier,
+                    WinStatus::Won as i64,
+                    WinStatus::Alternate as i64,
+                ),
+            )?;
+        }
+
+        //只统计其他活动中的有效中奖
+        if let Some(recent_acts) = policy.recent_acts {
+            exclusions.recent.extend(query_ids(
+                conn,
+                "select distinct cus_id from ld_win_list
+                  where win_status=?3 and act_id in (
+                        select act_id from ld_activity where act_id<?1 order by act_id desc limit ?2)",
+                (act_id, recent_acts, WinStatus::Won as i64),
+            )?);
+        }
+        if let Some(recent_days) = policy.recent_days {
+            let since =
+                time::OffsetDateTime::now_utc().unix_timestamp() - recent_days as i64 * 86400;
+            exclusions.recent.extend(query_ids(
+                conn,
+                "select distinct cus_id from ld_win_list
+                  where act_id<>?1 and win_status=?2 and win_time>=?3",
+                (act_id, WinStatus::Won as i64, since),
+            )?);
+        }
+
+        if policy.require_checkin {
+            exclusions.absent = query_ids(
+                conn,
+                "select cus_id from ld_custom where cus_id not in (
+                        select cus_id from ld_checkin
+                         where act_id=?1 and checkin_time>=ifnull(?2,checkin_time)
+                           and checkin_time<=ifnull(?3,checkin_time))",
+                (act_id, policy.checkin_from, policy.checkin_to),
+            )?;
+        }
+
+        exclusions.top_tier = &exclusions.top_tier - &exclusions.already_won;
+        exclusions.recent = &(&exclusions.recent - &exclusions.already_won) - &exclusions.top_tier;
+        exclusions.absent = &(&(&exclusions.absent - &exclusions.already_won)
+            - &exclusions.top_tier)
+            - &exclusions.recent;
+        Ok(exclusions)
+    }
+}
+
+fn query_ids<P: Params>(conn: &Connection, sql: &str, params: P) -> Result<HashSet<usize>> {
+    let mut stmt = conn.prepare(sql)?;
+    let ids = stmt
+        .query_map(params, |row| row.get(0))?
+        .collect::<Result<HashSet<usize>, _>>()?;
+    Ok(ids)
+}
@@ -0,0 +1,208 @@
+use std::fmt::{Display, Formatter};
+
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{Connection, OptionalExtension, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::error::BizError;
+
+/// 活动状态，对应 ld_activity.act_status
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ActStatus {
+    /// 0: 草稿，可以随意修改
+    Draft = 0,
+    /// 1: 已发布
+    Published = 1,
+    /// 2: 报名中
+    RegistrationOpen = 2,
+    /// 3: 抽奖中，奖项和参与规则不能再修改
+    Drawing = 3,
+    /// 4: 已结束
+    Closed = 4,
+    /// 5: 已归档
+    Archived = 5,
+}
+
+impl TryFrom<i64> for ActStatus {
+    type Error = BizError;
+
+    fn try_from(value: i64) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ActStatus::Draft),
+            1 => Ok(ActStatus::Published),
+            2 => Ok(ActStatus::RegistrationOpen),
+            3 => Ok(ActStatus::Drawing),
+            4 => Ok(ActStatus::Closed),
+            5 => Ok(ActStatus::Archived),
+            _ => Err(BizError::Invalid(format!("未知的活动状态: {value}"))),
+        }
+    }
+}
+
+impl Display for ActStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ActStatus::Draft => "草稿",
+            ActStatus::Published => "已发布",
+            ActStatus::RegistrationOpen => "报名中",
+            ActStatus::Drawing => "抽奖中",
+            ActStatus::Closed => "已结束",
+            ActStatus::Archived => "已归档",
+        };
+        f.write_str(name)
+    }
+}
+
+impl ActStatus {
+    /// 允许的状态迁移
+    pub(crate) fn can_transit(&self, to: ActStatus) -> bool {
+        use ActStatus::*;
+
+        matches!(
+            (self, to),
+            (Draft, Published)
+                | (Draft, Archived)
+                | (Published, Draft)
+                | (Published, RegistrationOpen)
+                | (Published, Drawing)
+                | (RegistrationOpen, Published)
+                | (RegistrationOpen, Drawing)
+                | (Drawing, Closed)
+                | (Closed, Archived)
+        )
+    }
+
+    /// 奖项和参与规则是否还能修改
+    pub(crate) fn plan_editable(&self) -> bool {
+        matches!(
+            self,
+            ActStatus::Draft | ActStatus::Published | ActStatus::RegistrationOpen
+        )
+    }
+}
+
+/// 一次状态迁移，对应 ld_activity_status 的一行
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Transition {
+    pub from_status: ActStatus,
+    pub to_status: ActStatus,
+    pub user_id: Option<usize>,
+    pub change_time: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct StatusInfo {
+    pub act_id: usize,
+    pub act_status: ActStatus,
+    pub history: Vec<Transition>,
+}
+
+/// 查询活动当前状态
+pub(crate) fn status(conn: &Connection, act_id: usize) -> Result<ActStatus> {
+    let status: Option<i64> = conn
+        .query_row(
+            "select act_status from ld_activity where act_id=?",
+            [act_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    match status {
+        Some(status) => Ok(ActStatus::try_from(status)?),
+        None => Err(BizError::NotFound(format!("活动{act_id}不存在")).into()),
+    }
+}
+
+/// 校验活动处于允许的状态之一，否则返回冲突错误
+pub(crate) fn ensure(
+    conn: &Connection,
+    act_id: usize,
+    allowed: &[ActStatus],
+    operation: &str,
+) -> Result<ActStatus> {
+    let status = status(conn, act_id)?;
+    if !allowed.contains(&status) {
+        return Err(
+            BizError::Conflict(format!("活动{act_id}当前状态为{status}，不能{operation}")).into(),
+        );
+    }
+    Ok(status)
+}
+
+/// 校验活动的奖项和参与规则还能修改
+pub(crate) fn ensure_plan_editable(conn: &Connection, act_id: usize) -> Result<()> {
+    let status = status(conn, act_id)?;
+    if !status.plan_editable() {
+        return Err(BizError::Conflict(format!("活动{act_id}已开始抽奖，不能修改奖项配置")).into());
+    }
+    Ok(())
+}
+
+pub(crate) fn history(conn: &Connection, act_id: usize) -> Result<StatusInfo> {
+    let act_status = status(conn, act_id)?;
+    let mut stmt = conn.prepare(
+        "select from_status,to_status,user_id,change_time from ld_activity_status
+          where act_id=? order by change_time,rowid",
+    )?;
+    let mut rows = stmt.query([act_id])?;
+
+    let mut history = Vec::new();
+    while let Some(row) = rows.next()? {
+        history.push(Transition {
+            from_status: ActStatus::try_from(row.get::<_, i64>(0)?)?,
+            to_status: ActStatus::try_from(row.get::<_, i64>(1)?)?,
+            user_id: row.get(2)?,
+            change_time: row.get(3)?,
+        });
+    }
+
+    Ok(StatusInfo {
+        act_id,
+        act_status,
+        history,
+    })
+}
+
+/// 迁移活动状态并记录迁移时间
+pub(crate) fn transit(
+    conn: &mut Connection,
+    act_id: usize,
+    to: ActStatus,
+    user_id: usize,
+) -> Result<StatusInfo> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let from = status(&tx, act_id)?;
+    if !from.can_transit(to) {
+        return Err(BizError::Conflict(format!("活动{act_id}不能从{from}变更为{to}")).into());
+    }
+
+    //开始抽奖前至少要有一个有名额的奖项
+    if to == ActStatus::Drawing {
+        let prizes: usize = tx.query_row(
+            "select count(*) from ld_plan where act_id=? and prize_amount>0",
+            [act_id],
+            |row| row.get(0),
+        )?;
+        if prizes == 0 {
+            return Err(BizError::Conflict(format!("活动{act_id}还没有配置奖项")).into());
+        }
+    }
+
+    let change_time = time::OffsetDateTime::now_utc().unix_timestamp();
+    tx.execute(
+        "update ld_activity set act_status=?, status_time=? where act_id=?",
+        (to as i64, change_time, act_id),
+    )?;
+    tx.execute(
+        "insert into ld_activity_status (act_id, from_status, to_status, user_id, change_time)
+         values (?, ?, ?, ?, ?)",
+        (act_id, from as i64, to as i64, user_id, change_time),
+    )?;
+    let info = history(&tx, act_id)?;
+    tx.commit()?;
+    info!("活动{act_id}状态 {from} -> {to}");
+
+    Ok(info)
+}
@@ -0,0 +1,268 @@
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{Connection, ErrorCode, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::activity::{self, state};
+use crate::draw::rule::parse_flags;
+use crate::draw::weight::MAX_WEIGHT;
+use crate::error::BizError;
+
+/// 团体奖项中的一个团体，对应 ld_team 的一行
+///
+/// 成员为带有 `cus_flag` 标签的客户加上 ld_team_member 中明确指定的客户。
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Team {
+    pub team_id: usize,
+    pub act_id: usize,
+    pub team_name: String,
+    pub cus_flag: Option<String>,
+    /// 按权重抽奖时团体的权重
+    pub weight: u64,
+    /// 明确指定的成员，不包括按标签匹配的
+    pub cus_ids: Vec<usize>,
+    /// 全部成员的 cus_id，升序
+    pub members: Vec<usize>,
+}
+
+/// 新建或修改团体的参数
+#[derive(Debug, Deserialize)]
+pub(crate) struct TeamArgs {
+    pub team_name: String,
+    #[serde(default)]
+    pub cus_flag: Option<String>,
+    #[serde(default)]
+    pub cus_ids: Vec<usize>,
+    #[serde(default = "default_weight")]
+    pub weight: u64,
+}
+
+fn default_weight() -> u64 {
+    1
+}
+
+impl TeamArgs {
+    fn validate(&self) -> Result<()> {
+        if self.team_name.trim().is_empty() {
+            return Err(BizError::Invalid("团体名称不能为空".to_owned()).into());
+        }
+        let cus_flag = self.cus_flag.as_deref().map(str::trim).unwrap_or_default();
+        if cus_flag.is_empty() && self.cus_ids.is_empty() {
+            return Err(BizError::Invalid("团体必须指定标签或成员".to_owned()).into());
+        }
+        if self.weight > MAX_WEIGHT {
+            return Err(BizError::Invalid(format!(
+                "团体{}的权重{}超过上限{MAX_WEIGHT}",
+                self.team_name, self.weight
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    fn cus_flag(&self) -> Option<&str> {
+        self.cus_flag
+            .as_deref()
+            .map(str::trim)
+            .filter(|flag| !flag.is_empty())
+    }
+}
+
+const SELECT_TEAM: &str = "select team_id,act_id,team_name,cus_flag,weight from ld_team";
+
+impl Team {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Team {
+            team_id: row.get(0)?,
+            act_id: row.get(1)?,
+            team_name: row.get(2)?,
+            cus_flag: row.get(3)?,
+            weight: row.get::<_, i64>(4)?.max(0) as u64,
+            cus_ids: Vec::new(),
+            members: Vec::new(),
+        })
+    }
+
+    /// 加载明确指定的成员，并按标签匹配出全部成员
+    fn load_members(&mut self, conn: &Connection) -> Result<()> {
+        let mut stmt =
+            conn.prepare("select cus_id from ld_team_member where team_id=? order by cus_id")?;
+        self.cus_ids = stmt
+            .query_map([self.team_id], |row| row.get(0))?
+            .collect::<Result<Vec<usize>, _>>()?;
+
+        self.members = self.cus_ids.clone();
+        if let Some(team_flag) = &self.cus_flag {
+            let mut stmt = conn.prepare("select cus_id,cus_flag from ld_custom")?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let cus_flag: Option<String> = row.get(1)?;
+                if cus_flag.is_some_and(|flags| parse_flags(&flags).contains(&team_flag.as_str())) {
+                    self.members.push(row.get(0)?);
+                }
+            }
+        }
+        self.members.sort_unstable();
+        self.members.dedup();
+        Ok(())
+    }
+}
+
+/// 活动的全部团体，按 team_id 排序
+pub(crate) fn list(conn: &Connection, act_id: usize) -> Result<Vec<Team>> {
+    activity::get(conn, act_id)?;
+    let sql = format!("{SELECT_TEAM} where act_id=? order by team_id");
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([act_id])?;
+
+    let mut teams = Vec::new();
+    while let Some(row) = rows.next()? {
+        teams.push(Team::from_row(row)?);
+    }
+    for team in &mut teams {
+        team.load_members(conn)?;
+    }
+
+    Ok(teams)
+}
+
+pub(crate) fn get(conn: &Connection, act_id: usize, team_id: usize) -> Result<Team> {
+    let sql = format!("{SELECT_TEAM} where act_id=? and team_id=?");
+    let team = conn
+        .query_row(&sql, [act_id, team_id], |row| Ok(Team::from_row(row)))
+        .optional()?;
+
+    match team {
+        Some(team) => {
+            let mut team = team?;
+            team.load_members(conn)?;
+            Ok(team)
+        }
+        None => Err(BizError::NotFound(format!("活动{act_id}没有团体{team_id}")).into()),
+    }
+}
+
+/// 同一客户不能属于多个团体，否则一次抽奖可能让他重复中奖
+///
+/// 团体成员会随客户标签变化，所以抽奖前还要再检查一次。
+pub(crate) fn ensure_disjoint(teams: &[Team]) -> Result<()> {
+    for (i, team) in teams.iter().enumerate() {
+        for other in &teams[..i] {
+            if let Some(cus_id) = team
+                .members
+                .iter()
+                .find(|cus_id| other.members.binary_search(cus_id).is_ok())
+            {
+                return Err(BizError::Conflict(format!(
+                    "客户{cus_id}同时属于团体{}和{}",
+                    other.team_name, team.team_name
+                ))
+                .into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 替换团体明确指定的成员
+fn save_members(conn: &Connection, team_id: usize, cus_ids: &[usize]) -> Result<()> {
+    conn.execute("delete from ld_team_member where team_id=?", [team_id])?;
+    let mut exists = conn.prepare("select exists(select 1 from ld_custom where cus_id=?)")?;
+    let mut insert =
+        conn.prepare("insert or ignore into ld_team_member (team_id, cus_id) values (?, ?)")?;
+    for &cus_id in cus_ids {
+        if !exists.query_row([cus_id], |row| row.get::<_, bool>(0))? {
+            return Err(BizError::NotFound(format!("客户{cus_id}不存在")).into());
+        }
+        insert.execute([team_id, cus_id])?;
+    }
+    Ok(())
+}
+
+fn conflict(
+    res: r2d2_sqlite::rusqlite::Result<usize>,
+    act_id: usize,
+    args: &TeamArgs,
+) -> Result<()> {
+    match res {
+        Err(e) if e.sqlite_error_code() == Some(ErrorCode::ConstraintViolation) => Err(
+            BizError::Conflict(format!("活动{act_id}已存在团体{}", args.team_name.trim())).into(),
+        ),
+        res => Ok(res.map(|_| ())?),
+    }
+}
+
+pub(crate) fn create(conn: &mut Connection, act_id: usize, args: &TeamArgs) -> Result<Team> {
+    args.validate()?;
+    let tx = conn.transaction()?;
+    state::ensure_plan_editable(&tx, act_id)?;
+
+    let res = tx.execute(
+        "insert into ld_team (act_id, team_name, cus_flag, weight) values (?, ?, ?, ?)",
+        (
+            act_id,
+            args.team_name.trim(),
+            args.cus_flag(),
+            args.weight as i64,
+        ),
+    );
+    conflict(res, act_id, args)?;
+    let team_id = tx.last_insert_rowid() as usize;
+    save_members(&tx, team_id, &args.cus_ids)?;
+    ensure_disjoint(&list(&tx, act_id)?)?;
+    let team = get(&tx, act_id, team_id)?;
+    tx.commit()?;
+    info!(
+        "活动{act_id}新建团体{team_id} {}，成员{}人",
+        team.team_name,
+        team.members.len()
+    );
+
+    Ok(team)
+}
+
+pub(crate) fn update(
+    conn: &mut Connection,
+    act_id: usize,
+    team_id: usize,
+    args: &TeamArgs,
+) -> Result<Team> {
+    args.validate()?;
+    let tx = conn.transaction()?;
+    state::ensure_plan_editable(&tx, act_id)?;
+    get(&tx, act_id, team_id)?;
+
+    let res = tx.execute(
+        "update ld_team set team_name=?, cus_flag=?, weight=? where team_id=?",
+        (
+            args.team_name.trim(),
+            args.cus_flag(),
+            args.weight as i64,
+            team_id,
+        ),
+    );
+    conflict(res, act_id, args)?;
+    save_members(&tx, team_id, &args.cus_ids)?;
+    ensure_disjoint(&list(&tx, act_id)?)?;
+    let team = get(&tx, act_id, team_id)?;
+    tx.commit()?;
+    info!(
+        "活动{act_id}修改团体{team_id}，成员{}人",
+        team.members.len()
+    );
+
+    Ok(team)
+}
+
+pub(crate) fn remove(conn: &mut Connection, act_id: usize, team_id: usize) -> Result<()> {
+    let tx = conn.transaction()?;
+    state::ensure_plan_editable(&tx, act_id)?;
+    get(&tx, act_id, team_id)?;
+
+    tx.execute("delete from ld_team_member where team_id=?", [team_id])?;
+    tx.execute("delete from ld_team where team_id=?", [team_id])?;
+    tx.commit()?;
+    info!("活动{act_id}删除团体{team_id}");
+
+    Ok(())
+}
@@ -0,0 +1,444 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{params_from_iter, Connection, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::activity;
+
+/// 第一条审计记录的 prev_hash
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// 审计事件类型
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AuditEvent {
+    Draw,
+    Redraw,
+    Forfeit,
+    Promote,
+    ClaimNotify,
+    ClaimRedeem,
+    ClaimShip,
+    ClaimExpire,
+    Spin,
+    InstantWin,
+    Partition,
+}
+
+impl AuditEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            AuditEvent::Draw => "draw",
+            AuditEvent::Redraw => "redraw",
+            AuditEvent::Forfeit => "forfeit",
+            AuditEvent::Promote => "promote",
+            AuditEvent::ClaimNotify => "claim_notify",
+            AuditEvent::ClaimRedeem => "claim_redeem",
+            AuditEvent::ClaimShip => "claim_ship",
+            AuditEvent::ClaimExpire => "claim_expire",
+            AuditEvent::Spin => "spin",
+            AuditEvent::InstantWin => "instant_win",
+            AuditEvent::Partition => "partition",
+        }
+    }
+}
+
+/// ld_win_list 一行的完整快照，审计记录中保存事件发生后受影响行的状态
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+struct WinRow {
+    win_id: usize,
+    act_id: usize,
+    act_seq: usize,
+    cus_id: usize,
+    win_time: Option<i64>,
+    draw_id: Option<usize>,
+    win_status: i64,
+    forfeit_reason: Option<String>,
+    forfeit_time: Option<i64>,
+    forfeit_user: Option<usize>,
+    replaces: Option<usize>,
+    amount: Option<i64>,
+    team_id: Option<usize>,
+}
+
+const SELECT_WIN_ROW: &str =
+    "select win_id,act_id,act_seq,cus_id,win_time,draw_id,win_status,forfeit_reason,forfeit_time,
+            forfeit_user,replaces,amount,team_id
+       from ld_win_list";
+
+impl WinRow {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(WinRow {
+            win_id: row.get(0)?,
+            act_id: row.get(1)?,
+            act_seq: row.get(2)?,
+            cus_id: row.get(3)?,
+            win_time: row.get(4)?,
+            draw_id: row.get(5)?,
+            win_status: row.get(6)?,
+            forfeit_reason: row.get(7)?,
+            forfeit_time: row.get(8)?,
+            forfeit_user: row.get(9)?,
+            replaces: row.get(10)?,
+            amount: row.get(11)?,
+            team_id: row.get(12)?,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct Payload {
+    detail: Value,
+    rows: Vec<WinRow>,
+}
+
+/// 审计记录，对应 ld_audit 的一行
+#[derive(Debug, Serialize)]
+pub(crate) struct AuditEntry {
+    pub audit_id: usize,
+    pub event: String,
+    pub act_id: usize,
+    pub event_time: i64,
+    pub payload: String,
+    pub prev_hash: String,
+    pub entry_hash: String,
+}
+
+const SELECT_ENTRY: &str =
+    "select audit_id,event,act_id,event_time,payload,prev_hash,entry_hash from ld_audit";
+
+impl AuditEntry {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(AuditEntry {
+            audit_id: row.get(0)?,
+            event: row.get(1)?,
+            act_id: row.get(2)?,
+            event_time: row.get(3)?,
+            payload: row.get(4)?,
+            prev_hash: row.get(5)?,
+            entry_hash: row.get(6)?,
+        })
+    }
+
+    fn hash(&self) -> String {
+        entry_hash(
+            &self.prev_hash,
+            self.audit_id,
+            &self.event,
+            self.act_id,
+            self.event_time,
+            &self.payload,
+        )
+    }
+}
+
+/// SHA-256(prev_hash | audit_id | event | act_id | event_time | payload) 的十六进制
+fn entry_hash(
+    prev_hash: &str,
+    audit_id: usize,
+    event: &str,
+    act_id: usize,
+    event_time: i64,
+    payload: &str,
+) -> String {
+    let mut hasher = Sha256::new();
+    for part in [
+        prev_hash,
+        &audit_id.to_string(),
+        event,
+        &act_id.to_string(),
+        &event_time.to_string(),
+        payload,
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update(b"|");
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// 追加一条审计记录，必须在修改 ld_win_list 的同一个写事务中调用
+///
+/// `win_ids` 为受影响的中奖记录，会保存它们修改后的完整快照。
+pub(crate) fn append(
+    tx: &Connection,
+    event: AuditEvent,
+    act_id: usize,
+    win_ids: &[usize],
+    detail: Value,
+) -> Result<()> {
+    let rows = {
+        let placeholders = vec!["?"; win_ids.len()].join(",");
+        let sql = format!("{SELECT_WIN_ROW} where win_id in ({placeholders}) order by win_id");
+        let mut stmt = tx.prepare(&sql)?;
+        let mut rows = stmt.query(params_from_iter(win_ids))?;
+        let mut snapshot = Vec::with_capacity(win_ids.len());
+        while let Some(row) = rows.next()? {
+            snapshot.push(WinRow::from_row(row)?);
+        }
+        snapshot
+    };
+    let payload = serde_json::to_string(&Payload { detail, rows })?;
+
+    let (last_id, prev_hash) = tx.query_row(
+        "select ifnull(max(audit_id),0),
+                ifnull((select entry_hash from ld_audit order by audit_id desc limit 1), ?)
+           from ld_audit",
+        [GENESIS],
+        |row| Ok((row.get::<_, usize>(0)?, row.get::<_, String>(1)?)),
+    )?;
+    let audit_id = last_id + 1;
+    let event_time = time::OffsetDateTime::now_utc().unix_timestamp();
+    let entry_hash = entry_hash(
+        &prev_hash,
+        audit_id,
+        event.name(),
+        act_id,
+        event_time,
+        &payload,
+    );
+
+    tx.execute(
+        "insert into ld_audit (audit_id, event, act_id, event_time, payload, prev_hash, entry_hash)
+         values (?, ?, ?, ?, ?, ?, ?)",
+        (
+            audit_id,
+            event.name(),
+            act_id,
+            event_time,
+            &payload,
+            &prev_hash,
+            &entry_hash,
+        ),
+    )?;
+
+    Ok(())
+}
+
+/// 活动的审计记录
+pub(crate) fn list(conn: &Connection, act_id: usize) -> Result<Vec<AuditEntry>> {
+    activity::get(conn, act_id)?;
+    let sql = format!("{SELECT_ENTRY} where act_id=? order by audit_id");
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([act_id])?;
+
+    let mut entries = Vec::new();
+    while let Some(row) = rows.next()? {
+        entries.push(AuditEntry::from_row(row)?);
+    }
+
+    Ok(entries)
+}
+
+/// 哈希链中第一处断开的位置
+#[derive(Debug, Serialize)]
+pub(crate) struct BrokenLink {
+    pub audit_id: usize,
+    pub reason: String,
+}
+
+/// 与审计日志不一致的中奖记录
+#[derive(Debug, Serialize)]
+pub(crate) struct Mismatch {
+    pub win_id: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct AuditReport {
+    pub matched: bool,
+    /// 检查过的审计记录数
+    pub entries: usize,
+    pub broken: Option<BrokenLink>,
+    /// 按审计日志重放后与 ld_win_list 不一致的记录，哈希链断开时不比对
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// 从头校验哈希链，再用每条记录中的快照重放出 ld_win_list 应有的状态并与实际数据比对
+pub(crate) fn verify(conn: &Connection) -> Result<AuditReport> {
+    let mut expected = BTreeMap::<usize, WinRow>::new();
+    let mut report = AuditReport {
+        matched: false,
+        entries: 0,
+        broken: None,
+        mismatches: Vec::new(),
+    };
+
+    {
+        let sql = format!("{SELECT_ENTRY} order by audit_id");
+        let mut stmt = conn.prepare(&sql)?;
+        let mut rows = stmt.query([])?;
+        let mut prev_hash = GENESIS.to_owned();
+        while let Some(row) = rows.next()? {
+            let entry = AuditEntry::from_row(row)?;
+            let broken = |reason: &str| BrokenLink {
+                audit_id: entry.audit_id,
+                reason: reason.to_owned(),
+            };
+
+            let payload = serde_json::from_str::<Payload>(&entry.payload);
+            let link = if entry.audit_id != report.entries + 1 {
+                Some(broken("审计记录编号不连续，有记录被删除或插入"))
+            } else if entry.prev_hash != prev_hash {
+                Some(broken("prev_hash 与上一条记录的哈希不一致"))
+            } else if entry.hash() != entry.entry_hash {
+                Some(broken("记录内容与哈希不一致，已被修改"))
+            } else if payload.is_err() {
+                Some(broken("记录内容无法解析"))
+            } else {
+                None
+            };
+            if let Some(link) = link {
+                warn!("审计记录{}校验失败: {}", link.audit_id, link.reason);
+                report.broken = Some(link);
+                return Ok(report);
+            }
+
+            for win_row in payload?.rows {
+                expected.insert(win_row.win_id, win_row);
+            }
+            prev_hash = entry.entry_hash;
+            report.entries += 1;
+        }
+    }
+
+    let mut stmt = conn.prepare(&format!("{SELECT_WIN_ROW} order by win_id"))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let actual = WinRow::from_row(row)?;
+        let win_id = actual.win_id;
+        match expected.remove(&win_id) {
+            None => report.mismatches.push(Mismatch {
+                win_id,
+                reason: "没有对应的审计记录".to_owned(),
+            }),
+            Some(row) if row != actual => report.mismatches.push(Mismatch {
+                win_id,
+                reason: "与审计记录中的最新状态不一致".to_owned(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for win_id in expected.into_keys() {
+        report.mismatches.push(Mismatch {
+            win_id,
+            reason: "审计记录中存在，但已被删除".to_owned(),
+        });
+    }
+
+    report.matched = report.mismatches.is_empty();
+    info!(
+        "校验{}条审计记录，{}条中奖记录不一致",
+        report.entries,
+        report.mismatches.len()
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw::{self, seed};
+    use crate::test_db;
+
+    /// 抽奖中的活动 1 抽出 2 个中奖者和 1 个候补，每一步都写了审计记录
+    fn drawn() -> Connection {
+        let mut conn = test_db::open();
+        conn.execute_batch(
+            "insert into ld_activity (act_id, act_name, act_status) values (1, '年会', 3);
+             insert into ld_plan (act_id, act_seq, act_prize, prize_amount) values (1, 1, '一等奖', 2);",
+        )
+        .unwrap();
+        for cus_id in 1..=10 {
+            conn.execute(
+                "insert into ld_custom (cus_id, cus_nickname) values (?, ?)",
+                (cus_id, format!("客户{cus_id}")),
+            )
+            .unwrap();
+        }
+        seed::commit(&mut conn, 1, 1).unwrap();
+        draw::draw(&mut conn, 1, 1, None, 1).unwrap();
+        conn
+    }
+
+    fn first_win_id(conn: &Connection) -> usize {
+        conn.query_row("select min(win_id) from ld_win_list", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn verify_intact_chain() {
+        let conn = drawn();
+        let report = verify(&conn).unwrap();
+        assert!(report.matched);
+        assert!(report.entries > 0);
+        assert!(report.broken.is_none());
+        assert!(report.mismatches.is_empty());
+    }
+
+    #[test]
+    fn verify_reports_tampered_payload() {
+        let conn = drawn();
+        let audit_id: usize = conn
+            .query_row("select max(audit_id) from ld_audit", [], |row| row.get(0))
+            .unwrap();
+        //绕过只能追加的触发器直接改库
+        conn.execute_batch(
+            "drop trigger ld_audit_no_update;
+             update ld_audit set payload=replace(payload, '\"win_status\":0', '\"win_status\":1')
+              where audit_id=(select max(audit_id) from ld_audit);",
+        )
+        .unwrap();
+
+        let report = verify(&conn).unwrap();
+        assert!(!report.matched);
+        let broken = report.broken.unwrap();
+        assert_eq!(broken.audit_id, audit_id);
+        assert_eq!(broken.reason, "记录内容与哈希不一致，已被修改");
+        assert_eq!(report.entries, audit_id - 1);
+    }
+
+    #[test]
+    fn verify_reports_direct_win_list_edit() {
+        let conn = drawn();
+        let win_id = first_win_id(&conn);
+        conn.execute(
+            "update ld_win_list set cus_id=cus_id+100 where win_id=?",
+            [win_id],
+        )
+        .unwrap();
+
+        let report = verify(&conn).unwrap();
+        assert!(!report.matched);
+        assert!(report.broken.is_none());
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].win_id, win_id);
+        assert_eq!(report.mismatches[0].reason, "与审计记录中的最新状态不一致");
+    }
+
+    #[test]
+    fn verify_reports_inserted_and_deleted_rows() {
+        let conn = drawn();
+        let win_id = first_win_id(&conn);
+        conn.execute_batch(&format!(
+            "delete from ld_claim where win_id={win_id};
+             delete from ld_win_list where win_id={win_id};
+             insert into ld_win_list (act_id, act_seq, cus_id, win_status) values (1, 1, 10, 0);"
+        ))
+        .unwrap();
+
+        let report = verify(&conn).unwrap();
+        assert!(!report.matched);
+        let mut mismatched = report
+            .mismatches
+            .iter()
+            .map(|mismatch| mismatch.win_id)
+            .collect::<Vec<_>>();
+        mismatched.sort_unstable();
+        let inserted = conn.last_insert_rowid() as usize;
+        assert_eq!(mismatched, vec![win_id, inserted]);
+    }
+}
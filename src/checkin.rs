@@ -0,0 +1,162 @@
+use anyhow::{anyhow, Result};
+use r2d2_sqlite::rusqlite::{Connection, OptionalExtension, Row, TransactionBehavior};
+use serde::Serialize;
+use tracing::info;
+
+use crate::activity::state::{self, ActStatus};
+use crate::activity::{self, policy};
+use crate::error::BizError;
+
+/// 可以签到的活动状态
+const CHECKIN_OPEN: [ActStatus; 3] = [
+    ActStatus::Published,
+    ActStatus::RegistrationOpen,
+    ActStatus::Drawing,
+];
+
+/// 客户在活动中的签到信息，对应 ld_checkin 的一行
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Attendee {
+    pub act_id: usize,
+    pub cus_id: usize,
+    pub cus_nickname: String,
+    pub cus_name: Option<String>,
+    /// 二维码内容，扫码或打开自助签到链接时使用
+    pub token: String,
+    pub checkin_time: Option<i64>,
+    /// 代为签到的工作人员，自助签到时为空
+    pub checkin_user: Option<usize>,
+}
+
+const SELECT_ATTENDEE: &str =
+    "select k.act_id,k.cus_id,c.cus_nickname,c.cus_name,k.token,k.checkin_time,k.checkin_user
+       from ld_checkin k
+       join ld_custom c on c.cus_id=k.cus_id";
+
+impl Attendee {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Attendee {
+            act_id: row.get(0)?,
+            cus_id: row.get(1)?,
+            cus_nickname: row.get(2)?,
+            cus_name: row.get(3)?,
+            token: row.get(4)?,
+            checkin_time: row.get(5)?,
+            checkin_user: row.get(6)?,
+        })
+    }
+}
+
+fn new_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow!("生成签到码失败: {e}"))?;
+    Ok(hex::encode(bytes))
+}
+
+/// 为活动中还没有签到码的客户生成签到码，返回新生成的个数
+pub(crate) fn issue_tokens(conn: &mut Connection, act_id: usize) -> Result<usize> {
+    let tx = conn.transaction()?;
+    activity::get(&tx, act_id)?;
+    let missing = {
+        let mut stmt = tx.prepare(
+            "select cus_id from ld_custom
+              where cus_id not in (select cus_id from ld_checkin where act_id=?)",
+        )?;
+        let ids = stmt
+            .query_map([act_id], |row| row.get(0))?
+            .collect::<Result<Vec<usize>, _>>()?;
+        ids
+    };
+
+    {
+        let mut stmt =
+            tx.prepare("insert into ld_checkin (act_id, cus_id, token) values (?, ?, ?)")?;
+        for cus_id in &missing {
+            stmt.execute((act_id, cus_id, new_token()?))?;
+        }
+    }
+    tx.commit()?;
+    info!("活动{act_id}生成{}个签到码", missing.len());
+
+    Ok(missing.len())
+}
+
+/// 活动的签到名单，`checked_in` 为空时返回全部
+pub(crate) fn list(
+    conn: &Connection,
+    act_id: usize,
+    checked_in: Option<bool>,
+) -> Result<Vec<Attendee>> {
+    activity::get(conn, act_id)?;
+    let sql = format!(
+        "{SELECT_ATTENDEE} where k.act_id=?1 and (?2 is null or (k.checkin_time is not null)=?2)
+          order by k.cus_id"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query((act_id, checked_in))?;
+
+    let mut attendees = Vec::new();
+    while let Some(row) = rows.next()? {
+        attendees.push(Attendee::from_row(row)?);
+    }
+
+    Ok(attendees)
+}
+
+pub(crate) fn get(conn: &Connection, token: &str) -> Result<Attendee> {
+    let sql = format!("{SELECT_ATTENDEE} where k.token=?");
+    let attendee = conn
+        .query_row(&sql, [token.trim()], |row| Ok(Attendee::from_row(row)))
+        .optional()?;
+
+    match attendee {
+        Some(attendee) => attendee,
+        None => Err(BizError::NotFound("签到码不存在".to_owned()).into()),
+    }
+}
+
+/// 凭签到码签到，`user_id` 为空表示客户自助签到，需要活动允许
+///
+/// 重复签到保留第一次的签到时间。
+pub(crate) fn check_in(
+    conn: &mut Connection,
+    act_id: Option<usize>,
+    token: &str,
+    user_id: Option<usize>,
+) -> Result<Attendee> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let attendee = get(&tx, token)?;
+    if act_id.is_some_and(|act_id| act_id != attendee.act_id) {
+        return Err(BizError::Invalid("签到码不属于该活动".to_owned()).into());
+    }
+    state::ensure(&tx, attendee.act_id, &CHECKIN_OPEN, "签到")?;
+    if user_id.is_none() && !policy::get(&tx, attendee.act_id)?.self_checkin {
+        return Err(BizError::Conflict(format!("活动{}没有开放自助签到", attendee.act_id)).into());
+    }
+    if attendee.checkin_time.is_some() {
+        return Ok(attendee);
+    }
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    tx.execute(
+        "update ld_checkin set checkin_time=?, checkin_user=? where token=?",
+        (now, user_id, &attendee.token),
+    )?;
+    let attendee = get(&tx, &attendee.token)?;
+    tx.commit()?;
+    info!(
+        "活动{} 客户{}签到，工作人员{user_id:?}",
+        attendee.act_id, attendee.cus_id
+    );
+
+    Ok(attendee)
+}
+
+/// 自助签到页面查询签到信息，活动没有开放自助签到时不返回
+pub(crate) fn get_public(conn: &Connection, token: &str) -> Result<Attendee> {
+    let attendee = get(conn, token)?;
+    if !policy::get(conn, attendee.act_id)?.self_checkin {
+        return Err(BizError::Conflict(format!("活动{}没有开放自助签到", attendee.act_id)).into());
+    }
+    Ok(attendee)
+}
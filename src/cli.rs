@@ -0,0 +1,149 @@
+use anyhow::{bail, Result};
+use arc_swap::access::Access;
+use r2d2_sqlite::rusqlite::Connection;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::str::FromStr;
+
+use crate::audit;
+use crate::config::{Config, GLOBAL_CONFIG};
+use crate::custom::import::{self, Mapping};
+use crate::draw::engine::{Split, Strata};
+use crate::draw::simulate;
+use crate::draw::verify;
+
+const USAGE: &str = "用法:
+  luckydraw                                         启动 web 服务
+  luckydraw verify <act_id> <act_seq>               复算奖项的抽奖结果并与 ld_win_list 比对
+  luckydraw replay <algorithm> <seed> <count> <ids> [weights]
+                                                    用公开的种子和候选人 cus_id 列表离线复算
+  luckydraw replay-quota <algorithm> <seed> <count> <ids> <strata> [weights]
+                                                    按 ld_draw.quota 中冻结的配额约束（json）离线复算
+  luckydraw replay-split <seed> <budget> <shares> <share_min> <share_max>
+                                                    用公开的种子和拆分参数离线复算红包金额（分）
+  luckydraw import <file> [--dry-run] [字段=表头 ...]
+                                                    从 csv 或 xlsx 导入客户，如 cus_phone=联系电话
+  luckydraw simulate <act_id> [runs]                按当前配置模拟抽奖，默认 1000 次，不写入中奖名单
+  luckydraw audit-verify                            校验审计日志的哈希链，并与 ld_win_list 比对";
+
+/// 执行命令行子命令
+pub(crate) fn run(args: &[String]) -> Result<()> {
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["verify", act_id, act_seq] => {
+            let conn = open_db()?;
+            let verification = verify::verify(&conn, act_id.parse()?, act_seq.parse()?)?;
+            print_json(&verification)?;
+            if !verification.matched {
+                bail!("复算结果与中奖名单不一致");
+            }
+            Ok(())
+        }
+        ["replay", algorithm, seed, count, participants, weights @ ..] if weights.len() <= 1 => {
+            let participants = parse_list::<usize>(participants)?;
+            let weights = weights.first().map(|w| parse_list::<u64>(w)).transpose()?;
+            let winners = verify::replay(
+                algorithm,
+                &hex::decode(seed)?,
+                &participants,
+                weights.as_deref(),
+                count.parse()?,
+            )?;
+            print_json(&winners)
+        }
+        ["replay-quota", algorithm, seed, count, participants, strata, weights @ ..]
+            if weights.len() <= 1 =>
+        {
+            let participants = parse_list::<usize>(participants)?;
+            let strata = serde_json::from_str::<Strata>(strata)?;
+            let weights = weights.first().map(|w| parse_list::<u64>(w)).transpose()?;
+            let winners = verify::replay_stratified(
+                algorithm,
+                &hex::decode(seed)?,
+                &participants,
+                weights.as_deref(),
+                count.parse()?,
+                &strata,
+            )?;
+            print_json(&winners)
+        }
+        ["replay-split", seed, budget, shares, share_min, share_max] => {
+            let split = Split {
+                budget: budget.parse()?,
+                shares: shares.parse()?,
+                share_min: share_min.parse()?,
+                share_max: share_max.parse()?,
+            };
+            let amounts = verify::replay_split(&hex::decode(seed)?, &split)?;
+            print_json(&amounts)
+        }
+        ["import", file, options @ ..] => {
+            let mut dry_run = false;
+            let mut mapping = Mapping::default();
+            for option in options {
+                match option.split_once('=') {
+                    _ if *option == "--dry-run" => dry_run = true,
+                    Some((field, header)) => mapping.set(field, header.to_owned())?,
+                    None => bail!("{USAGE}"),
+                }
+            }
+            let mut conn = open_db()?;
+            let report = import::import(&mut conn, &std::fs::read(file)?, &mapping, dry_run)?;
+            print_json(&report)?;
+            if !report.errors.is_empty() {
+                bail!("导入文件中有{}个错误", report.errors.len());
+            }
+            Ok(())
+        }
+        ["simulate", act_id, runs @ ..] if runs.len() <= 1 => {
+            let runs = runs.first().map(|runs| runs.parse()).transpose()?;
+            let conn = open_db()?;
+            let simulation = simulate::simulate(&conn, act_id.parse()?, runs.unwrap_or(1000))?;
+            print_json(&simulation)
+        }
+        ["audit-verify"] => {
+            let conn = open_db()?;
+            let report = audit::verify(&conn)?;
+            print_json(&report)?;
+            if let Some(broken) = &report.broken {
+                bail!("审计记录{}校验失败: {}", broken.audit_id, broken.reason);
+            }
+            if !report.matched {
+                bail!("中奖名单与审计日志不一致");
+            }
+            Ok(())
+        }
+        _ => bail!("{USAGE}"),
+    }
+}
+
+fn open_db() -> Result<Connection> {
+    let sqlite_cfg = GLOBAL_CONFIG.map(|cfg: &Config| &cfg.sqlite).load();
+    Ok(Connection::open(&*sqlite_cfg.path)?)
+}
+
+/// cus_id 或权重列表，可以是 JSON 数组，也可以用逗号分隔
+fn parse_list<T>(list: &str) -> Result<Vec<T>>
+where
+    T: FromStr + DeserializeOwned,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    if list.trim_start().starts_with('[') {
+        return Ok(serde_json::from_str(list)?);
+    }
+    Ok(list
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::parse)
+        .collect::<Result<_, _>>()?)
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
@@ -27,6 +27,10 @@ pub(crate) struct Config {
     pub(crate) log: LogCfg,
     pub(crate) web: WebCfg,
     pub(crate) sqlite: SqliteCfg,
+    #[serde(default)]
+    pub(crate) export: ExportCfg,
+    #[serde(default)]
+    pub(crate) claim: ClaimCfg,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -75,3 +79,34 @@ impl Default for SqliteCfg {
         }
     }
 }
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ExportCfg {
+    /// 导出 pdf 时嵌入的中文字体
+    pub(crate) font: String,
+}
+
+impl Default for ExportCfg {
+    fn default() -> Self {
+        ExportCfg {
+            font: "simkai.ttf".to_owned(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ClaimCfg {
+    /// 中奖后领奖的期限天数
+    pub(crate) deadline_days: i64,
+    /// 逾期未领奖时作废中奖记录，空出的名额可以递补或重抽
+    pub(crate) forfeit_expired: bool,
+}
+
+impl Default for ClaimCfg {
+    fn default() -> Self {
+        ClaimCfg {
+            deadline_days: 30,
+            forfeit_expired: true,
+        }
+    }
+}
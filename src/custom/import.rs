@@ -0,0 +1,397 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+
+use anyhow::Result;
+use calamine::{open_workbook_from_rs, Reader, Xlsx};
+use r2d2_sqlite::rusqlite::{Connection, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::error::BizError;
+
+/// 导入文件大小上限
+pub(crate) const MAX_FILE_SIZE: usize = 20 * 1024 * 1024;
+
+/// 可以导入的 ld_custom 字段
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub(crate) enum Field {
+    #[serde(rename = "cus_nickname")]
+    Nickname,
+    #[serde(rename = "cus_name")]
+    Name,
+    #[serde(rename = "cus_phone")]
+    Phone,
+    #[serde(rename = "cus_identity")]
+    Identity,
+    #[serde(rename = "cus_flag")]
+    Flag,
+}
+
+impl Field {
+    const ALL: [Field; 5] = [
+        Field::Nickname,
+        Field::Name,
+        Field::Phone,
+        Field::Identity,
+        Field::Flag,
+    ];
+
+    fn column(&self) -> &'static str {
+        match self {
+            Field::Nickname => "cus_nickname",
+            Field::Name => "cus_name",
+            Field::Phone => "cus_phone",
+            Field::Identity => "cus_identity",
+            Field::Flag => "cus_flag",
+        }
+    }
+
+    /// 没有指定列映射时，按这些表头自动识别
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Field::Nickname => &["昵称", "工号", "编号"],
+            Field::Name => &["姓名", "名字"],
+            Field::Phone => &["手机", "手机号", "手机号码", "电话", "联系电话"],
+            Field::Identity => &["身份证", "身份证号", "证件号", "证件号码"],
+            Field::Flag => &["标签", "部门", "分组"],
+        }
+    }
+}
+
+/// 列映射：字段对应的表头名称
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct Mapping {
+    pub cus_nickname: Option<String>,
+    pub cus_name: Option<String>,
+    pub cus_phone: Option<String>,
+    pub cus_identity: Option<String>,
+    pub cus_flag: Option<String>,
+}
+
+impl Mapping {
+    fn get(&self, field: Field) -> Option<&str> {
+        match field {
+            Field::Nickname => self.cus_nickname.as_deref(),
+            Field::Name => self.cus_name.as_deref(),
+            Field::Phone => self.cus_phone.as_deref(),
+            Field::Identity => self.cus_identity.as_deref(),
+            Field::Flag => self.cus_flag.as_deref(),
+        }
+    }
+
+    /// 按字段名设置映射，供命令行使用
+    pub(crate) fn set(&mut self, column: &str, header: String) -> Result<()> {
+        let slot = match column {
+            "cus_nickname" => &mut self.cus_nickname,
+            "cus_name" => &mut self.cus_name,
+            "cus_phone" => &mut self.cus_phone,
+            "cus_identity" => &mut self.cus_identity,
+            "cus_flag" => &mut self.cus_flag,
+            _ => return Err(BizError::Invalid(format!("未知的字段: {column}")).into()),
+        };
+        *slot = Some(header);
+        Ok(())
+    }
+
+    /// 确定每个字段在表格中的列号
+    fn resolve(&self, headers: &[String]) -> Result<HashMap<Field, usize>> {
+        let find = |name: &str| headers.iter().position(|header| header.trim() == name);
+
+        let mut columns = HashMap::new();
+        for field in Field::ALL {
+            let index = match self.get(field) {
+                Some(header) => Some(find(header).ok_or_else(|| {
+                    BizError::Invalid(format!("表格中没有{}对应的列: {header}", field.column()))
+                })?),
+                None => std::iter::once(field.column())
+                    .chain(field.aliases().iter().copied())
+                    .find_map(find),
+            };
+            if let Some(index) = index {
+                columns.insert(field, index);
+            }
+        }
+
+        if !columns.contains_key(&Field::Nickname) {
+            return Err(BizError::Invalid(format!(
+                "没有找到 cus_nickname 对应的列，表头为: {}",
+                headers.join(",")
+            ))
+            .into());
+        }
+        Ok(columns)
+    }
+}
+
+/// 某一行的校验错误，`row` 为表格中的行号（表头为第 1 行）
+#[derive(Debug, Serialize)]
+pub(crate) struct RowError {
+    pub row: usize,
+    pub field: Option<Field>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ImportReport {
+    pub dry_run: bool,
+    /// 字段与表头的对应关系
+    pub columns: HashMap<Field, String>,
+    pub total: usize,
+    pub valid: usize,
+    pub errors: Vec<RowError>,
+    /// 没有错误且不是试运行时才会写入
+    pub committed: bool,
+    pub inserted: usize,
+}
+
+struct CustomRow {
+    cus_nickname: String,
+    cus_name: Option<String>,
+    cus_phone: Option<i64>,
+    cus_identity: Option<String>,
+    cus_flag: Option<String>,
+}
+
+/// 读取 xlsx 的第一个工作表或 utf-8 编码的 csv，返回包括表头在内的所有行
+pub(crate) fn read_table(data: &[u8]) -> Result<Vec<Vec<String>>> {
+    //xlsx 是 zip 格式
+    if data.starts_with(b"PK\x03\x04") {
+        let mut workbook: Xlsx<_> = open_workbook_from_rs(Cursor::new(data))
+            .map_err(|e| BizError::Invalid(format!("xlsx 文件无法解析: {e}")))?;
+        let range = workbook
+            .worksheet_range_at(0)
+            .ok_or_else(|| BizError::Invalid("xlsx 文件没有工作表".to_owned()))?
+            .map_err(|e| BizError::Invalid(format!("xlsx 工作表无法解析: {e}")))?;
+        return Ok(range
+            .rows()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect());
+    }
+
+    let data = data.strip_prefix("\u{feff}".as_bytes()).unwrap_or(data);
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data);
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| BizError::Invalid(format!("csv 文件无法解析: {e}")))?;
+        rows.push(record.iter().map(str::to_owned).collect());
+    }
+    Ok(rows)
+}
+
+/// 手机号只保留数字，去掉 +86 前缀后必须是 1 开头的 11 位数字
+fn parse_phone(phone: &str) -> Option<i64> {
+    let digits = phone
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-'))
+        .collect::<String>();
+    let digits = digits.strip_prefix("+86").unwrap_or(&digits);
+    if digits.len() == 11 && digits.starts_with('1') && digits.chars().all(|c| c.is_ascii_digit()) {
+        digits.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// 身份证号为 18 位（最后一位可以是 X）或老式的 15 位数字
+fn check_identity(identity: &str) -> bool {
+    //先排除非 ASCII 字符，下面按字节切分才不会落在多字节字符中间
+    if !identity.is_ascii() {
+        return false;
+    }
+    match identity.len() {
+        15 => identity.bytes().all(|b| b.is_ascii_digit()),
+        18 => {
+            let (body, last) = identity.split_at(17);
+            body.bytes().all(|b| b.is_ascii_digit())
+                && last
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || b == b'X' || b == b'x')
+        }
+        _ => false,
+    }
+}
+
+/// 导入客户，先校验所有行，没有错误且不是试运行时在一个事务中全部写入
+pub(crate) fn import(
+    conn: &mut Connection,
+    data: &[u8],
+    mapping: &Mapping,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let mut table = read_table(data)?.into_iter();
+    let headers = table
+        .next()
+        .ok_or_else(|| BizError::Invalid("表格是空的".to_owned()))?;
+    let columns = mapping.resolve(&headers)?;
+
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let existing = {
+        let mut stmt = tx.prepare("select cus_nickname from ld_custom")?;
+        let nicknames = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<HashSet<_>, _>>()?;
+        nicknames
+    };
+
+    let mut report = ImportReport {
+        dry_run,
+        columns: columns
+            .iter()
+            .map(|(&field, &index)| (field, headers[index].trim().to_owned()))
+            .collect(),
+        total: 0,
+        valid: 0,
+        errors: Vec::new(),
+        committed: false,
+        inserted: 0,
+    };
+    let mut seen = HashMap::new();
+    let mut customs = Vec::new();
+    for (index, cells) in table.enumerate() {
+        let row = index + 2;
+        let cell = |field: Field| {
+            columns
+                .get(&field)
+                .and_then(|&i| cells.get(i))
+                .map(|cell| cell.trim())
+                .filter(|cell| !cell.is_empty())
+        };
+        //跳过空行
+        if cells.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        report.total += 1;
+
+        let errors = report.errors.len();
+        let mut error = |field: Field, message: String| {
+            report.errors.push(RowError {
+                row,
+                field: Some(field),
+                message,
+            })
+        };
+
+        let cus_nickname = cell(Field::Nickname).unwrap_or_default().to_owned();
+        if cus_nickname.is_empty() {
+            error(Field::Nickname, "昵称不能为空".to_owned());
+        } else if existing.contains(&cus_nickname) {
+            error(Field::Nickname, format!("昵称{cus_nickname}已存在"));
+        } else if let Some(first) = seen.insert(cus_nickname.clone(), row) {
+            error(
+                Field::Nickname,
+                format!("昵称{cus_nickname}与第{first}行重复"),
+            );
+        }
+
+        let cus_phone = match cell(Field::Phone) {
+            Some(phone) => {
+                let parsed = parse_phone(phone);
+                if parsed.is_none() {
+                    error(Field::Phone, format!("手机号{phone}格式错误"));
+                }
+                parsed
+            }
+            None => None,
+        };
+
+        let cus_identity = cell(Field::Identity).map(str::to_uppercase);
+        if let Some(identity) = &cus_identity {
+            if !check_identity(identity) {
+                error(Field::Identity, format!("证件号{identity}格式错误"));
+            }
+        }
+
+        if report.errors.len() == errors {
+            report.valid += 1;
+            customs.push(CustomRow {
+                cus_nickname,
+                cus_name: cell(Field::Name).map(str::to_owned),
+                cus_phone,
+                cus_identity,
+                cus_flag: cell(Field::Flag).map(str::to_owned),
+            });
+        }
+    }
+    info!(
+        "导入客户 共{}行，有效{}行，错误{}个",
+        report.total,
+        report.valid,
+        report.errors.len()
+    );
+
+    if dry_run || !report.errors.is_empty() {
+        return Ok(report);
+    }
+
+    {
+        let mut stmt = tx.prepare(
+            "insert into ld_custom (cus_nickname, cus_name, cus_phone, cus_identity, cus_flag)
+             values (?, ?, ?, ?, ?)",
+        )?;
+        for custom in &customs {
+            stmt.execute((
+                &custom.cus_nickname,
+                &custom.cus_name,
+                custom.cus_phone,
+                &custom.cus_identity,
+                &custom.cus_flag,
+            ))?;
+        }
+    }
+    tx.commit()?;
+    report.committed = true;
+    report.inserted = customs.len();
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_phone_accepts_mainland_mobile() {
+        assert_eq!(parse_phone("13800138000"), Some(13800138000));
+        assert_eq!(parse_phone("+86 138-0013-8000"), Some(13800138000));
+        assert_eq!(parse_phone("+8613800138000"), Some(13800138000));
+    }
+
+    #[test]
+    fn parse_phone_rejects_invalid() {
+        assert_eq!(parse_phone(""), None);
+        assert_eq!(parse_phone("23800138000"), None);
+        assert_eq!(parse_phone("1380013800"), None);
+        assert_eq!(parse_phone("138001380001"), None);
+        assert_eq!(parse_phone("1380013800a"), None);
+        assert_eq!(parse_phone("１３８００１３８０００"), None);
+    }
+
+    #[test]
+    fn check_identity_accepts_valid() {
+        assert!(check_identity("110105194912310021"));
+        assert!(check_identity("11010519491231002X"));
+        assert!(check_identity("11010519491231002x"));
+        assert!(check_identity("110105491231002"));
+    }
+
+    #[test]
+    fn check_identity_rejects_invalid() {
+        assert!(!check_identity(""));
+        assert!(!check_identity("11010519491231002Y"));
+        assert!(!check_identity("1101051949123100X1"));
+        assert!(!check_identity("11010549123100X"));
+        assert!(!check_identity("11010519491231002"));
+    }
+
+    #[test]
+    fn check_identity_rejects_multibyte_without_panic() {
+        //18 字节但最后一个字符是多字节的
+        assert!(!check_identity("110105194912310中"));
+        assert!(!check_identity("110105194912中"));
+        assert!(!check_identity("中"));
+    }
+}
@@ -0,0 +1,840 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::draw::rng::DrawRng;
+use crate::error::BizError;
+
+/// 等概率抽奖的算法版本
+pub(crate) const UNIFORM: &str = "sha256-ctr-v1";
+/// 按权重不放回抽奖的算法版本
+pub(crate) const WEIGHTED: &str = "sha256-ctr-weighted-v1";
+/// 红包拆分金额的算法版本
+pub(crate) const SPLIT: &str = "sha256-ctr-split-v1";
+/// 按配额筛选中奖者的算法版本
+pub(crate) const QUOTA: &str = "quota-greedy-v1";
+/// 随机分组的算法版本
+pub(crate) const PARTITION: &str = "sha256-ctr-partition-v1";
+/// 随机分组第一次放置失败时重试使用的随机序列
+const PARTITION_RETRY: &str = "sha256-ctr-partition-retry-v1";
+
+/// 从候选人中不放回地随机抽取 `count` 个（部分 Fisher-Yates 洗牌）
+///
+/// 第 i 轮在 `[i, len)` 中取随机下标与 i 交换，前 `count` 个即为中奖者。
+/// 候选人不足时返回全部候选人，调用方负责事先校验数量。
+pub(crate) fn pick<T>(mut candidates: Vec<T>, count: usize, rng: &mut DrawRng) -> Vec<T> {
+    let count = count.min(candidates.len());
+    for i in 0..count {
+        let j = i + rng.below((candidates.len() - i) as u64) as usize;
+        candidates.swap(i, j);
+    }
+    candidates.truncate(count);
+    candidates
+}
+
+/// 按权重不放回地随机抽取 `count` 个
+///
+/// 每一轮在 `[0, 剩余总权重)` 中取随机数，按候选人顺序累加权重找到落点，
+/// 选中的候选人用 `swap_remove` 移出后进入下一轮。权重为 0 的候选人不会被抽中。
+pub(crate) fn pick_weighted<T>(
+    mut candidates: Vec<(T, u64)>,
+    count: usize,
+    rng: &mut DrawRng,
+) -> Vec<T> {
+    let mut total = candidates.iter().map(|(_, weight)| weight).sum::<u64>();
+    let mut winners = Vec::with_capacity(count.min(candidates.len()));
+    while winners.len() < count && total > 0 {
+        let mut point = rng.below(total);
+        let index = candidates
+            .iter()
+            .position(|(_, weight)| {
+                if point < *weight {
+                    return true;
+                }
+                point -= weight;
+                false
+            })
+            .expect("随机落点一定在总权重之内");
+
+        let (winner, weight) = candidates.swap_remove(index);
+        total -= weight;
+        winners.push(winner);
+    }
+    winners
+}
+
+/// 按算法版本从候选人 cus_id 中抽取中奖者，抽奖和复算都走这里
+///
+/// `weights` 与 `participants` 一一对应，只有按权重抽奖时需要。
+pub(crate) fn select(
+    algorithm: &str,
+    seed: &[u8],
+    participants: &[usize],
+    weights: Option<&[u64]>,
+    count: usize,
+) -> Result<Vec<usize>> {
+    let mut rng = DrawRng::new(seed);
+    match (algorithm, weights) {
+        (UNIFORM, _) => Ok(pick(participants.to_vec(), count, &mut rng)),
+        (WEIGHTED, Some(weights)) if weights.len() == participants.len() => {
+            let candidates = participants.iter().copied().zip(weights.iter().copied());
+            Ok(pick_weighted(candidates.collect(), count, &mut rng))
+        }
+        (WEIGHTED, _) => Err(BizError::Invalid("候选人权重与候选人列表不匹配".to_owned()).into()),
+        _ => Err(BizError::Invalid(format!("不支持的抽奖算法: {algorithm}")).into()),
+    }
+}
+
+/// 一个标签分组在本次抽奖时的配额，人数都已扣除该分组已有的中奖者
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct Stratum {
+    pub cus_flag: String,
+    /// 候选人中带该标签的 cus_id，升序
+    pub members: Vec<usize>,
+    /// 还至少需要的中奖人数
+    pub need: usize,
+    /// 还最多可以中奖的人数，为空表示不限
+    pub cap: Option<usize>,
+}
+
+/// 按配额抽奖的约束
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct Strata {
+    /// 本次抽奖前奖项剩余的名额
+    pub slots: usize,
+    /// 本次抽出的中奖人数，其余为候补
+    pub winners: usize,
+    pub strata: Vec<Stratum>,
+}
+
+impl Strata {
+    /// 客户属于哪些分组，与 `strata` 一一对应
+    fn groups(&self, cus_id: usize) -> Vec<bool> {
+        self.strata
+            .iter()
+            .map(|stratum| stratum.members.binary_search(&cus_id).is_ok())
+            .collect()
+    }
+
+    /// 加入一个属于 `groups` 的中奖者后是否超过某个分组的上限
+    fn within_cap(&self, taken: &[usize], groups: &[bool]) -> bool {
+        self.strata
+            .iter()
+            .zip(taken.iter().zip(groups))
+            .all(|(stratum, (&taken, &member))| {
+                stratum.cap.is_none_or(|cap| taken + member as usize <= cap)
+            })
+    }
+
+    /// 在 `slots` 个名额内能否用 `available` 中的客户补齐各分组还差的最少人数
+    ///
+    /// `available` 为各种分组组合的剩余人数。每次取能补上最多分组、且不超过上限的一人，
+    /// 一样多时优先取占用有上限分组较少的。
+    ///
+    /// 这是贪心检查，标签重叠且有上限时可能把能补齐的情况判为不能补齐，抽奖会报配额无法满足。
+    /// 检查方式是 [`QUOTA`] 算法版本的一部分，改动会让已有的抽奖记录无法复算。
+    fn completable(
+        &self,
+        mut taken: Vec<usize>,
+        mut slots: usize,
+        mut available: BTreeMap<Vec<bool>, usize>,
+    ) -> bool {
+        loop {
+            let short = |taken: &[usize], i: usize| taken[i] < self.strata[i].need;
+            if (0..self.strata.len()).all(|i| !short(&taken, i)) {
+                return true;
+            }
+            if slots == 0 {
+                return false;
+            }
+
+            let best = available
+                .iter()
+                .filter(|(groups, &count)| count > 0 && self.within_cap(&taken, groups))
+                .map(|(groups, _)| {
+                    let gain = (0..groups.len())
+                        .filter(|&i| groups[i] && short(&taken, i))
+                        .count();
+                    let capped = (0..groups.len())
+                        .filter(|&i| groups[i] && self.strata[i].cap.is_some())
+                        .count();
+                    (gain, std::cmp::Reverse(capped), groups)
+                })
+                .filter(|(gain, _, _)| *gain > 0)
+                .max_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)))
+                .map(|(_, _, groups)| groups.clone());
+            let Some(groups) = best else {
+                return false;
+            };
+
+            for (taken, member) in taken.iter_mut().zip(&groups) {
+                *taken += *member as usize;
+            }
+            if let Some(count) = available.get_mut(&groups) {
+                *count -= 1;
+            }
+            slots -= 1;
+        }
+    }
+}
+
+/// 按配额从抽取顺序中挑出中奖者和候补
+///
+/// 每次从头找第一个满足条件的候选人：加入后每个分组都不超过上限，
+/// 并且剩余的候选人在剩余名额内仍能补齐各分组还差的最少人数。
+/// 一个客户有多个标签时同时计入每个分组。候补只检查上限。
+/// 结果不足 `draw_count` 个时说明配额无法满足，由调用方报错。
+pub(crate) fn stratify(order: &[usize], draw_count: usize, strata: &Strata) -> Vec<usize> {
+    let mut taken = vec![0; strata.strata.len()];
+    let mut picked = Vec::with_capacity(draw_count);
+    let mut rest = order
+        .iter()
+        .map(|&cus_id| (cus_id, strata.groups(cus_id)))
+        .collect::<Vec<_>>();
+
+    while picked.len() < strata.winners {
+        let Some(slots_left) = strata.slots.checked_sub(picked.len() + 1) else {
+            break;
+        };
+        let mut available = BTreeMap::<Vec<bool>, usize>::new();
+        for (_, groups) in &rest {
+            *available.entry(groups.clone()).or_default() += 1;
+        }
+
+        //同一种分组组合的结果相同，每种只判断一次
+        let mut admits = BTreeMap::<&[bool], bool>::new();
+        let position = rest.iter().position(|(_, groups)| {
+            *admits.entry(groups).or_insert_with(|| {
+                if !strata.within_cap(&taken, groups) {
+                    return false;
+                }
+                let mut after = taken.clone();
+                for (taken, member) in after.iter_mut().zip(groups) {
+                    *taken += *member as usize;
+                }
+                let mut available = available.clone();
+                if let Some(count) = available.get_mut(groups) {
+                    *count -= 1;
+                }
+                strata.completable(after, slots_left, available)
+            })
+        });
+        let Some(position) = position else {
+            break;
+        };
+
+        let (cus_id, groups) = rest.remove(position);
+        for (taken, member) in taken.iter_mut().zip(&groups) {
+            *taken += *member as usize;
+        }
+        picked.push(cus_id);
+    }
+    if picked.len() < strata.winners {
+        return picked;
+    }
+
+    let alternates = rest
+        .into_iter()
+        .filter(|(_, groups)| strata.within_cap(&taken, groups))
+        .map(|(cus_id, _)| cus_id)
+        .take(draw_count.saturating_sub(strata.winners));
+    picked.extend(alternates);
+    picked
+}
+
+/// 先按算法版本排出全部候选人的抽取顺序，再按配额筛选
+///
+/// 配额没有起作用时结果与 [`select`] 抽取 `draw_count` 个完全相同。
+pub(crate) fn select_stratified(
+    algorithm: &str,
+    seed: &[u8],
+    participants: &[usize],
+    weights: Option<&[u64]>,
+    draw_count: usize,
+    strata: &Strata,
+) -> Result<Vec<usize>> {
+    let order = select(algorithm, seed, participants, weights, participants.len())?;
+    Ok(stratify(&order, draw_count, strata))
+}
+
+/// 红包拆分的参数，金额单位为分
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct Split {
+    /// 待拆分的总金额
+    pub budget: i64,
+    /// 拆分的份数
+    pub shares: usize,
+    pub share_min: i64,
+    pub share_max: i64,
+}
+
+impl Split {
+    /// 每份都在上下限之内时总金额能否恰好分完
+    pub(crate) fn feasible(&self) -> bool {
+        let shares = self.shares as i64;
+        let (Some(low), Some(high)) = (
+            self.share_min.checked_mul(shares),
+            self.share_max.checked_mul(shares),
+        ) else {
+            return false;
+        };
+        shares > 0
+            && 0 <= self.share_min
+            && self.share_min <= self.share_max
+            && low <= self.budget
+            && self.budget <= high
+    }
+}
+
+/// 把 `budget` 随机拆成 `shares` 份，每份在 `[share_min, share_max]` 之间，总和恰好等于 `budget`
+///
+/// 随机数来自以 `seed || "sha256-ctr-split-v1"` 为种子的 [`DrawRng`]，与抽取中奖者的随机序列互不影响。
+/// 按二倍均值法依次取值：每份在保证剩余份数仍能分完的区间内均匀取值，且不超过剩余平均值的两倍，
+/// 最后一份取剩下的全部金额。
+pub(crate) fn split(seed: &[u8], split: &Split) -> Result<Vec<i64>> {
+    if !split.feasible() {
+        return Err(BizError::Invalid(format!(
+            "{}分无法拆成{}份{}~{}分的红包",
+            split.budget, split.shares, split.share_min, split.share_max
+        ))
+        .into());
+    }
+
+    let mut rng = DrawRng::new(&[seed, SPLIT.as_bytes()].concat());
+    let mut rest = split.budget;
+    let mut amounts = Vec::with_capacity(split.shares);
+    for left in (2..=split.shares as i64).rev() {
+        let low = split.share_min.max(rest - (left - 1) * split.share_max);
+        let high = split
+            .share_max
+            .min(rest - (left - 1) * split.share_min)
+            .min(2 * rest / left)
+            .max(low);
+        let amount = low + rng.below((high - low + 1) as u64) as i64;
+        amounts.push(amount);
+        rest -= amount;
+    }
+    amounts.push(rest);
+    Ok(amounts)
+}
+
+/// 带有某个标签的一组参与者，用于随机分组的约束
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct FlagGroup {
+    pub cus_flag: String,
+    /// 带有该标签的参与者 cus_id，升序
+    pub members: Vec<usize>,
+}
+
+/// 随机分组的输入，分组时冻结保存，复算时原样使用
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Layout {
+    /// 参与分组的 cus_id，升序
+    pub participants: Vec<usize>,
+    /// 各组的容量，按组的下标排列
+    pub capacities: Vec<usize>,
+    /// 每个标签的成员必须分在同一组，有公共成员的标签会合并成一组
+    pub together: Vec<FlagGroup>,
+    /// 每个标签的成员尽量分散，每组最多 ⌈成员数 / 组数⌉ 人
+    pub apart: Vec<FlagGroup>,
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// 把参与者随机分到各组，返回与 `participants` 一一对应的组下标
+///
+/// 先用 [`pick`] 把参与者的下标完整洗牌一次，需要在一起的参与者合并为一个单元，
+/// 单元按第一个成员在洗牌结果中的位置排列，再按人数从多到少稳定排序。
+/// 然后依次为每个单元在放得下、且不会让分散标签超过上限的组中选剩余容量最多的，
+/// 有多个时用同一个随机数发生器等概率选择。
+///
+/// 这样逐个放置失败时，改为先放分散标签成员多的单元再试一次，组之间的平局改用以
+/// `seed || "sha256-ctr-partition-retry-v1"` 为种子的随机数发生器选择；仍失败时报错，不会放宽约束。
+pub(crate) fn partition(seed: &[u8], layout: &Layout) -> Result<Vec<usize>> {
+    let participants = &layout.participants;
+    if layout.capacities.is_empty() {
+        return Err(BizError::Invalid("至少需要一个组".to_owned()).into());
+    }
+    let capacity = layout.capacities.iter().sum::<usize>();
+    if participants.len() > capacity {
+        return Err(BizError::Conflict(format!(
+            "参与人数{}超过了各组的总容量{capacity}",
+            participants.len()
+        ))
+        .into());
+    }
+
+    let mut parent = (0..participants.len()).collect::<Vec<_>>();
+    for group in &layout.together {
+        let mut members = group
+            .members
+            .iter()
+            .filter_map(|cus_id| participants.binary_search(cus_id).ok());
+        if let Some(first) = members.next() {
+            for member in members {
+                let (a, b) = (find(&mut parent, first), find(&mut parent, member));
+                parent[b] = a;
+            }
+        }
+    }
+
+    let mut rng = DrawRng::new(seed);
+    let order = pick(
+        (0..participants.len()).collect(),
+        participants.len(),
+        &mut rng,
+    );
+    let mut units = Vec::<Vec<usize>>::new();
+    let mut unit_of = BTreeMap::<usize, usize>::new();
+    for i in order {
+        let root = find(&mut parent, i);
+        let unit = *unit_of.entry(root).or_insert_with(|| {
+            units.push(Vec::new());
+            units.len() - 1
+        });
+        units[unit].push(i);
+    }
+    units.sort_by_key(|unit| std::cmp::Reverse(unit.len()));
+
+    let caps = layout
+        .apart
+        .iter()
+        .map(|group| group.members.len().div_ceil(layout.capacities.len()))
+        .collect::<Vec<_>>();
+    //每个单元在各分散标签中的人数
+    let needs = units
+        .iter()
+        .map(|unit| {
+            layout
+                .apart
+                .iter()
+                .map(|group| {
+                    unit.iter()
+                        .filter(|&&i| group.members.binary_search(&participants[i]).is_ok())
+                        .count()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let order = (0..units.len()).collect::<Vec<_>>();
+    if let Ok(buckets) = place(layout, &units, &needs, &caps, &order, &mut rng) {
+        return Ok(buckets);
+    }
+    //先放分散标签成员多的单元，它们可选的组最少
+    let mut order = order;
+    order.sort_by_key(|&u| std::cmp::Reverse(needs[u].iter().sum::<usize>()));
+    let mut rng = DrawRng::new(&[seed, PARTITION_RETRY.as_bytes()].concat());
+    place(layout, &units, &needs, &caps, &order, &mut rng).map_err(|u| {
+        BizError::Conflict(format!(
+            "客户{}所在的{}人找不到容量足够且满足分散要求的组。\
+             分组逐个放置，放不下时不会回头调整已放好的人，可以增加组的容量或减少约束后重试",
+            participants[units[u][0]],
+            units[u].len()
+        ))
+        .into()
+    })
+}
+
+/// 按 `order` 的顺序为每个单元在放得下、且不会让分散标签超过上限的组中选剩余容量最多的，
+/// 有多个时等概率选择，返回每个参与者的组下标，放不下时返回该单元的下标
+fn place(
+    layout: &Layout,
+    units: &[Vec<usize>],
+    needs: &[Vec<usize>],
+    caps: &[usize],
+    order: &[usize],
+    rng: &mut DrawRng,
+) -> std::result::Result<Vec<usize>, usize> {
+    let mut remaining = layout.capacities.clone();
+    let mut counts = vec![vec![0usize; caps.len()]; remaining.len()];
+    let mut buckets = vec![0; layout.participants.len()];
+    for &u in order {
+        let (unit, need) = (&units[u], &needs[u]);
+        let feasible = (0..remaining.len())
+            .filter(|&b| {
+                remaining[b] >= unit.len()
+                    && counts[b]
+                        .iter()
+                        .zip(need)
+                        .zip(caps)
+                        .all(|((count, need), cap)| count + need <= *cap)
+            })
+            .collect::<Vec<_>>();
+        let most = feasible.iter().map(|&b| remaining[b]).max().ok_or(u)?;
+        let ties = feasible
+            .into_iter()
+            .filter(|&b| remaining[b] == most)
+            .collect::<Vec<_>>();
+        let bucket = match ties.len() {
+            1 => ties[0],
+            n => ties[rng.below(n as u64) as usize],
+        };
+
+        remaining[bucket] -= unit.len();
+        for (count, need) in counts[bucket].iter_mut().zip(need) {
+            *count += need;
+        }
+        for &i in unit {
+            buckets[i] = bucket;
+        }
+    }
+
+    Ok(buckets)
+}
+
+/// 估算按权重不放回抽取 `count` 个时每个候选人的中奖概率
+///
+/// 精确值需要枚举抽取顺序，这里用 Rosén 近似：`p_i = 1 - exp(-w_i * t)`，
+/// 其中 t 满足 `Σ p_i = count`，用二分法求解。权重都相同时结果等于 `count / n`。
+pub(crate) fn win_probabilities(weights: &[u64], count: usize) -> Vec<f64> {
+    let positive = weights.iter().filter(|&&w| w > 0).count();
+    if count >= positive {
+        return weights
+            .iter()
+            .map(|&w| if w > 0 { 1.0 } else { 0.0 })
+            .collect();
+    }
+
+    let expected = |t: f64| {
+        weights
+            .iter()
+            .map(|&w| 1.0 - (-(w as f64) * t).exp())
+            .sum::<f64>()
+    };
+    let (mut low, mut high) = (0.0, 1.0);
+    while expected(high) < count as f64 {
+        high *= 2.0;
+    }
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        if expected(mid) < count as f64 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    weights
+        .iter()
+        .map(|&w| 1.0 - (-(w as f64) * high).exp())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed(i: u32) -> Vec<u8> {
+        i.to_be_bytes().to_vec()
+    }
+
+    #[test]
+    fn split_sums_to_budget_within_bounds() {
+        let split = Split {
+            budget: 10000,
+            shares: 7,
+            share_min: 100,
+            share_max: 3000,
+        };
+        for i in 0..500 {
+            let amounts = super::split(&seed(i), &split).unwrap();
+            assert_eq!(amounts.len(), split.shares);
+            assert_eq!(amounts.iter().sum::<i64>(), split.budget);
+            assert!(amounts
+                .iter()
+                .all(|amount| (split.share_min..=split.share_max).contains(amount)));
+        }
+    }
+
+    #[test]
+    fn split_is_deterministic_for_seed() {
+        let split = Split {
+            budget: 888,
+            shares: 5,
+            share_min: 1,
+            share_max: 500,
+        };
+        assert_eq!(
+            super::split(&seed(1), &split).unwrap(),
+            super::split(&seed(1), &split).unwrap()
+        );
+    }
+
+    #[test]
+    fn split_at_bounds() {
+        let lowest = Split {
+            budget: 500,
+            shares: 5,
+            share_min: 100,
+            share_max: 300,
+        };
+        let highest = Split {
+            budget: 1500,
+            ..lowest
+        };
+        let single = Split {
+            budget: 200,
+            shares: 1,
+            ..lowest
+        };
+        for i in 0..100 {
+            assert_eq!(super::split(&seed(i), &lowest).unwrap(), vec![100; 5]);
+            assert_eq!(super::split(&seed(i), &highest).unwrap(), vec![300; 5]);
+            assert_eq!(super::split(&seed(i), &single).unwrap(), vec![200]);
+        }
+    }
+
+    #[test]
+    fn split_rejects_infeasible() {
+        let valid = Split {
+            budget: 1000,
+            shares: 5,
+            share_min: 100,
+            share_max: 300,
+        };
+        assert!(valid.feasible());
+        let infeasible = [
+            Split {
+                budget: 499,
+                ..valid
+            },
+            Split {
+                budget: 1501,
+                ..valid
+            },
+            Split { shares: 0, ..valid },
+            Split {
+                share_min: 301,
+                ..valid
+            },
+            Split {
+                share_min: -1,
+                ..valid
+            },
+            Split {
+                share_max: i64::MAX,
+                shares: 2,
+                budget: i64::MAX,
+                share_min: 0,
+            },
+        ];
+        for split in infeasible {
+            assert!(!split.feasible(), "{split:?}");
+            assert!(super::split(&seed(0), &split).is_err(), "{split:?}");
+        }
+    }
+
+    fn stratum(cus_flag: &str, members: Vec<usize>, need: usize, cap: Option<usize>) -> Stratum {
+        Stratum {
+            cus_flag: cus_flag.to_owned(),
+            members,
+            need,
+            cap,
+        }
+    }
+
+    fn taken(picked: &[usize], stratum: &Stratum) -> usize {
+        picked
+            .iter()
+            .filter(|id| stratum.members.binary_search(id).is_ok())
+            .count()
+    }
+
+    #[test]
+    fn stratify_inactive_quota_matches_select() {
+        let participants = (1..=30).collect::<Vec<_>>();
+        let weights = (1..=30).map(|w| w % 4).collect::<Vec<u64>>();
+        let strata = Strata {
+            slots: 10,
+            winners: 5,
+            strata: vec![
+                stratum("研发", (1..=15).collect(), 0, None),
+                stratum("市场", (10..=30).collect(), 0, Some(10)),
+            ],
+        };
+        for i in 0..200 {
+            assert_eq!(
+                select_stratified(UNIFORM, &seed(i), &participants, None, 8, &strata).unwrap(),
+                select(UNIFORM, &seed(i), &participants, None, 8).unwrap()
+            );
+            assert_eq!(
+                select_stratified(
+                    WEIGHTED,
+                    &seed(i),
+                    &participants,
+                    Some(&weights),
+                    8,
+                    &strata
+                )
+                .unwrap(),
+                select(WEIGHTED, &seed(i), &participants, Some(&weights), 8).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn stratify_counts_overlapping_flags_in_each_group() {
+        //只有 1 和 2 同时带两个标签，两个名额必须都给他们
+        let participants = (1..=20).collect::<Vec<_>>();
+        let strata = Strata {
+            slots: 2,
+            winners: 2,
+            strata: vec![
+                stratum("研发", vec![1, 2, 3, 4, 5], 2, None),
+                stratum("深圳", vec![1, 2, 6, 7, 8], 2, None),
+            ],
+        };
+        for i in 0..200 {
+            let mut picked =
+                select_stratified(UNIFORM, &seed(i), &participants, None, 2, &strata).unwrap();
+            picked.sort_unstable();
+            assert_eq!(picked, vec![1, 2]);
+        }
+    }
+
+    #[test]
+    fn stratify_respects_min_and_cap_together() {
+        //研发至少 1 人至多 1 人，深圳至少 3 人，研发和深圳有重叠
+        let participants = (1..=20).collect::<Vec<_>>();
+        let strata = Strata {
+            slots: 4,
+            winners: 4,
+            strata: vec![
+                stratum("研发", (1..=5).collect(), 1, Some(1)),
+                stratum("深圳", (1..=10).collect(), 3, None),
+            ],
+        };
+        for i in 0..200 {
+            let picked =
+                select_stratified(UNIFORM, &seed(i), &participants, None, 7, &strata).unwrap();
+            assert_eq!(picked.len(), 7);
+            let (winners, alternates) = picked.split_at(4);
+            assert_eq!(taken(winners, &strata.strata[0]), 1);
+            assert!(taken(winners, &strata.strata[1]) >= 3);
+            //候补只检查上限
+            assert_eq!(taken(alternates, &strata.strata[0]), 0);
+        }
+    }
+
+    #[test]
+    fn stratify_returns_short_when_quota_unreachable() {
+        //研发至少 3 人，但名额只有 2 个
+        let participants = (1..=20).collect::<Vec<_>>();
+        let strata = Strata {
+            slots: 2,
+            winners: 2,
+            strata: vec![stratum("研发", (1..=5).collect(), 3, None)],
+        };
+        let picked = select_stratified(UNIFORM, &seed(0), &participants, None, 2, &strata).unwrap();
+        assert!(picked.len() < 2);
+    }
+
+    fn group(cus_flag: &str, members: Vec<usize>) -> FlagGroup {
+        FlagGroup {
+            cus_flag: cus_flag.to_owned(),
+            members,
+        }
+    }
+
+    fn layout(participants: usize, capacities: Vec<usize>) -> Layout {
+        Layout {
+            participants: (1..=participants).collect(),
+            capacities,
+            together: Vec::new(),
+            apart: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn partition_is_deterministic_for_seed() {
+        let layout = layout(30, vec![10, 10, 10]);
+        assert_eq!(
+            partition(&seed(1), &layout).unwrap(),
+            partition(&seed(1), &layout).unwrap()
+        );
+        let results = (0..20)
+            .map(|i| partition(&seed(i), &layout).unwrap())
+            .collect::<Vec<_>>();
+        assert!(results.iter().any(|buckets| *buckets != results[0]));
+    }
+
+    #[test]
+    fn partition_respects_capacities() {
+        let layout = layout(23, vec![5, 8, 10]);
+        for i in 0..200 {
+            let buckets = partition(&seed(i), &layout).unwrap();
+            assert_eq!(buckets.len(), 23);
+            for (bucket, capacity) in layout.capacities.iter().enumerate() {
+                assert!(buckets.iter().filter(|&&b| b == bucket).count() <= *capacity);
+            }
+        }
+        assert!(partition(&seed(0), &self::layout(24, vec![5, 8, 10])).is_err());
+        assert!(partition(&seed(0), &self::layout(3, Vec::new())).is_err());
+    }
+
+    #[test]
+    fn partition_keeps_together() {
+        let mut layout = layout(20, vec![8, 8, 8]);
+        //有公共成员 5 的两个标签合并成一组
+        layout.together = vec![
+            group("一家", vec![1, 3, 5]),
+            group("同行", vec![5, 7]),
+            group("同事", vec![10, 11, 12]),
+        ];
+        for i in 0..200 {
+            let buckets = partition(&seed(i), &layout).unwrap();
+            let bucket = |cus_id: usize| buckets[cus_id - 1];
+            assert!([1, 3, 5, 7].iter().all(|&id| bucket(id) == bucket(1)));
+            assert!([10, 11, 12].iter().all(|&id| bucket(id) == bucket(10)));
+        }
+
+        //需要在一起的人比任何一组的容量都多
+        layout.together = vec![group("一家", (1..=9).collect())];
+        assert!(partition(&seed(0), &layout).is_err());
+    }
+
+    #[test]
+    fn partition_keeps_apart_within_cap() {
+        let mut layout = layout(30, vec![12, 12, 12]);
+        //7 人分到 3 组，每组最多 3 人
+        layout.apart = vec![group("主管", vec![2, 4, 6, 8, 10, 12, 14])];
+        layout.together = vec![group("一家", vec![2, 3])];
+        for i in 0..200 {
+            let buckets = partition(&seed(i), &layout).unwrap();
+            for bucket in 0..3 {
+                let count = layout.apart[0]
+                    .members
+                    .iter()
+                    .filter(|&&id| buckets[id - 1] == bucket)
+                    .count();
+                assert!(count <= 3);
+            }
+            assert_eq!(buckets[1], buckets[2]);
+        }
+
+        //分散标签的两人必须在一起时，每组上限 1 人无法满足
+        layout.apart = vec![group("主管", vec![2, 3, 4])];
+        assert!(partition(&seed(0), &layout).is_err());
+    }
+
+    #[test]
+    fn partition_retries_constrained_units_first() {
+        //容量正好装满时，先放人数多的单元可能让最后一名分散标签成员无组可去
+        let mut layout = layout(30, vec![10, 10, 10]);
+        layout.apart = vec![group("主管", vec![2, 4, 6, 8, 10, 12, 14])];
+        for i in 0..500 {
+            let buckets = partition(&seed(i), &layout).unwrap();
+            for bucket in 0..3 {
+                assert_eq!(buckets.iter().filter(|&&b| b == bucket).count(), 10);
+            }
+        }
+    }
+}
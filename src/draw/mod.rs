@@ -0,0 +1,695 @@
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{Connection, OptionalExtension, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, info};
+
+use crate::activity::policy::{self, Exclusions, Policy};
+use crate::activity::state::{self, ActStatus};
+use crate::activity::team;
+use crate::audit::{self, AuditEvent};
+use crate::draw::engine::Split;
+use crate::draw::quota::QuotaOutcome;
+use crate::draw::rule::RuleSet;
+use crate::draw::seed::{DrawRecord, Inputs};
+use crate::error::BizError;
+use crate::winner::{claim, WinStatus};
+
+pub(crate) mod engine;
+pub(crate) mod quota;
+pub(crate) mod rng;
+pub(crate) mod rule;
+pub(crate) mod seed;
+pub(crate) mod simulate;
+pub(crate) mod verify;
+pub(crate) mod weight;
+
+/// ld_plan.draw_mode 的取值
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DrawMode {
+    /// 0: 每个候选人中奖概率相同
+    #[default]
+    Uniform = 0,
+    /// 1: 按 ld_custom_weight 中的权重抽奖
+    Weighted = 1,
+}
+
+impl TryFrom<i64> for DrawMode {
+    type Error = BizError;
+
+    fn try_from(value: i64) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(DrawMode::Uniform),
+            1 => Ok(DrawMode::Weighted),
+            _ => Err(BizError::Invalid(format!("未知的抽奖方式: {value}"))),
+        }
+    }
+}
+
+impl DrawMode {
+    /// 该抽奖方式对应的算法版本
+    pub(crate) fn algorithm(&self) -> &'static str {
+        match self {
+            DrawMode::Uniform => engine::UNIFORM,
+            DrawMode::Weighted => engine::WEIGHTED,
+        }
+    }
+}
+
+/// ld_plan.draw_unit 的取值
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DrawUnit {
+    /// 0: 抽取个人
+    #[default]
+    Customer = 0,
+    /// 1: 抽取团体，团体的每个成员都记一条中奖记录
+    Team = 1,
+}
+
+impl TryFrom<i64> for DrawUnit {
+    type Error = BizError;
+
+    fn try_from(value: i64) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(DrawUnit::Customer),
+            1 => Ok(DrawUnit::Team),
+            _ => Err(BizError::Invalid(format!("未知的抽奖对象: {value}"))),
+        }
+    }
+}
+
+/// ld_plan.prize_type 的取值
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum PrizeType {
+    /// 0: 实物或固定奖品，每个名额一份
+    #[default]
+    Item = 0,
+    /// 1: 红包，总金额随机拆给每个中奖者
+    RedEnvelope = 1,
+}
+
+impl TryFrom<i64> for PrizeType {
+    type Error = BizError;
+
+    fn try_from(value: i64) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(PrizeType::Item),
+            1 => Ok(PrizeType::RedEnvelope),
+            _ => Err(BizError::Invalid(format!("未知的奖品类型: {value}"))),
+        }
+    }
+}
+
+/// 红包奖项的金额配置，单位为分，份数即奖项的 `prize_amount`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct Envelope {
+    /// 总金额
+    pub budget: i64,
+    /// 每份的最小金额
+    pub share_min: i64,
+    /// 每份的最大金额
+    pub share_max: i64,
+}
+
+/// 奖项，对应 ld_plan 的一行
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Plan {
+    pub act_id: usize,
+    pub act_seq: usize,
+    pub act_prize: Option<String>,
+    pub prize_amount: usize,
+    pub draw_mode: DrawMode,
+    pub prize_type: PrizeType,
+    /// 红包奖项的金额配置，其他奖品类型为空
+    pub envelope: Option<Envelope>,
+    /// 团体奖项的 `prize_amount` 为中奖团体数
+    pub draw_unit: DrawUnit,
+}
+
+/// 参与抽奖的客户
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Candidate {
+    pub cus_id: usize,
+    pub cus_nickname: String,
+    pub cus_name: Option<String>,
+    pub cus_flag: Option<String>,
+    /// 本活动中的权重，没有配置时为 1
+    pub weight: u64,
+    /// 团体奖项中所属的团体
+    pub team_id: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct DrawResult {
+    pub plan: Plan,
+    /// 本次抽奖之前该奖项已产生的中奖名额数，团体奖项按团体计数
+    pub drawn_before: usize,
+    /// 本次抽出的中奖名额数，团体奖项按团体计数，`winners` 中包括团体的所有成员
+    pub drawn: usize,
+    pub winners: Vec<Candidate>,
+    /// 同时抽出的候补，按递补顺序排列
+    pub alternates: Vec<Candidate>,
+    /// 红包奖项中与 `winners` 一一对应的金额（分），其他奖品类型为空
+    pub amounts: Vec<i64>,
+    /// 抽奖后各分组的配额执行情况，没有配置配额时为空
+    pub quotas: Vec<QuotaOutcome>,
+    /// 本次抽奖使用的种子，已公开
+    pub seed: DrawRecord,
+}
+
+pub(crate) fn load_plan(conn: &Connection, act_id: usize, act_seq: usize) -> Result<Plan> {
+    let plan = conn
+        .query_row(
+            "select act_id,act_seq,act_prize,prize_amount,draw_mode,prize_type,budget,share_min,
+                    share_max,draw_unit
+               from ld_plan
+              where act_id=? and act_seq=?",
+            [act_id, act_seq],
+            |row| {
+                let envelope = match (row.get(6)?, row.get(7)?, row.get(8)?) {
+                    (Some(budget), Some(share_min), Some(share_max)) => Some(Envelope {
+                        budget,
+                        share_min,
+                        share_max,
+                    }),
+                    _ => None,
+                };
+                let plan = Plan {
+                    act_id: row.get(0)?,
+                    act_seq: row.get(1)?,
+                    act_prize: row.get(2)?,
+                    prize_amount: row.get::<_, Option<usize>>(3)?.unwrap_or_default(),
+                    draw_mode: DrawMode::default(),
+                    prize_type: PrizeType::default(),
+                    envelope,
+                    draw_unit: DrawUnit::default(),
+                };
+                Ok((
+                    plan,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, i64>(5)?,
+                    row.get::<_, i64>(9)?,
+                ))
+            },
+        )
+        .optional()?;
+
+    match plan {
+        Some((mut plan, draw_mode, prize_type, draw_unit)) => {
+            plan.draw_mode = DrawMode::try_from(draw_mode)?;
+            plan.prize_type = PrizeType::try_from(prize_type)?;
+            plan.draw_unit = DrawUnit::try_from(draw_unit)?;
+            if plan.prize_type != PrizeType::RedEnvelope {
+                plan.envelope = None;
+            }
+            Ok(plan)
+        }
+        None => Err(BizError::NotFound(format!("活动{act_id}没有第{act_seq}个奖项")).into()),
+    }
+}
+
+/// 已中奖人数，不包括作废和候补，团体奖项按团体计数
+pub(crate) fn count_winners(conn: &Connection, act_id: usize, act_seq: usize) -> Result<usize> {
+    Ok(conn.query_row(
+        "select ifnull(sum(team_id is null),0) + count(distinct team_id) from ld_win_list
+          where act_id=? and act_seq=? and win_status=0",
+        [act_id, act_seq],
+        |row| row.get(0),
+    )?)
+}
+
+/// 已发出的红包金额，不包括作废和候补
+pub(crate) fn awarded_amount(conn: &Connection, act_id: usize, act_seq: usize) -> Result<i64> {
+    Ok(conn.query_row(
+        "select ifnull(sum(amount),0) from ld_win_list
+          where act_id=? and act_seq=? and win_status=0",
+        [act_id, act_seq],
+        |row| row.get(0),
+    )?)
+}
+
+/// 某个奖项的参与资格统计
+#[derive(Debug, Serialize)]
+pub(crate) struct Eligibility {
+    pub rules: RuleSet,
+    pub policy: Policy,
+    /// 客户总数
+    pub total: usize,
+    /// 本奖项已有记录（包括作废和候补），或本活动中奖和候补次数已达上限而被排除的人数
+    pub already_won: usize,
+    /// 已中过更高奖项而被排除的人数
+    pub top_tier_excluded: usize,
+    /// 近期在其他活动中中过奖而被排除的人数
+    pub recent_excluded: usize,
+    /// 要求签到时没有签到而被排除的人数
+    pub absent: usize,
+    /// 不满足标签规则而被排除的人数
+    pub rule_excluded: usize,
+    /// 权重为 0 而被排除的人数
+    pub zero_weight: usize,
+    /// 有资格参与抽奖的人数，团体奖项为有资格的团体的成员人次
+    pub eligible: usize,
+    /// 团体奖项中有资格的团体数
+    pub teams: usize,
+    /// 团体奖项中因没有成员、权重为 0 或有成员被上述条件排除而整队排除的团体数
+    pub team_excluded: usize,
+    #[serde(skip)]
+    pub candidates: Vec<Candidate>,
+}
+
+/// 计算奖项的候选人：满足 ld_plan_range 规则、且没有被活动的参与和中奖限制排除的客户
+///
+/// 按权重抽奖的奖项还会排除权重为 0 的客户。
+/// 团体奖项只保留全部成员都有资格的团体，候选人按团体依次排列，权重为团体的权重。
+pub(crate) fn eligibility(conn: &Connection, act_id: usize, act_seq: usize) -> Result<Eligibility> {
+    let plan = load_plan(conn, act_id, act_seq)?;
+    let rules = RuleSet::load(conn, act_id, act_seq)?;
+    let policy = policy::get(conn, act_id)?;
+    let exclusions = Exclusions::load(conn, act_id, act_seq, &policy)?;
+    let mut stmt = conn.prepare(
+        "select c.cus_id,c.cus_nickname,c.cus_name,c.cus_flag,ifnull(cw.weight,1)
+           from ld_custom c
+           left join ld_custom_weight cw on cw.act_id=? and cw.cus_id=c.cus_id
+          order by c.cus_id",
+    )?;
+    let mut rows = stmt.query([act_id])?;
+
+    let mut eligibility = Eligibility {
+        rules,
+        policy,
+        total: 0,
+        already_won: 0,
+        top_tier_excluded: 0,
+        recent_excluded: 0,
+        absent: 0,
+        rule_excluded: 0,
+        zero_weight: 0,
+        eligible: 0,
+        teams: 0,
+        team_excluded: 0,
+        candidates: Vec::new(),
+    };
+    while let Some(row) = rows.next()? {
+        eligibility.total += 1;
+        let candidate = Candidate {
+            cus_id: row.get(0)?,
+            cus_nickname: row.get(1)?,
+            cus_name: row.get(2)?,
+            cus_flag: row.get(3)?,
+            weight: row.get::<_, i64>(4)?.max(0) as u64,
+            team_id: None,
+        };
+
+        if exclusions.already_won.contains(&candidate.cus_id) {
+            eligibility.already_won += 1;
+        } else if exclusions.top_tier.contains(&candidate.cus_id) {
+            eligibility.top_tier_excluded += 1;
+        } else if exclusions.recent.contains(&candidate.cus_id) {
+            eligibility.recent_excluded += 1;
+        } else if exclusions.absent.contains(&candidate.cus_id) {
+            eligibility.absent += 1;
+        } else if !eligibility.rules.matches(candidate.cus_flag.as_deref()) {
+            eligibility.rule_excluded += 1;
+        } else if plan.draw_mode == DrawMode::Weighted
+            && plan.draw_unit == DrawUnit::Customer
+            && candidate.weight == 0
+        {
+            eligibility.zero_weight += 1;
+        } else {
+            eligibility.candidates.push(candidate);
+        }
+    }
+    if plan.draw_unit == DrawUnit::Team {
+        let individuals = std::mem::take(&mut eligibility.candidates);
+        let teams = team::list(conn, act_id)?;
+        team::ensure_disjoint(&teams)?;
+        for team in teams {
+            let members = team
+                .members
+                .iter()
+                .map(|cus_id| {
+                    individuals
+                        .binary_search_by_key(cus_id, |c| c.cus_id)
+                        .ok()
+                        .map(|index| &individuals[index])
+                })
+                .collect::<Option<Vec<_>>>();
+            match members {
+                Some(members)
+                    if !members.is_empty()
+                        && (plan.draw_mode == DrawMode::Uniform || team.weight > 0) =>
+                {
+                    eligibility.teams += 1;
+                    eligibility
+                        .candidates
+                        .extend(members.into_iter().map(|member| Candidate {
+                            weight: team.weight,
+                            team_id: Some(team.team_id),
+                            ..member.clone()
+                        }));
+                }
+                _ => eligibility.team_excluded += 1,
+            }
+        }
+    }
+    eligibility.eligible = eligibility.candidates.len();
+
+    Ok(eligibility)
+}
+
+/// 抽奖的对象，个人奖项为每个候选人，团体奖项为每个团体
+pub(crate) struct Unit {
+    /// cus_id 或 team_id
+    pub id: usize,
+    pub weight: u64,
+    pub members: Vec<Candidate>,
+}
+
+/// 把候选人按抽奖对象分组，同一团体的成员在候选人中是连续的
+pub(crate) fn units(candidates: &[Candidate]) -> Vec<Unit> {
+    let mut units = Vec::<Unit>::new();
+    for candidate in candidates {
+        match (candidate.team_id, units.last_mut()) {
+            (Some(team_id), Some(unit)) if unit.id == team_id => {
+                unit.members.push(candidate.clone());
+            }
+            (team_id, _) => units.push(Unit {
+                id: team_id.unwrap_or(candidate.cus_id),
+                weight: candidate.weight,
+                members: vec![candidate.clone()],
+            }),
+        }
+    }
+    units
+}
+
+/// 抽奖对象的 id 和按权重抽奖时的权重，公布种子和抽奖时都按这个顺序
+pub(crate) fn inputs(plan: &Plan, units: &[Unit]) -> Inputs {
+    Inputs {
+        participants: units.iter().map(|u| u.id).collect(),
+        weights: match plan.draw_mode {
+            DrawMode::Uniform => None,
+            DrawMode::Weighted => Some(units.iter().map(|u| u.weight).collect()),
+        },
+    }
+}
+
+/// 预览中的候选人及其中奖概率
+#[derive(Debug, Serialize)]
+pub(crate) struct PreviewItem {
+    #[serde(flatten)]
+    pub candidate: Candidate,
+    /// 抽完剩余名额时的中奖概率，按权重抽奖时为估算值
+    pub probability: f64,
+}
+
+/// 抽奖前预览候选人
+#[derive(Debug, Serialize)]
+pub(crate) struct Preview {
+    pub plan: Plan,
+    /// 剩余名额
+    pub remaining: usize,
+    #[serde(flatten)]
+    pub eligibility: Eligibility,
+    pub page: usize,
+    pub size: usize,
+    pub list: Vec<PreviewItem>,
+}
+
+/// 分页预览候选人，`page` 从 1 开始
+pub(crate) fn preview(
+    conn: &Connection,
+    act_id: usize,
+    act_seq: usize,
+    page: usize,
+    size: usize,
+) -> Result<Preview> {
+    let plan = load_plan(conn, act_id, act_seq)?;
+    let remaining = plan
+        .prize_amount
+        .saturating_sub(count_winners(conn, act_id, act_seq)?);
+    let mut eligibility = eligibility(conn, act_id, act_seq)?;
+
+    //团体的每个成员与团体的中奖概率相同
+    let units = units(&std::mem::take(&mut eligibility.candidates));
+    let probabilities = match plan.draw_mode {
+        DrawMode::Uniform => {
+            let probability = (remaining as f64 / units.len().max(1) as f64).min(1.0);
+            vec![probability; units.len()]
+        }
+        DrawMode::Weighted => {
+            let weights = units.iter().map(|u| u.weight).collect::<Vec<_>>();
+            engine::win_probabilities(&weights, remaining)
+        }
+    };
+    let list = units
+        .into_iter()
+        .zip(probabilities)
+        .flat_map(|(unit, probability)| unit.members.into_iter().map(move |c| (c, probability)))
+        .skip(page.saturating_sub(1) * size)
+        .take(size)
+        .map(|(candidate, probability)| PreviewItem {
+            candidate,
+            probability,
+        })
+        .collect();
+
+    Ok(Preview {
+        plan,
+        remaining,
+        eligibility,
+        page,
+        size,
+        list,
+    })
+}
+
+/// 抽取一个奖项，`count` 为空时抽完剩余名额，另外按顺序抽出 `alternates` 个候补
+///
+/// 必须先通过 [`seed::commit`] 公布种子哈希。
+/// 整个过程在一个写事务中完成，中奖人数不会超过 `prize_amount`，
+/// 完成后公开种子和冻结的候选人列表。
+pub(crate) fn draw(
+    conn: &mut Connection,
+    act_id: usize,
+    act_seq: usize,
+    count: Option<usize>,
+    alternates: usize,
+) -> Result<DrawResult> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let result = draw_in(&tx, act_id, act_seq, count, alternates)?;
+    tx.commit()?;
+    info!("奖项{act_id}-{act_seq}公开种子: {:?}", result.seed.seed);
+    debug!("中奖名单： {:#?}", result.winners);
+
+    Ok(result)
+}
+
+/// 在调用方的事务中抽奖
+pub(crate) fn draw_in(
+    tx: &Connection,
+    act_id: usize,
+    act_seq: usize,
+    count: Option<usize>,
+    alternates: usize,
+) -> Result<DrawResult> {
+    state::ensure(tx, act_id, &[ActStatus::Drawing], "抽奖")?;
+    let plan = load_plan(tx, act_id, act_seq)?;
+    let drawn_before = count_winners(tx, act_id, act_seq)?;
+    let remaining = plan.prize_amount.saturating_sub(drawn_before);
+    let count = count.unwrap_or(remaining);
+    info!(
+        "奖项{act_id}-{act_seq} 共{}个，已抽{drawn_before}个，本次抽{count}个，候补{alternates}个",
+        plan.prize_amount
+    );
+
+    if remaining == 0 {
+        return Err(BizError::Conflict(format!("奖项{act_id}-{act_seq}已抽完")).into());
+    }
+    if count == 0 || count > remaining {
+        return Err(
+            BizError::Invalid(format!("抽奖人数{count}不合法，剩余名额{remaining}")).into(),
+        );
+    }
+
+    let pending = seed::pending(tx, act_id, act_seq)?
+        .ok_or_else(|| BizError::Conflict(format!("奖项{act_id}-{act_seq}还没有公布种子哈希")))?;
+
+    let units = units(&eligibility(tx, act_id, act_seq)?.candidates);
+    let draw_count = count + alternates;
+    if units.len() < draw_count {
+        let message = match plan.draw_unit {
+            DrawUnit::Customer => format!("候选人数{}不足{draw_count}人", units.len()),
+            DrawUnit::Team => format!("候选团体数{}不足{draw_count}个", units.len()),
+        };
+        return Err(BizError::Conflict(message).into());
+    }
+
+    //中奖者和候补一起抽取，前 count 个为中奖者，复算时按 draw_count 整体比对
+    let algorithm = plan.draw_mode.algorithm();
+    let Inputs {
+        participants,
+        weights,
+    } = inputs(&plan, &units);
+    let strata = match plan.draw_unit {
+        DrawUnit::Customer => {
+            let members = units
+                .iter()
+                .flat_map(|u| &u.members)
+                .map(|c| (c.cus_id, c.cus_flag.as_deref()))
+                .collect::<Vec<_>>();
+            quota::strata(tx, &plan, &members, count)?
+        }
+        DrawUnit::Team if !quota::load(tx, act_id, act_seq)?.is_empty() => {
+            return Err(BizError::Conflict("团体奖项不支持分组配额".to_owned()).into());
+        }
+        DrawUnit::Team => None,
+    };
+    let selected_ids = match &strata {
+        Some(strata) => {
+            quota::ensure_reachable(strata)?;
+            let selected = engine::select_stratified(
+                algorithm,
+                &pending.seed,
+                &participants,
+                weights.as_deref(),
+                draw_count,
+                strata,
+            )?;
+            if selected.len() < draw_count {
+                return Err(BizError::Conflict(format!(
+                    "{}，本次需要{draw_count}人",
+                    quota::explain(strata, &selected)
+                ))
+                .into());
+            }
+            selected
+        }
+        None => engine::select(
+            algorithm,
+            &pending.seed,
+            &participants,
+            weights.as_deref(),
+            draw_count,
+        )?,
+    };
+    //团体中奖时每个成员都是中奖者
+    let mut selected = Vec::with_capacity(selected_ids.len());
+    for id in selected_ids {
+        if let Some(unit) = units.iter().find(|u| u.id == id) {
+            selected.push(unit.members.clone());
+        }
+    }
+    let alternates = selected.split_off(count.min(selected.len())).concat();
+    let winners = selected.concat();
+
+    //红包把剩余金额拆给剩余名额，本次只发出前 count 份，作废空出的金额留给递补或重抽
+    let split = match &plan.envelope {
+        Some(envelope) => {
+            let split = Split {
+                budget: envelope.budget - awarded_amount(tx, act_id, act_seq)?,
+                shares: remaining,
+                share_min: envelope.share_min,
+                share_max: envelope.share_max,
+            };
+            if !split.feasible() {
+                return Err(BizError::Conflict(format!(
+                    "奖项{act_id}-{act_seq}剩余金额{}分无法分给剩余{remaining}个名额",
+                    split.budget
+                ))
+                .into());
+            }
+            Some(split)
+        }
+        None => None,
+    };
+    let amounts = match &split {
+        Some(split) => {
+            let mut amounts = engine::split(&pending.seed, split)?;
+            amounts.truncate(winners.len());
+            amounts
+        }
+        None => Vec::new(),
+    };
+
+    let draw_id = pending.record.draw_id;
+    let win_time = time::OffsetDateTime::now_utc().unix_timestamp();
+    let mut win_ids = Vec::with_capacity(winners.len() + alternates.len());
+    {
+        let mut stmt = tx.prepare(
+            "insert into ld_win_list (act_id, act_seq, cus_id, win_time, draw_id, win_status, amount,
+                                      team_id)
+             values (?, ?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        for (i, winner) in winners.iter().enumerate() {
+            let status = WinStatus::Won as i64;
+            let amount = amounts.get(i);
+            stmt.execute((
+                act_id,
+                act_seq,
+                winner.cus_id,
+                win_time,
+                draw_id,
+                status,
+                amount,
+                winner.team_id,
+            ))?;
+            let win_id = tx.last_insert_rowid() as usize;
+            claim::issue(tx, win_id, win_time)?;
+            win_ids.push(win_id);
+        }
+        for alternate in &alternates {
+            let status = WinStatus::Alternate as i64;
+            let amount: Option<i64> = None;
+            stmt.execute((
+                act_id,
+                act_seq,
+                alternate.cus_id,
+                win_time,
+                draw_id,
+                status,
+                amount,
+                alternate.team_id,
+            ))?;
+            win_ids.push(tx.last_insert_rowid() as usize);
+        }
+    }
+    seed::reveal(
+        tx,
+        draw_id,
+        algorithm,
+        &participants,
+        weights.as_deref(),
+        draw_count,
+        win_time,
+    )?;
+    seed::save_win_count(tx, draw_id, count)?;
+    if let Some(split) = split {
+        seed::save_split(tx, draw_id, split, &amounts)?;
+    }
+    if let Some(strata) = strata {
+        seed::save_quota(tx, draw_id, strata)?;
+    }
+    let seed = seed::get(tx, draw_id)?;
+    audit::append(
+        tx,
+        AuditEvent::Draw,
+        act_id,
+        &win_ids,
+        json!({ "act_seq": act_seq, "draw_id": draw_id, "seed_hash": seed.seed_hash }),
+    )?;
+
+    Ok(DrawResult {
+        plan,
+        drawn_before,
+        drawn: count,
+        winners,
+        alternates,
+        amounts,
+        quotas: quota::outcome(tx, act_id, act_seq)?,
+        seed,
+    })
+}
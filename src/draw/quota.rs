@@ -0,0 +1,230 @@
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::draw::engine::{Strata, Stratum};
+use crate::draw::rule::parse_flags;
+use crate::draw::{load_plan, Plan};
+use crate::error::BizError;
+
+/// 奖项按标签分组的中奖人数限制，对应 ld_plan_quota 的一行
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Quota {
+    pub cus_flag: String,
+    /// 至少中奖人数
+    #[serde(default)]
+    pub min_count: usize,
+    /// 最多中奖人数
+    #[serde(default)]
+    pub max_count: Option<usize>,
+    /// 最多占奖项名额的百分比，按名额向下取整
+    #[serde(default)]
+    pub max_percent: Option<usize>,
+}
+
+impl Quota {
+    /// 按奖项名额换算出的上限，同时配置人数和百分比时取较小值
+    pub(crate) fn limit(&self, prize_amount: usize) -> Option<usize> {
+        let by_percent = self.max_percent.map(|percent| prize_amount * percent / 100);
+        match (self.max_count, by_percent) {
+            (Some(count), Some(percent)) => Some(count.min(percent)),
+            (count, percent) => count.or(percent),
+        }
+    }
+
+    fn matches(&self, cus_flag: Option<&str>) -> bool {
+        cus_flag.is_some_and(|flags| parse_flags(flags).contains(&self.cus_flag.as_str()))
+    }
+}
+
+pub(crate) fn load(conn: &Connection, act_id: usize, act_seq: usize) -> Result<Vec<Quota>> {
+    let mut stmt = conn.prepare(
+        "select cus_flag,min_count,max_count,max_percent from ld_plan_quota
+          where act_id=? and act_seq=? order by rowid",
+    )?;
+    let mut rows = stmt.query([act_id, act_seq])?;
+
+    let mut quotas = Vec::new();
+    while let Some(row) = rows.next()? {
+        quotas.push(Quota {
+            cus_flag: row.get(0)?,
+            min_count: row.get(1)?,
+            max_count: row.get(2)?,
+            max_percent: row.get(3)?,
+        });
+    }
+
+    Ok(quotas)
+}
+
+/// 检查配额本身是否自相矛盾，不同标签的客户可能重叠，最少人数之和不在这里检查
+pub(crate) fn validate(quotas: &[Quota], prize_amount: usize) -> Result<()> {
+    for (i, quota) in quotas.iter().enumerate() {
+        let cus_flag = quota.cus_flag.as_str();
+        if cus_flag.trim().is_empty() {
+            return Err(BizError::Invalid("配额标签不能为空".to_owned()).into());
+        }
+        if quotas[..i]
+            .iter()
+            .any(|q| q.cus_flag.trim() == cus_flag.trim())
+        {
+            return Err(BizError::Invalid(format!("标签{cus_flag}的配额重复")).into());
+        }
+        if quota.max_percent.is_some_and(|percent| percent > 100) {
+            return Err(BizError::Invalid(format!("标签{cus_flag}的百分比不能超过100")).into());
+        }
+        if quota.min_count > prize_amount {
+            return Err(BizError::Invalid(format!(
+                "标签{cus_flag}至少{}人超过了奖品数量{prize_amount}",
+                quota.min_count
+            ))
+            .into());
+        }
+        if let Some(limit) = quota.limit(prize_amount) {
+            if quota.min_count > limit {
+                return Err(BizError::Invalid(format!(
+                    "标签{cus_flag}至少{}人，但按奖品数量{prize_amount}最多只能{limit}人",
+                    quota.min_count
+                ))
+                .into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 奖项当前有效中奖者的标签
+fn winner_flags(conn: &Connection, act_id: usize, act_seq: usize) -> Result<Vec<Option<String>>> {
+    let mut stmt = conn.prepare(
+        "select c.cus_flag from ld_win_list w
+           join ld_custom c on c.cus_id=w.cus_id
+          where w.act_id=? and w.act_seq=? and w.win_status=0",
+    )?;
+    let flags = stmt
+        .query_map([act_id, act_seq], |row| row.get(0))?
+        .collect::<Result<Vec<Option<String>>, _>>()?;
+    Ok(flags)
+}
+
+/// 一个标签分组的配额执行情况
+#[derive(Debug, Serialize)]
+pub(crate) struct QuotaOutcome {
+    pub cus_flag: String,
+    pub min_count: usize,
+    /// 换算后的上限，为空表示不限
+    pub limit: Option<usize>,
+    /// 该分组的有效中奖人数
+    pub won: usize,
+    pub satisfied: bool,
+}
+
+/// 奖项各分组的配额执行情况，奖项没有抽完时最少人数可能还未满足
+pub(crate) fn outcome(
+    conn: &Connection,
+    act_id: usize,
+    act_seq: usize,
+) -> Result<Vec<QuotaOutcome>> {
+    let plan = load_plan(conn, act_id, act_seq)?;
+    let flags = winner_flags(conn, act_id, act_seq)?;
+
+    Ok(load(conn, act_id, act_seq)?
+        .into_iter()
+        .map(|quota| {
+            let won = flags.iter().filter(|f| quota.matches(f.as_deref())).count();
+            let limit = quota.limit(plan.prize_amount);
+            QuotaOutcome {
+                satisfied: won >= quota.min_count && limit.is_none_or(|limit| won <= limit),
+                cus_flag: quota.cus_flag,
+                min_count: quota.min_count,
+                limit,
+                won,
+            }
+        })
+        .collect())
+}
+
+/// 按奖项当前的中奖情况计算本次抽奖的配额约束，奖项没有配置配额时返回空
+///
+/// `candidates` 为本次的候选人 cus_id 及其标签。
+pub(crate) fn strata(
+    conn: &Connection,
+    plan: &Plan,
+    candidates: &[(usize, Option<&str>)],
+    winners: usize,
+) -> Result<Option<Strata>> {
+    let quotas = load(conn, plan.act_id, plan.act_seq)?;
+    if quotas.is_empty() {
+        return Ok(None);
+    }
+    let flags = winner_flags(conn, plan.act_id, plan.act_seq)?;
+
+    let mut strata = Vec::with_capacity(quotas.len());
+    for quota in &quotas {
+        let won = flags.iter().filter(|f| quota.matches(f.as_deref())).count();
+        let mut members = candidates
+            .iter()
+            .filter(|(_, cus_flag)| quota.matches(*cus_flag))
+            .map(|(cus_id, _)| *cus_id)
+            .collect::<Vec<_>>();
+        members.sort_unstable();
+        strata.push(Stratum {
+            cus_flag: quota.cus_flag.clone(),
+            members,
+            need: quota.min_count.saturating_sub(won),
+            cap: quota
+                .limit(plan.prize_amount)
+                .map(|limit| limit.saturating_sub(won)),
+        });
+    }
+
+    Ok(Some(Strata {
+        slots: plan.prize_amount.saturating_sub(flags.len()),
+        winners,
+        strata,
+    }))
+}
+
+/// 检查每个分组的候选人都够补齐还差的最少人数
+pub(crate) fn ensure_reachable(strata: &Strata) -> Result<()> {
+    match strata.strata.iter().find(|s| s.members.len() < s.need) {
+        Some(stratum) => Err(BizError::Conflict(format!(
+            "标签{}还需要{}人中奖，候选人只有{}人",
+            stratum.cus_flag,
+            stratum.need,
+            stratum.members.len()
+        ))
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// 配额无法满足时说明各分组的情况，`picked` 为按配额已经选出的 cus_id
+pub(crate) fn explain(strata: &Strata, picked: &[usize]) -> String {
+    let details = strata
+        .strata
+        .iter()
+        .map(|stratum| {
+            let taken = picked
+                .iter()
+                .filter(|id| stratum.members.binary_search(id).is_ok())
+                .count();
+            let cap = match stratum.cap {
+                Some(cap) => format!("最多还能中{cap}人"),
+                None => "不限".to_owned(),
+            };
+            format!(
+                "标签{}还需{}人、{cap}、候选人{}人、本次选中{taken}人",
+                stratum.cus_flag,
+                stratum.need,
+                stratum.members.len()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("；");
+    format!(
+        "按配额只能抽出{}人，奖项剩余{}个名额: {details}。\
+         配额按抽取顺序逐人贪心检查，标签重叠较多时可能找不到实际存在的组合，可以调整配额后重试",
+        picked.len(),
+        strata.slots
+    )
+}
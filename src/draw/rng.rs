@@ -0,0 +1,60 @@
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+
+/// 种子字节数
+pub(crate) const SEED_LEN: usize = 32;
+
+/// 基于 SHA-256 计数器模式的确定性随机数发生器
+///
+/// 第 n 个（从 0 开始）随机数为 `SHA-256(seed || n)` 的前 8 个字节，
+/// n 按 8 字节大端编码，结果按大端解释为 u64。
+/// 只要知道种子，任何人都可以离线重新生成同样的随机序列。
+pub(crate) struct DrawRng {
+    seed: Vec<u8>,
+    counter: u64,
+}
+
+impl DrawRng {
+    pub(crate) fn new(seed: &[u8]) -> Self {
+        DrawRng {
+            seed: seed.to_vec(),
+            counter: 0,
+        }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.seed);
+        hasher.update(self.counter.to_be_bytes());
+        self.counter += 1;
+
+        let digest = hasher.finalize();
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        u64::from_be_bytes(bytes)
+    }
+
+    /// 生成 `[0, n)` 之间均匀分布的整数，用拒绝采样消除取模偏差
+    pub(crate) fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "随机数范围不能为空");
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let v = self.next_u64();
+            if v < zone {
+                return v % n;
+            }
+        }
+    }
+}
+
+/// 从操作系统获取一个新的随机种子
+pub(crate) fn new_seed() -> Result<[u8; SEED_LEN]> {
+    let mut seed = [0u8; SEED_LEN];
+    getrandom::fill(&mut seed).map_err(|e| anyhow!("生成随机种子失败: {e}"))?;
+    Ok(seed)
+}
+
+/// 种子的承诺值：SHA-256(seed) 的十六进制
+pub(crate) fn seed_hash(seed: &[u8]) -> String {
+    hex::encode(Sha256::digest(seed))
+}
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::error::BizError;
+
+/// ld_plan_range.flag_type 的取值
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FlagType {
+    /// 0: 拥有其中任意一个标签即可参与
+    Any = 0,
+    /// 1: 必须拥有该标签
+    Require = 1,
+    /// 2: 拥有该标签的客户不能参与
+    Exclude = 2,
+}
+
+impl TryFrom<i64> for FlagType {
+    type Error = BizError;
+
+    fn try_from(value: i64) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(FlagType::Any),
+            1 => Ok(FlagType::Require),
+            2 => Ok(FlagType::Exclude),
+            _ => Err(BizError::Invalid(format!("未知的标签规则类型: {value}"))),
+        }
+    }
+}
+
+/// 一个奖项的参与资格规则
+///
+/// 多条规则之间的关系为：(任一 Any 标签) 且 (全部 Require 标签) 且 (没有 Exclude 标签)，
+/// 没有配置规则时所有客户都有资格。
+#[derive(Clone, Debug, Default, Serialize)]
+pub(crate) struct RuleSet {
+    pub any: Vec<String>,
+    pub require: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl RuleSet {
+    pub(crate) fn load(conn: &Connection, act_id: usize, act_seq: usize) -> Result<Self> {
+        let mut stmt = conn
+            .prepare("select cus_flag,flag_type from ld_plan_range where act_id=? and act_seq=?")?;
+        let mut rows = stmt.query([act_id, act_seq])?;
+
+        let mut rules = RuleSet::default();
+        while let Some(row) = rows.next()? {
+            let cus_flag: Option<String> = row.get(0)?;
+            let flag_type: Option<i64> = row.get(1)?;
+            let cus_flag = match cus_flag.as_deref().map(str::trim) {
+                Some(flag) if !flag.is_empty() => flag.to_owned(),
+                _ => continue,
+            };
+            rules.push(FlagType::try_from(flag_type.unwrap_or_default())?, cus_flag);
+        }
+
+        Ok(rules)
+    }
+
+    pub(crate) fn push(&mut self, flag_type: FlagType, cus_flag: String) {
+        match flag_type {
+            FlagType::Any => self.any.push(cus_flag),
+            FlagType::Require => self.require.push(cus_flag),
+            FlagType::Exclude => self.exclude.push(cus_flag),
+        }
+    }
+
+    /// 判断客户的 cus_flag 是否满足规则
+    pub(crate) fn matches(&self, cus_flag: Option<&str>) -> bool {
+        let flags = cus_flag.map(parse_flags).unwrap_or_default();
+        let has = |flag: &String| flags.contains(&flag.as_str());
+
+        (self.any.is_empty() || self.any.iter().any(has))
+            && self.require.iter().all(has)
+            && !self.exclude.iter().any(has)
+    }
+}
+
+/// 客户可以有多个标签，用逗号、分号或空白分隔
+pub(crate) fn parse_flags(cus_flag: &str) -> Vec<&str> {
+    cus_flag
+        .split(|c: char| matches!(c, ',' | '，' | ';' | '；') || c.is_whitespace())
+        .filter(|flag| !flag.is_empty())
+        .collect()
+}
@@ -0,0 +1,313 @@
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{Connection, OptionalExtension, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::activity::state::{self, ActStatus};
+use crate::draw::engine::{self, Split, Strata};
+use crate::draw::{self, eligibility, load_plan, rng};
+use crate::error::BizError;
+
+/// 一次抽奖的种子记录，对应 ld_draw 的一行
+///
+/// 抽奖前只公开 `seed_hash` 和 `input_hash`，抽奖完成后公开 `seed` 和冻结的候选人列表，
+/// 任何人都可以用它们离线复算中奖名单。
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct DrawRecord {
+    pub draw_id: usize,
+    pub act_id: usize,
+    pub act_seq: usize,
+    pub seed_hash: String,
+    /// 公布种子哈希时冻结的候选对象和权重的哈希，与 `seed_hash` 同时公开
+    pub input_hash: Option<String>,
+    pub seed: Option<String>,
+    pub algorithm: String,
+    /// 本次抽取的人数（团体奖项为团体数），包括候补
+    pub draw_count: Option<usize>,
+    /// 按抽取顺序前 `win_count` 个为中奖者，其余为候补
+    pub win_count: Option<usize>,
+    /// 抽奖时的候选人 cus_id，按 cus_id 升序
+    pub participants: Option<Vec<usize>>,
+    /// 按权重抽奖时与 `participants` 一一对应的权重
+    pub weights: Option<Vec<u64>>,
+    pub commit_time: i64,
+    pub draw_time: Option<i64>,
+    /// 红包奖项本次拆分的参数和发出的金额
+    pub split: Option<SplitRecord>,
+    /// 配置了配额的奖项本次冻结的分组约束
+    pub quota: Option<QuotaRecord>,
+    /// 公布种子哈希时冻结的候选对象和权重，抽奖后公开
+    pub inputs: Option<Inputs>,
+}
+
+/// 抽奖的候选对象 id 和按权重抽奖时一一对应的权重
+///
+/// 公布种子哈希时冻结一份并公布其哈希，之后调整候选人会在复算时暴露出来。
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct Inputs {
+    pub participants: Vec<usize>,
+    pub weights: Option<Vec<u64>>,
+}
+
+impl Inputs {
+    pub(crate) fn hash(&self) -> Result<String> {
+        Ok(rng::seed_hash(&serde_json::to_vec(self)?))
+    }
+}
+
+/// 一次抽奖的红包拆分记录，存放在 ld_draw.split
+///
+/// 用公开的种子和参数复算拆分结果，前 `amounts.len()` 份应与记录的金额一致。
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct SplitRecord {
+    pub algorithm: String,
+    #[serde(flatten)]
+    pub split: Split,
+    /// 按中奖顺序发给本次中奖者的金额
+    pub amounts: Vec<i64>,
+}
+
+/// 一次按配额抽奖的分组约束，存放在 ld_draw.quota
+///
+/// 复算时先按 `algorithm` 排出全部候选人的顺序，再用这里的约束筛选。
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct QuotaRecord {
+    pub algorithm: String,
+    #[serde(flatten)]
+    pub strata: Strata,
+}
+
+const SELECT_DRAW: &str = "select draw_id,act_id,act_seq,seed_hash,seed,algorithm,draw_count,
+                                  participants,weights,commit_time,draw_time,split,quota,
+                                  win_count,input_hash,inputs
+                             from ld_draw";
+
+impl DrawRecord {
+    fn from_row(row: &Row) -> Result<Self> {
+        let draw_time: Option<i64> = row.get(10)?;
+        let participants: Option<String> = row.get(7)?;
+        let weights: Option<String> = row.get(8)?;
+        let split: Option<String> = row.get(11)?;
+        let quota: Option<String> = row.get(12)?;
+        let inputs: Option<String> = row.get(15)?;
+
+        Ok(DrawRecord {
+            draw_id: row.get(0)?,
+            act_id: row.get(1)?,
+            act_seq: row.get(2)?,
+            seed_hash: row.get(3)?,
+            input_hash: row.get(14)?,
+            //抽奖完成前不能泄露种子
+            seed: draw_time.and(row.get(4)?),
+            algorithm: row.get(5)?,
+            draw_count: row.get(6)?,
+            win_count: row.get(13)?,
+            participants: participants
+                .map(|json| serde_json::from_str(&json))
+                .transpose()?,
+            weights: weights
+                .map(|json| serde_json::from_str(&json))
+                .transpose()?,
+            commit_time: row.get(9)?,
+            draw_time,
+            split: split.map(|json| serde_json::from_str(&json)).transpose()?,
+            quota: quota.map(|json| serde_json::from_str(&json)).transpose()?,
+            inputs: draw_time
+                .and(inputs)
+                .map(|json| serde_json::from_str(&json))
+                .transpose()?,
+        })
+    }
+}
+
+/// 已公布承诺、尚未抽奖的种子
+pub(crate) struct PendingSeed {
+    pub record: DrawRecord,
+    pub seed: Vec<u8>,
+}
+
+/// 查询奖项尚未使用的种子
+pub(crate) fn pending(
+    conn: &Connection,
+    act_id: usize,
+    act_seq: usize,
+) -> Result<Option<PendingSeed>> {
+    let sql = format!("{SELECT_DRAW} where act_id=? and act_seq=? and draw_time is null");
+    let pending = conn
+        .query_row(&sql, [act_id, act_seq], |row| {
+            Ok((DrawRecord::from_row(row), row.get::<_, String>(4)?))
+        })
+        .optional()?;
+
+    match pending {
+        Some((record, seed)) => Ok(Some(PendingSeed {
+            record: record?,
+            seed: hex::decode(seed)?,
+        })),
+        None => Ok(None),
+    }
+}
+
+/// 为奖项生成种子并公布其哈希，已有未使用的种子时直接返回它
+///
+/// 只有进入抽奖中状态、奖项和参与规则都已锁定后才能公布，
+/// 否则知道种子的人可以在公布后继续调整候选人来左右结果。
+pub(crate) fn commit(conn: &mut Connection, act_id: usize, act_seq: usize) -> Result<DrawRecord> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let record = commit_in(&tx, act_id, act_seq)?;
+    tx.commit()?;
+    Ok(record)
+}
+
+/// 在调用方的写事务中公布种子哈希，规则同 [`commit`]
+///
+/// 同时冻结当前的候选对象和权重并公布其哈希。
+pub(crate) fn commit_in(tx: &Connection, act_id: usize, act_seq: usize) -> Result<DrawRecord> {
+    state::ensure(tx, act_id, &[ActStatus::Drawing], "公布种子")?;
+    let plan = load_plan(tx, act_id, act_seq)?;
+
+    if let Some(pending) = pending(tx, act_id, act_seq)? {
+        info!(
+            "奖项{act_id}-{act_seq}已有未使用的种子: {}",
+            pending.record.seed_hash
+        );
+        return Ok(pending.record);
+    }
+
+    let inputs = draw::inputs(
+        &plan,
+        &draw::units(&eligibility(tx, act_id, act_seq)?.candidates),
+    );
+    let input_hash = inputs.hash()?;
+    let seed = rng::new_seed()?;
+    let seed_hash = rng::seed_hash(&seed);
+    let commit_time = time::OffsetDateTime::now_utc().unix_timestamp();
+    tx.execute(
+        "insert into ld_draw (act_id, act_seq, seed_hash, input_hash, inputs, seed, algorithm,
+                              commit_time)
+         values (?, ?, ?, ?, ?, ?, ?, ?)",
+        (
+            act_id,
+            act_seq,
+            &seed_hash,
+            &input_hash,
+            serde_json::to_string(&inputs)?,
+            hex::encode(seed),
+            engine::UNIFORM,
+            commit_time,
+        ),
+    )?;
+    let draw_id = tx.last_insert_rowid() as usize;
+    info!("奖项{act_id}-{act_seq}公布种子哈希: {seed_hash}，候选对象哈希: {input_hash}");
+
+    Ok(DrawRecord {
+        draw_id,
+        act_id,
+        act_seq,
+        seed_hash,
+        input_hash: Some(input_hash),
+        seed: None,
+        algorithm: engine::UNIFORM.to_owned(),
+        draw_count: None,
+        win_count: None,
+        participants: None,
+        weights: None,
+        commit_time,
+        draw_time: None,
+        split: None,
+        quota: None,
+        inputs: None,
+    })
+}
+
+/// 抽奖完成后公开种子，并冻结本次的候选人列表和实际使用的算法
+pub(crate) fn reveal(
+    conn: &Connection,
+    draw_id: usize,
+    algorithm: &str,
+    participants: &[usize],
+    weights: Option<&[u64]>,
+    draw_count: usize,
+    draw_time: i64,
+) -> Result<()> {
+    conn.execute(
+        "update ld_draw set algorithm=?, participants=?, weights=?, draw_count=?, draw_time=?
+          where draw_id=?",
+        (
+            algorithm,
+            serde_json::to_string(participants)?,
+            weights.map(serde_json::to_string).transpose()?,
+            draw_count,
+            draw_time,
+            draw_id,
+        ),
+    )?;
+    Ok(())
+}
+
+/// 保存红包的拆分参数和本次发出的金额，与 [`reveal`] 在同一个事务中调用
+pub(crate) fn save_split(
+    conn: &Connection,
+    draw_id: usize,
+    split: Split,
+    amounts: &[i64],
+) -> Result<()> {
+    let record = SplitRecord {
+        algorithm: engine::SPLIT.to_owned(),
+        split,
+        amounts: amounts.to_vec(),
+    };
+    conn.execute(
+        "update ld_draw set split=? where draw_id=?",
+        (serde_json::to_string(&record)?, draw_id),
+    )?;
+    Ok(())
+}
+
+/// 保存本次中奖者的个数，之后的为候补，与 [`reveal`] 在同一个事务中调用
+pub(crate) fn save_win_count(conn: &Connection, draw_id: usize, win_count: usize) -> Result<()> {
+    conn.execute(
+        "update ld_draw set win_count=? where draw_id=?",
+        (win_count, draw_id),
+    )?;
+    Ok(())
+}
+
+/// 保存本次抽奖的配额约束，与 [`reveal`] 在同一个事务中调用
+pub(crate) fn save_quota(conn: &Connection, draw_id: usize, strata: Strata) -> Result<()> {
+    let record = QuotaRecord {
+        algorithm: engine::QUOTA.to_owned(),
+        strata,
+    };
+    conn.execute(
+        "update ld_draw set quota=? where draw_id=?",
+        (serde_json::to_string(&record)?, draw_id),
+    )?;
+    Ok(())
+}
+
+pub(crate) fn get(conn: &Connection, draw_id: usize) -> Result<DrawRecord> {
+    let sql = format!("{SELECT_DRAW} where draw_id=?");
+    let record = conn
+        .query_row(&sql, [draw_id], |row| Ok(DrawRecord::from_row(row)))
+        .optional()?;
+
+    match record {
+        Some(record) => record,
+        None => Err(BizError::NotFound(format!("抽奖记录{draw_id}不存在")).into()),
+    }
+}
+
+/// 活动的全部种子记录
+pub(crate) fn list(conn: &Connection, act_id: usize) -> Result<Vec<DrawRecord>> {
+    let sql = format!("{SELECT_DRAW} where act_id=? order by draw_id");
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([act_id])?;
+
+    let mut records = Vec::new();
+    while let Some(row) = rows.next()? {
+        records.push(DrawRecord::from_row(row)?);
+    }
+
+    Ok(records)
+}
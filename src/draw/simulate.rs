@@ -0,0 +1,299 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::Connection;
+use serde::Serialize;
+use tracing::info;
+
+use crate::activity::{self, plan, policy};
+use crate::draw::engine::Strata;
+use crate::draw::quota;
+use crate::draw::rule::parse_flags;
+use crate::draw::{count_winners, eligibility, engine, rng, units, DrawMode, DrawUnit, Plan, Unit};
+use crate::error::BizError;
+
+/// 单次请求最多的模拟次数
+pub(crate) const MAX_RUNS: usize = 10_000;
+
+/// 单次请求最多筛选的候选人次，即模拟次数 × 各奖项的候选人数之和
+const MAX_WORK: usize = 100_000_000;
+
+/// 一个奖项的模拟结果
+#[derive(Debug, Serialize)]
+pub(crate) struct TierStats {
+    #[serde(flatten)]
+    pub plan: Plan,
+    /// 剩余名额，模拟时按抽完剩余名额计算
+    pub remaining: usize,
+    /// 不考虑本次模拟中前面奖项中奖者时的候选人数
+    pub eligible: usize,
+    /// 平均抽出人数，团体奖项为团体数
+    pub mean_winners: f64,
+    /// 候选人不足、抽不满剩余名额的次数
+    pub short_runs: usize,
+    /// 一个人都抽不出的次数
+    pub empty_runs: usize,
+}
+
+/// 一个标签的模拟结果，有多个标签的客户在每个标签下都会统计
+#[derive(Debug, Serialize)]
+pub(crate) struct FlagStats {
+    /// 没有标签的客户记为空字符串
+    pub cus_flag: String,
+    pub customers: usize,
+    /// 每次模拟的平均中奖人次
+    pub mean_wins: f64,
+    /// 每次模拟中奖人次的 5%、50%、95% 分位数
+    pub p5: usize,
+    pub p50: usize,
+    pub p95: usize,
+    /// 人均中奖次数，每人只能中一次时即中奖概率
+    pub win_rate: f64,
+    /// 中奖人次中拥有该标签的比例
+    pub share: f64,
+    /// 各奖项的平均中奖人次，与 tiers 的顺序一致
+    pub tier_wins: Vec<f64>,
+}
+
+/// 一个奖项不考虑本次模拟中奖者时的抽奖对象和配额，每次模拟在此基础上排除前面奖项的中奖者
+struct Base {
+    units: Vec<Unit>,
+    strata: Option<Strata>,
+}
+
+/// 模拟结果
+#[derive(Debug, Serialize)]
+pub(crate) struct Simulation {
+    pub act_id: usize,
+    pub runs: usize,
+    pub tiers: Vec<TierStats>,
+    pub flags: Vec<FlagStats>,
+}
+
+/// 按当前的客户、奖项、规则和中奖限制把剩余名额模拟抽取 `runs` 次
+///
+/// 每次模拟按奖项序号依次抽完剩余名额，每个奖项使用新的随机种子。
+/// 每个奖项的候选人只按 [`eligibility`] 计算一次，每次模拟再按 max_wins 和 top_tier
+/// 排除本次模拟中前面奖项的中奖者，团体有成员被排除时整个团体排除。
+/// 结果只保存在内存中，不写 ld_win_list 和 ld_draw。
+pub(crate) fn simulate(conn: &Connection, act_id: usize, runs: usize) -> Result<Simulation> {
+    if runs == 0 || runs > MAX_RUNS {
+        return Err(BizError::Invalid(format!("模拟次数必须在1到{MAX_RUNS}之间")).into());
+    }
+    activity::get(conn, act_id)?;
+    let policy = policy::get(conn, act_id)?;
+    let win_counts = policy::win_counts(conn, act_id)?;
+
+    let mut tiers = Vec::new();
+    let mut bases = Vec::new();
+    for detail in plan::list(conn, act_id)? {
+        let plan = detail.plan;
+        let remaining =
+            plan.prize_amount
+                .saturating_sub(count_winners(conn, act_id, plan.act_seq)?);
+        let candidates = eligibility(conn, act_id, plan.act_seq)?.candidates;
+        let units = units(&candidates);
+        let strata = match plan.draw_unit {
+            DrawUnit::Customer if remaining > 0 => {
+                let members = candidates
+                    .iter()
+                    .map(|c| (c.cus_id, c.cus_flag.as_deref()))
+                    .collect::<Vec<_>>();
+                quota::strata(conn, &plan, &members, remaining)?
+            }
+            _ => None,
+        };
+        tiers.push(TierStats {
+            plan,
+            remaining,
+            eligible: candidates.len(),
+            mean_winners: 0.0,
+            short_runs: 0,
+            empty_runs: 0,
+        });
+        bases.push(Base { units, strata });
+    }
+
+    //每个客户所属标签的下标
+    let mut flag_names = BTreeMap::<String, usize>::new();
+    let mut customer_flags = HashMap::<usize, Vec<usize>>::new();
+    {
+        let mut stmt = conn.prepare("select cus_id,cus_flag from ld_custom")?;
+        let mut rows = stmt.query([])?;
+        let mut customers = Vec::new();
+        while let Some(row) = rows.next()? {
+            let cus_id: usize = row.get(0)?;
+            let cus_flag: Option<String> = row.get(1)?;
+            let mut flags = cus_flag
+                .as_deref()
+                .map(parse_flags)
+                .unwrap_or_default()
+                .into_iter()
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            if flags.is_empty() {
+                flags.push(String::new());
+            }
+            flags.sort_unstable();
+            flags.dedup();
+            for flag in &flags {
+                flag_names.entry(flag.clone()).or_default();
+            }
+            customers.push((cus_id, flags));
+        }
+        for (index, value) in flag_names.values_mut().enumerate() {
+            *value = index;
+        }
+        for (cus_id, flags) in customers {
+            let indexes = flags.iter().map(|flag| flag_names[flag]).collect();
+            customer_flags.insert(cus_id, indexes);
+        }
+    }
+
+    //每次模拟的每个奖项都要从候选人中排除前面奖项的中奖者
+    let work = tiers
+        .iter()
+        .filter(|tier| tier.remaining > 0)
+        .map(|tier| tier.eligible)
+        .sum::<usize>()
+        .max(1);
+    if runs.saturating_mul(work) > MAX_WORK {
+        return Err(BizError::Invalid(format!(
+            "各奖项共{work}个候选人，最多模拟{}次",
+            (MAX_WORK / work).max(1)
+        ))
+        .into());
+    }
+
+    let mut flag_customers = vec![0usize; flag_names.len()];
+    for indexes in customer_flags.values() {
+        for &index in indexes {
+            flag_customers[index] += 1;
+        }
+    }
+    let mut flag_runs = vec![Vec::with_capacity(runs); flag_names.len()];
+    let mut flag_tier_wins = vec![vec![0usize; tiers.len()]; flag_names.len()];
+    let mut tier_winners = vec![0usize; tiers.len()];
+
+    for _ in 0..runs {
+        //本次模拟中每个客户的中奖次数，以及中过 top_tier 及以上奖项的客户
+        let mut run_counts = HashMap::<usize, usize>::new();
+        let mut run_top = HashSet::<usize>::new();
+        let mut run_wins = vec![0usize; flag_names.len()];
+
+        for (index, (tier, base)) in tiers.iter_mut().zip(&bases).enumerate() {
+            if tier.remaining == 0 {
+                continue;
+            }
+            let plan = &tier.plan;
+            let top_tier = policy
+                .top_tier
+                .is_some_and(|top_tier| plan.act_seq > top_tier);
+            let excluded = |cus_id: &usize| {
+                let count =
+                    win_counts.get(cus_id).unwrap_or(&0) + run_counts.get(cus_id).unwrap_or(&0);
+                count >= policy.max_wins || (top_tier && run_top.contains(cus_id))
+            };
+            let units = base
+                .units
+                .iter()
+                .filter(|u| !u.members.iter().any(|c| excluded(&c.cus_id)))
+                .collect::<Vec<_>>();
+            let participants = units.iter().map(|u| u.id).collect::<Vec<_>>();
+            let weights = match plan.draw_mode {
+                DrawMode::Uniform => None,
+                DrawMode::Weighted => Some(units.iter().map(|u| u.weight).collect::<Vec<_>>()),
+            };
+            let strata = base.strata.clone().map(|mut strata| {
+                for stratum in &mut strata.strata {
+                    stratum.members.retain(|cus_id| !excluded(cus_id));
+                }
+                strata
+            });
+            let algorithm = plan.draw_mode.algorithm();
+            let seed = rng::new_seed()?;
+            let winners = match &strata {
+                Some(strata) => engine::select_stratified(
+                    algorithm,
+                    &seed,
+                    &participants,
+                    weights.as_deref(),
+                    tier.remaining,
+                    strata,
+                )?,
+                None => engine::select(
+                    algorithm,
+                    &seed,
+                    &participants,
+                    weights.as_deref(),
+                    tier.remaining,
+                )?,
+            };
+
+            tier_winners[index] += winners.len();
+            if winners.len() < tier.remaining {
+                tier.short_runs += 1;
+            }
+            if winners.is_empty() {
+                tier.empty_runs += 1;
+            }
+            let winners = winners
+                .iter()
+                .filter_map(|id| units.iter().find(|u| u.id == *id))
+                .flat_map(|u| &u.members);
+            for winner in winners {
+                *run_counts.entry(winner.cus_id).or_default() += 1;
+                if policy
+                    .top_tier
+                    .is_some_and(|top_tier| plan.act_seq <= top_tier)
+                {
+                    run_top.insert(winner.cus_id);
+                }
+                for &flag in customer_flags.get(&winner.cus_id).into_iter().flatten() {
+                    run_wins[flag] += 1;
+                    flag_tier_wins[flag][index] += 1;
+                }
+            }
+        }
+
+        for (flag, count) in run_wins.into_iter().enumerate() {
+            flag_runs[flag].push(count);
+        }
+    }
+
+    let total_wins = tier_winners.iter().sum::<usize>().max(1);
+    for (tier, winners) in tiers.iter_mut().zip(tier_winners) {
+        tier.mean_winners = winners as f64 / runs as f64;
+    }
+    let flags = flag_names
+        .into_iter()
+        .map(|(cus_flag, index)| {
+            let mut counts = std::mem::take(&mut flag_runs[index]);
+            counts.sort_unstable();
+            let wins = counts.iter().sum::<usize>();
+            let percentile = |p: f64| counts[((counts.len() - 1) as f64 * p).round() as usize];
+            FlagStats {
+                cus_flag,
+                customers: flag_customers[index],
+                mean_wins: wins as f64 / runs as f64,
+                p5: percentile(0.05),
+                p50: percentile(0.5),
+                p95: percentile(0.95),
+                win_rate: wins as f64 / (flag_customers[index].max(1) * runs) as f64,
+                share: wins as f64 / total_wins as f64,
+                tier_wins: flag_tier_wins[index]
+                    .iter()
+                    .map(|&wins| wins as f64 / runs as f64)
+                    .collect(),
+            }
+        })
+        .collect();
+    info!("活动{act_id}模拟抽奖{runs}次完成");
+
+    Ok(Simulation {
+        act_id,
+        runs,
+        tiers,
+        flags,
+    })
+}
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::Connection;
+use serde::Serialize;
+
+use crate::draw::engine::{Split, Strata};
+use crate::draw::rng;
+use crate::draw::seed::{self, DrawRecord, Inputs};
+use crate::draw::{engine, load_plan, DrawUnit};
+use crate::error::BizError;
+use crate::winner::WinStatus;
+
+/// 用公开的种子、候选人列表和算法版本重新计算中奖的 cus_id
+pub(crate) fn replay(
+    algorithm: &str,
+    seed: &[u8],
+    participants: &[usize],
+    weights: Option<&[u64]>,
+    draw_count: usize,
+) -> Result<Vec<usize>> {
+    engine::select(algorithm, seed, participants, weights, draw_count)
+}
+
+/// 用公开的种子、候选人列表和冻结的配额约束重新计算按配额抽出的 cus_id
+pub(crate) fn replay_stratified(
+    algorithm: &str,
+    seed: &[u8],
+    participants: &[usize],
+    weights: Option<&[u64]>,
+    draw_count: usize,
+    strata: &Strata,
+) -> Result<Vec<usize>> {
+    engine::select_stratified(algorithm, seed, participants, weights, draw_count, strata)
+}
+
+/// 用公开的种子和拆分参数重新计算红包金额
+pub(crate) fn replay_split(seed: &[u8], split: &Split) -> Result<Vec<i64>> {
+    engine::split(seed, split)
+}
+
+/// 一次抽奖的复算结果
+#[derive(Debug, Serialize)]
+pub(crate) struct DrawCheck {
+    pub draw_id: usize,
+    pub seed_hash: String,
+    pub algorithm: String,
+    /// 公开的种子与抽奖前公布的哈希是否一致
+    pub seed_matched: bool,
+    /// 公布种子哈希时冻结的候选对象与公布的哈希一致，且抽奖时的候选对象只少了之后在本活动中奖的，
+    /// 公布后新增候选人或调整权重都会不一致；早期没有冻结候选对象的记录为空
+    pub inputs_matched: Option<bool>,
+    /// 复算出的抽取顺序与 ld_win_list 是否一致
+    pub matched: bool,
+    /// 按抽取顺序前 win_count 个为中奖者、其余为候补，递补的候补除外
+    pub status_matched: bool,
+    /// 按抽取顺序排列的 cus_id，团体奖项为 team_id
+    pub expected: Vec<usize>,
+    pub actual: Vec<usize>,
+    /// 复算应中奖但 ld_win_list 中没有的 cus_id
+    pub missing: Vec<usize>,
+    /// ld_win_list 中有但复算不应中奖的 cus_id
+    pub unexpected: Vec<usize>,
+    /// 红包奖项复算出的金额与 ld_draw 和 ld_win_list 中的记录是否一致，其他奖品类型为空
+    pub amounts_matched: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Verification {
+    pub act_id: usize,
+    pub act_seq: usize,
+    pub matched: bool,
+    pub draws: Vec<DrawCheck>,
+    /// ld_win_list 中不属于任何抽奖、转盘或即时抽奖记录的 cus_id，通常是直接改库产生的
+    pub unrecorded: Vec<usize>,
+}
+
+fn check(conn: &Connection, record: DrawRecord) -> Result<DrawCheck> {
+    let (seed, participants, draw_count) =
+        match (&record.seed, &record.participants, record.draw_count) {
+            (Some(seed), Some(participants), Some(draw_count)) => {
+                (hex::decode(seed)?, participants, draw_count)
+            }
+            _ => {
+                return Err(
+                    BizError::Conflict(format!("抽奖记录{}还没有公开", record.draw_id)).into(),
+                )
+            }
+        };
+
+    let expected = match &record.quota {
+        Some(quota) => replay_stratified(
+            &record.algorithm,
+            &seed,
+            participants,
+            record.weights.as_deref(),
+            draw_count,
+            &quota.strata,
+        )?,
+        None => replay(
+            &record.algorithm,
+            &seed,
+            participants,
+            record.weights.as_deref(),
+            draw_count,
+        )?,
+    };
+    //团体奖项按团体复算，每个团体取第一条成员记录
+    let mut stmt = conn.prepare(
+        "select ifnull(team_id,cus_id),win_status,replaces is not null,min(win_id)
+           from ld_win_list where draw_id=?
+          group by ifnull(team_id,-win_id) order by min(win_id)",
+    )?;
+    let rows = stmt
+        .query_map([record.draw_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<Result<Vec<(usize, i64, bool)>, _>>()?;
+    let actual = rows.iter().map(|&(id, ..)| id).collect::<Vec<_>>();
+
+    //中奖者作废后仍在原位置；候补递补后变为中奖，但会记录 replaces
+    let win_count = record.win_count.unwrap_or(draw_count);
+    let status_matched = rows
+        .iter()
+        .enumerate()
+        .all(|(i, &(_, win_status, promoted))| {
+            let alternate = win_status == WinStatus::Alternate as i64;
+            if i < win_count {
+                !alternate
+            } else {
+                alternate || promoted
+            }
+        });
+
+    let missing = expected
+        .iter()
+        .filter(|id| !actual.contains(id))
+        .copied()
+        .collect::<Vec<_>>();
+    let unexpected = actual
+        .iter()
+        .filter(|id| !expected.contains(id))
+        .copied()
+        .collect::<Vec<_>>();
+    let seed_matched = rng::seed_hash(&seed) == record.seed_hash;
+    let inputs_matched = match (&record.inputs, &record.input_hash) {
+        (Some(inputs), Some(input_hash)) => Some(
+            inputs.hash()? == *input_hash && inputs_unchanged(conn, &record, inputs, participants)?,
+        ),
+        _ => None,
+    };
+
+    //复算的前几份应等于记录的金额，中奖记录按抽取顺序领取这几份
+    let amounts_matched = match &record.split {
+        Some(split) => {
+            let replayed = replay_split(&seed, &split.split)?;
+            let mut stmt = conn.prepare(
+                "select amount from ld_win_list where draw_id=? order by win_id limit ?",
+            )?;
+            let recorded = stmt
+                .query_map((record.draw_id, split.amounts.len()), |row| row.get(0))?
+                .collect::<Result<Vec<Option<i64>>, _>>()?;
+            Some(
+                replayed.starts_with(&split.amounts)
+                    && recorded
+                        .into_iter()
+                        .eq(split.amounts.iter().copied().map(Some)),
+            )
+        }
+        None => None,
+    };
+
+    Ok(DrawCheck {
+        draw_id: record.draw_id,
+        seed_hash: record.seed_hash,
+        algorithm: record.algorithm,
+        seed_matched,
+        inputs_matched,
+        matched: seed_matched
+            && inputs_matched != Some(false)
+            && expected == actual
+            && status_matched
+            && amounts_matched != Some(false),
+        status_matched,
+        expected,
+        actual,
+        missing,
+        unexpected,
+        amounts_matched,
+    })
+}
+
+/// 抽奖时的候选对象是否都在公布种子时冻结的候选对象中且权重不变，
+/// 冻结后少了的候选对象必须在公布种子之后、本次抽奖之前在本活动中过奖（团体为有成员中过奖）
+fn inputs_unchanged(
+    conn: &Connection,
+    record: &DrawRecord,
+    inputs: &Inputs,
+    participants: &[usize],
+) -> Result<bool> {
+    let frozen = inputs
+        .participants
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let weight = inputs
+                .weights
+                .as_ref()
+                .and_then(|weights| weights.get(i).copied());
+            (*id, weight)
+        })
+        .collect::<HashMap<_, _>>();
+    let unchanged = participants.iter().enumerate().all(|(i, id)| {
+        frozen.get(id).is_some_and(|weight| {
+            *weight
+                == record
+                    .weights
+                    .as_ref()
+                    .and_then(|weights| weights.get(i).copied())
+        })
+    });
+    if !unchanged {
+        return Ok(false);
+    }
+
+    let plan = load_plan(conn, record.act_id, record.act_seq)?;
+    let sql = match plan.draw_unit {
+        DrawUnit::Customer => {
+            "select exists(select 1 from ld_win_list
+                                where act_id=?1 and cus_id=?2 and win_time>=?3 and win_time<=?4)"
+        }
+        DrawUnit::Team => {
+            "select exists(select 1 from ld_win_list
+                            where act_id=?1 and win_time>=?3 and win_time<=?4
+                              and cus_id in (select cus_id from ld_team_member where team_id=?2))"
+        }
+    };
+    let mut stmt = conn.prepare(sql)?;
+    for id in inputs
+        .participants
+        .iter()
+        .filter(|id| !participants.contains(id))
+    {
+        let won: bool = stmt.query_row(
+            (record.act_id, id, record.commit_time, record.draw_time),
+            |row| row.get(0),
+        )?;
+        if !won {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// 复算奖项的每一次抽奖，并与 ld_win_list 中的中奖记录比对
+pub(crate) fn verify(conn: &Connection, act_id: usize, act_seq: usize) -> Result<Verification> {
+    load_plan(conn, act_id, act_seq)?;
+
+    let mut draws = Vec::new();
+    for record in seed::list(conn, act_id)?
+        .into_iter()
+        .filter(|record| record.act_seq == act_seq && record.draw_time.is_some())
+    {
+        draws.push(check(conn, record)?);
+    }
+
+    if draws.is_empty() {
+        return Err(BizError::NotFound(format!("奖项{act_id}-{act_seq}还没有抽奖记录")).into());
+    }
+
+    let mut stmt = conn.prepare(
+        "select cus_id from ld_win_list
+          where act_id=? and act_seq=?
+            and (draw_id is null or draw_id not in (select draw_id from ld_draw))
+            and win_id not in (select win_id from ld_wheel_spin where win_id is not null)
+            and win_id not in (select win_id from ld_instant_play where win_id is not null)
+          order by win_id",
+    )?;
+    let unrecorded = stmt
+        .query_map([act_id, act_seq], |row| row.get(0))?
+        .collect::<Result<Vec<usize>, _>>()?;
+
+    Ok(Verification {
+        act_id,
+        act_seq,
+        matched: unrecorded.is_empty() && draws.iter().all(|draw| draw.matched),
+        draws,
+        unrecorded,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw::{self, DrawResult};
+    use crate::test_db;
+    use crate::winner::replace;
+
+    /// 抽奖中的活动 1 有 20 个客户，奖项 1 抽出 3 个中奖者和 2 个候补
+    fn drawn() -> (Connection, DrawResult) {
+        let mut conn = test_db::open();
+        conn.execute_batch(
+            "insert into ld_activity (act_id, act_name, act_status) values (1, '年会', 3);
+             insert into ld_plan (act_id, act_seq, act_prize, prize_amount) values (1, 1, '一等奖', 3);",
+        )
+        .unwrap();
+        for cus_id in 1..=20 {
+            conn.execute(
+                "insert into ld_custom (cus_id, cus_nickname) values (?, ?)",
+                (cus_id, format!("客户{cus_id}")),
+            )
+            .unwrap();
+        }
+        seed::commit(&mut conn, 1, 1).unwrap();
+        let result = draw::draw(&mut conn, 1, 1, None, 2).unwrap();
+        (conn, result)
+    }
+
+    fn win_id(conn: &Connection, cus_id: usize) -> usize {
+        conn.query_row(
+            "select win_id from ld_win_list where cus_id=?",
+            [cus_id],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn verify_matches_untouched_draw() {
+        let (conn, result) = drawn();
+        let verification = verify(&conn, 1, 1).unwrap();
+        assert!(verification.matched);
+        assert!(verification.unrecorded.is_empty());
+
+        let check = &verification.draws[0];
+        assert!(check.seed_matched && check.status_matched);
+        assert_eq!(check.inputs_matched, Some(true));
+        let drawn = result
+            .winners
+            .iter()
+            .chain(&result.alternates)
+            .map(|c| c.cus_id)
+            .collect::<Vec<_>>();
+        assert_eq!(check.expected, drawn);
+        assert_eq!(check.actual, drawn);
+    }
+
+    #[test]
+    fn verify_matches_after_forfeit_and_promote() {
+        let (mut conn, result) = drawn();
+        let forfeited = win_id(&conn, result.winners[0].cus_id);
+        replace::forfeit(&mut conn, forfeited, "联系不上", 1).unwrap();
+        let promoted = replace::promote(&mut conn, forfeited, None).unwrap();
+        assert_eq!(promoted.cus_id, result.alternates[0].cus_id);
+        assert_eq!(promoted.replaces, Some(forfeited));
+
+        let verification = verify(&conn, 1, 1).unwrap();
+        assert!(verification.matched);
+        assert!(verification.draws[0].status_matched);
+    }
+
+    #[test]
+    fn verify_reports_altered_winner() {
+        let (conn, result) = drawn();
+        let original = result.winners[0].cus_id;
+        let outsider = (1..=20)
+            .find(|cus_id| {
+                !result
+                    .winners
+                    .iter()
+                    .chain(&result.alternates)
+                    .any(|c| c.cus_id == *cus_id)
+            })
+            .unwrap();
+        conn.execute(
+            "update ld_win_list set cus_id=? where cus_id=?",
+            [outsider, original],
+        )
+        .unwrap();
+
+        let verification = verify(&conn, 1, 1).unwrap();
+        assert!(!verification.matched);
+        let check = &verification.draws[0];
+        assert!(!check.matched);
+        assert_eq!(check.missing, vec![original]);
+        assert_eq!(check.unexpected, vec![outsider]);
+    }
+
+    #[test]
+    fn verify_reports_alternate_marked_won() {
+        let (conn, result) = drawn();
+        //候补直接改成中奖，没有经过递补
+        conn.execute(
+            "update ld_win_list set win_status=? where cus_id=?",
+            (WinStatus::Won as i64, result.alternates[0].cus_id),
+        )
+        .unwrap();
+
+        let verification = verify(&conn, 1, 1).unwrap();
+        assert!(!verification.matched);
+        assert!(!verification.draws[0].status_matched);
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::activity::state;
+use crate::error::BizError;
+
+/// 单个客户的最大权重，避免总权重溢出
+pub(crate) const MAX_WEIGHT: u64 = 1_000_000;
+
+/// 客户在某个活动中的权重，对应 ld_custom_weight 的一行
+///
+/// 权重可以是积分、券数或司龄等，按权重抽奖时中奖概率与权重成正比。
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Weight {
+    pub cus_id: usize,
+    pub weight: u64,
+    #[serde(default)]
+    pub weight_note: Option<String>,
+}
+
+pub(crate) fn list(conn: &Connection, act_id: usize) -> Result<Vec<Weight>> {
+    let mut stmt = conn.prepare(
+        "select cus_id,weight,weight_note from ld_custom_weight where act_id=? order by cus_id",
+    )?;
+    let mut rows = stmt.query([act_id])?;
+
+    let mut weights = Vec::new();
+    while let Some(row) = rows.next()? {
+        weights.push(Weight {
+            cus_id: row.get(0)?,
+            weight: row.get::<_, i64>(1)?.max(0) as u64,
+            weight_note: row.get(2)?,
+        });
+    }
+
+    Ok(weights)
+}
+
+/// 批量保存权重，已存在的覆盖
+pub(crate) fn save(conn: &mut Connection, act_id: usize, weights: &[Weight]) -> Result<usize> {
+    let tx = conn.transaction()?;
+
+    state::ensure_plan_editable(&tx, act_id)?;
+
+    {
+        let mut check = tx.prepare("select exists(select 1 from ld_custom where cus_id=?)")?;
+        let mut upsert = tx.prepare(
+            "insert into ld_custom_weight (act_id, cus_id, weight, weight_note) values (?, ?, ?, ?)
+             on conflict (act_id, cus_id) do update set weight=excluded.weight,
+                                                        weight_note=excluded.weight_note",
+        )?;
+        for weight in weights {
+            if weight.weight > MAX_WEIGHT {
+                return Err(BizError::Invalid(format!(
+                    "客户{}的权重{}超过上限{MAX_WEIGHT}",
+                    weight.cus_id, weight.weight
+                ))
+                .into());
+            }
+            if !check.query_row([weight.cus_id], |row| row.get::<_, bool>(0))? {
+                return Err(BizError::NotFound(format!("客户{}不存在", weight.cus_id)).into());
+            }
+            upsert.execute((act_id, weight.cus_id, weight.weight, &weight.weight_note))?;
+        }
+    }
+    tx.commit()?;
+    info!("活动{act_id}保存{}个权重", weights.len());
+
+    Ok(weights.len())
+}
+
+/// 删除权重，删除后按默认权重 1 参与
+pub(crate) fn remove(conn: &Connection, act_id: usize, cus_id: usize) -> Result<usize> {
+    state::ensure_plan_editable(conn, act_id)?;
+    Ok(conn.execute(
+        "delete from ld_custom_weight where act_id=? and cus_id=?",
+        [act_id, cus_id],
+    )?)
+}
@@ -0,0 +1,36 @@
+use std::fmt::{Display, Formatter};
+
+/// 业务错误，web 层会把它转换成对应状态码的 JSON 错误响应
+#[derive(Debug)]
+pub(crate) enum BizError {
+    /// 请求的数据不存在
+    NotFound(String),
+    /// 参数校验失败
+    Invalid(String),
+    /// 与当前数据状态冲突，例如奖品已抽完
+    Conflict(String),
+}
+
+impl BizError {
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            BizError::NotFound(_) => "not_found",
+            BizError::Invalid(_) => "invalid",
+            BizError::Conflict(_) => "conflict",
+        }
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        match self {
+            BizError::NotFound(msg) | BizError::Invalid(msg) | BizError::Conflict(msg) => msg,
+        }
+    }
+}
+
+impl Display for BizError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for BizError {}
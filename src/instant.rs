@@ -0,0 +1,566 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{Connection, OptionalExtension, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::activity;
+use crate::activity::state::{self, ActStatus};
+use crate::audit::AuditEvent;
+use crate::checkin;
+use crate::draw::rng::{self, DrawRng};
+use crate::draw::{self, count_winners, engine, load_plan, DrawUnit};
+use crate::error::BizError;
+use crate::winner;
+
+/// 中奖概率的分母，odds 以万分之一为单位
+const ODDS_BASE: u64 = 10000;
+
+/// 奖池奖券数的上限，保存时会为每张奖券写一行 ld_instant_ticket
+const MAX_POOL_SIZE: usize = 1_000_000;
+
+/// ld_instant.odds_mode 的取值
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OddsMode {
+    /// 0: 预先生成并洗好的奖池，每次按顺序揭开一张奖券
+    Pool = 0,
+    /// 1: 按概率即时开奖，奖品在开始和结束时间之间均匀释放
+    Spread = 1,
+}
+
+impl TryFrom<i64> for OddsMode {
+    type Error = BizError;
+
+    fn try_from(value: i64) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(OddsMode::Pool),
+            1 => Ok(OddsMode::Spread),
+            _ => Err(BizError::Invalid(format!("未知的开奖方式: {value}"))),
+        }
+    }
+}
+
+/// 参与即时抽奖的奖项，库存即奖项剩余的名额
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct InstantPrize {
+    pub act_seq: usize,
+    pub act_prize: Option<String>,
+    /// 按概率开奖时每次抽中该奖项的概率，单位为万分之一
+    pub odds: u64,
+    pub stock: usize,
+}
+
+/// 活动的即时抽奖配置，对应 ld_instant 的一行
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Instant {
+    pub act_id: usize,
+    pub odds_mode: OddsMode,
+    /// 每个客户最多的抽奖次数
+    pub max_plays: usize,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    /// 奖池的奖券总数，只用于奖池模式
+    pub pool_size: Option<usize>,
+    pub prizes: Vec<InstantPrize>,
+    /// 奖池洗牌种子的哈希，生成奖池时公布
+    pub seed_hash: Option<String>,
+    /// 奖池洗牌种子，活动结束后公开
+    pub seed: Option<String>,
+    /// 奖池中还没有揭开的奖券数
+    pub tickets_left: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PrizeArgs {
+    pub act_seq: usize,
+    #[serde(default)]
+    pub odds: u64,
+}
+
+/// 保存即时抽奖配置的参数
+#[derive(Debug, Deserialize)]
+pub(crate) struct InstantArgs {
+    pub odds_mode: OddsMode,
+    #[serde(default = "default_max_plays")]
+    pub max_plays: usize,
+    #[serde(default)]
+    pub start_time: Option<i64>,
+    #[serde(default)]
+    pub end_time: Option<i64>,
+    #[serde(default)]
+    pub pool_size: Option<usize>,
+    pub prizes: Vec<PrizeArgs>,
+}
+
+fn default_max_plays() -> usize {
+    1
+}
+
+impl InstantArgs {
+    /// 校验参数，返回各奖项的奖品数量
+    fn validate(&self, conn: &Connection, act_id: usize) -> Result<Vec<usize>> {
+        if self.max_plays == 0 {
+            return Err(BizError::Invalid("每人抽奖次数必须大于0".to_owned()).into());
+        }
+        if self.prizes.is_empty() {
+            return Err(BizError::Invalid("至少需要一个奖项".to_owned()).into());
+        }
+        let mut seqs = HashSet::new();
+        let mut amounts = Vec::with_capacity(self.prizes.len());
+        for prize in &self.prizes {
+            if !seqs.insert(prize.act_seq) {
+                return Err(BizError::Invalid(format!("奖项{}重复", prize.act_seq)).into());
+            }
+            let plan = load_plan(conn, act_id, prize.act_seq)?;
+            if plan.draw_unit == DrawUnit::Team {
+                return Err(BizError::Invalid(format!(
+                    "奖项{}是团体奖项，不能用于即时抽奖",
+                    prize.act_seq
+                ))
+                .into());
+            }
+            //先逐个检查，概率之和才不会溢出
+            if prize.odds > ODDS_BASE {
+                return Err(BizError::Invalid(format!(
+                    "奖项{}的概率{}超过{ODDS_BASE}",
+                    prize.act_seq, prize.odds
+                ))
+                .into());
+            }
+            amounts.push(plan.prize_amount);
+        }
+        if let (Some(start), Some(end)) = (self.start_time, self.end_time) {
+            if start >= end {
+                return Err(BizError::Invalid("开始时间必须早于结束时间".to_owned()).into());
+            }
+        }
+
+        match self.odds_mode {
+            OddsMode::Pool => {
+                let prizes = amounts.iter().sum::<usize>();
+                match self.pool_size {
+                    Some(pool_size) if pool_size > MAX_POOL_SIZE => {
+                        return Err(BizError::Invalid(format!(
+                            "奖池的奖券数{pool_size}超过上限{MAX_POOL_SIZE}"
+                        ))
+                        .into())
+                    }
+                    Some(pool_size) if pool_size >= prizes => {}
+                    _ => {
+                        return Err(BizError::Invalid(format!(
+                            "奖池的奖券数不能少于奖品总数{prizes}"
+                        ))
+                        .into())
+                    }
+                }
+            }
+            OddsMode::Spread => {
+                if self.start_time.is_none() || self.end_time.is_none() {
+                    return Err(
+                        BizError::Invalid("按概率开奖必须指定开始和结束时间".to_owned()).into(),
+                    );
+                }
+                if self.prizes.iter().map(|prize| prize.odds).sum::<u64>() > ODDS_BASE {
+                    return Err(BizError::Invalid("各奖项的概率之和不能超过100%".to_owned()).into());
+                }
+            }
+        }
+        Ok(amounts)
+    }
+}
+
+pub(crate) fn get(conn: &Connection, act_id: usize) -> Result<Instant> {
+    let status = state::status(conn, act_id)?;
+    let instant = conn
+        .query_row(
+            "select odds_mode,max_plays,start_time,end_time,pool_size,seed,seed_hash
+               from ld_instant where act_id=?",
+            [act_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    Instant {
+                        act_id,
+                        odds_mode: OddsMode::Pool,
+                        max_plays: row.get(1)?,
+                        start_time: row.get(2)?,
+                        end_time: row.get(3)?,
+                        pool_size: row.get(4)?,
+                        prizes: Vec::new(),
+                        //活动结束前不能泄露奖池的顺序
+                        seed: row
+                            .get::<_, Option<String>>(5)?
+                            .filter(|_| matches!(status, ActStatus::Closed | ActStatus::Archived)),
+                        seed_hash: row.get(6)?,
+                        tickets_left: None,
+                    },
+                ))
+            },
+        )
+        .optional()?;
+    let Some((odds_mode, mut instant)) = instant else {
+        return Err(BizError::NotFound(format!("活动{act_id}没有配置即时抽奖")).into());
+    };
+    instant.odds_mode = OddsMode::try_from(odds_mode)?;
+
+    let mut stmt = conn.prepare(
+        "select i.act_seq,p.act_prize,i.odds,ifnull(p.prize_amount,0)
+           from ld_instant_prize i
+           left join ld_plan p on p.act_id=i.act_id and p.act_seq=i.act_seq
+          where i.act_id=? order by i.act_seq",
+    )?;
+    let mut rows = stmt.query([act_id])?;
+    while let Some(row) = rows.next()? {
+        let act_seq: usize = row.get(0)?;
+        let prize_amount: usize = row.get(3)?;
+        instant.prizes.push(InstantPrize {
+            act_seq,
+            act_prize: row.get(1)?,
+            odds: row.get(2)?,
+            stock: prize_amount.saturating_sub(count_winners(conn, act_id, act_seq)?),
+        });
+    }
+
+    if instant.odds_mode == OddsMode::Pool {
+        instant.tickets_left = Some(conn.query_row(
+            "select count(*) from ld_instant_ticket where act_id=? and play_id is null",
+            [act_id],
+            |row| row.get(0),
+        )?);
+    }
+
+    Ok(instant)
+}
+
+/// 保存即时抽奖配置，开始抽奖后不能修改
+///
+/// 奖池模式会重新生成奖池：先按奖项序号排列中奖券，再补足未中奖券，
+/// 然后用新种子按 sha256-ctr-v1 算法整体洗牌，第 i 张奖券是洗牌结果中的第 i 个下标。
+pub(crate) fn save(conn: &mut Connection, act_id: usize, args: &InstantArgs) -> Result<Instant> {
+    let tx = conn.transaction()?;
+    state::ensure_plan_editable(&tx, act_id)?;
+    let amounts = args.validate(&tx, act_id)?;
+
+    let seed = match args.odds_mode {
+        OddsMode::Pool => Some(rng::new_seed()?),
+        OddsMode::Spread => None,
+    };
+    tx.execute(
+        "insert or replace into ld_instant (act_id, odds_mode, max_plays, start_time, end_time,
+                                            pool_size, seed, seed_hash)
+         values (?, ?, ?, ?, ?, ?, ?, ?)",
+        (
+            act_id,
+            args.odds_mode as i64,
+            args.max_plays,
+            args.start_time,
+            args.end_time,
+            args.pool_size.filter(|_| seed.is_some()),
+            seed.map(hex::encode),
+            seed.map(|seed| rng::seed_hash(&seed)),
+        ),
+    )?;
+    tx.execute("delete from ld_instant_prize where act_id=?", [act_id])?;
+    tx.execute("delete from ld_instant_ticket where act_id=?", [act_id])?;
+    {
+        let mut stmt =
+            tx.prepare("insert into ld_instant_prize (act_id, act_seq, odds) values (?, ?, ?)")?;
+        for prize in &args.prizes {
+            stmt.execute((act_id, prize.act_seq, prize.odds as i64))?;
+        }
+    }
+
+    if let (Some(seed), Some(pool_size)) = (seed, args.pool_size) {
+        let mut prizes = args.prizes.iter().zip(&amounts).collect::<Vec<_>>();
+        prizes.sort_by_key(|(prize, _)| prize.act_seq);
+        let mut tickets = Vec::with_capacity(pool_size);
+        for (prize, amount) in prizes {
+            tickets.extend(std::iter::repeat_n(Some(prize.act_seq), *amount));
+        }
+        tickets.resize(pool_size, None);
+
+        let positions = (0..pool_size).collect::<Vec<_>>();
+        let order = engine::select(engine::UNIFORM, &seed, &positions, None, pool_size)?;
+        let mut stmt = tx.prepare(
+            "insert into ld_instant_ticket (act_id, ticket_no, act_seq) values (?, ?, ?)",
+        )?;
+        for (ticket_no, position) in order.into_iter().enumerate() {
+            stmt.execute((act_id, ticket_no, tickets[position]))?;
+        }
+    }
+    let instant = get(&tx, act_id)?;
+    tx.commit()?;
+    info!("活动{act_id}保存即时抽奖配置: {args:?}");
+
+    Ok(instant)
+}
+
+/// 一次即时抽奖的结果，对应 ld_instant_play 的一行
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Play {
+    pub play_id: usize,
+    pub act_id: usize,
+    pub cus_id: usize,
+    pub won: bool,
+    pub act_seq: Option<usize>,
+    pub act_prize: Option<String>,
+    pub win_id: Option<usize>,
+    /// 红包奖项的金额（分）
+    pub amount: Option<i64>,
+    /// 中奖后领奖使用的兑奖码
+    pub redeem_code: Option<String>,
+    /// 本次使用的随机种子，按概率开奖时 `roll = 随机数 % 10000`
+    pub seed: String,
+    pub roll: Option<u64>,
+    /// 奖池模式揭开的奖券序号
+    pub ticket_no: Option<usize>,
+    pub play_time: i64,
+}
+
+const SELECT_PLAY: &str =
+    "select p.play_id,p.act_id,p.cus_id,p.act_seq,pl.act_prize,p.win_id,w.amount,l.redeem_code,
+            p.seed,p.roll,p.ticket_no,p.play_time
+       from ld_instant_play p
+       left join ld_plan pl on pl.act_id=p.act_id and pl.act_seq=p.act_seq
+       left join ld_win_list w on w.win_id=p.win_id
+       left join ld_claim l on l.win_id=p.win_id";
+
+impl Play {
+    fn from_row(row: &Row) -> Result<Self> {
+        let win_id: Option<usize> = row.get(5)?;
+        Ok(Play {
+            play_id: row.get(0)?,
+            act_id: row.get(1)?,
+            cus_id: row.get(2)?,
+            won: win_id.is_some(),
+            act_seq: row.get(3)?,
+            act_prize: row.get(4)?,
+            win_id,
+            amount: row.get(6)?,
+            redeem_code: row.get(7)?,
+            seed: row.get(8)?,
+            roll: row.get(9)?,
+            ticket_no: row.get(10)?,
+            play_time: row.get(11)?,
+        })
+    }
+}
+
+pub(crate) fn get_play(conn: &Connection, play_id: usize) -> Result<Play> {
+    let sql = format!("{SELECT_PLAY} where p.play_id=?");
+    let play = conn
+        .query_row(&sql, [play_id], |row| Ok(Play::from_row(row)))
+        .optional()?;
+
+    match play {
+        Some(play) => play,
+        None => Err(BizError::NotFound(format!("抽奖记录{play_id}不存在")).into()),
+    }
+}
+
+/// 活动的即时抽奖记录，`cus_id` 为空时返回全部
+pub(crate) fn plays(conn: &Connection, act_id: usize, cus_id: Option<usize>) -> Result<Vec<Play>> {
+    activity::get(conn, act_id)?;
+    let sql = format!(
+        "{SELECT_PLAY} where p.act_id=?1 and (?2 is null or p.cus_id=?2) order by p.play_id"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query((act_id, cus_id))?;
+
+    let mut plays = Vec::new();
+    while let Some(row) = rows.next()? {
+        plays.push(Play::from_row(row)?);
+    }
+
+    Ok(plays)
+}
+
+/// 奖项还有库存，且客户符合该奖项的参与条件
+fn winnable(conn: &Connection, act_id: usize, act_seq: usize, cus_id: usize) -> Result<bool> {
+    let prize_amount = load_plan(conn, act_id, act_seq)?.prize_amount;
+    if count_winners(conn, act_id, act_seq)? >= prize_amount {
+        return Ok(false);
+    }
+    Ok(draw::eligibility(conn, act_id, act_seq)?
+        .candidates
+        .iter()
+        .any(|c| c.cus_id == cus_id))
+}
+
+/// 按时间均匀释放后，`now` 时刻奖项还可以抽中的名额
+fn released(conn: &Connection, instant: &Instant, act_seq: usize, now: i64) -> Result<usize> {
+    let (Some(start), Some(end)) = (instant.start_time, instant.end_time) else {
+        return Ok(0);
+    };
+    let prize_amount = load_plan(conn, instant.act_id, act_seq)?.prize_amount;
+    let elapsed = (now - start).clamp(0, end - start) as u128;
+    let released = (prize_amount as u128 * elapsed / (end - start) as u128) as usize;
+    Ok(released.saturating_sub(count_winners(conn, instant.act_id, act_seq)?))
+}
+
+/// 客户抽一次，立即返回是否中奖
+///
+/// 整个过程在一个写事务中完成，并发请求会依次执行，抽奖次数和库存不会超出限制。
+/// 奖池模式按顺序揭开下一张奖券，客户不符合该奖项的条件时改为揭开下一张未中奖券，
+/// 中奖券留给后面的客户。按概率开奖时，抽中的奖项还没有释放或库存不足都算未中奖。
+pub(crate) fn play(conn: &mut Connection, act_id: usize, cus_id: usize) -> Result<Play> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    state::ensure(&tx, act_id, &[ActStatus::Drawing], "即时抽奖")?;
+    let instant = get(&tx, act_id)?;
+    let exists: bool = tx.query_row(
+        "select exists(select 1 from ld_custom where cus_id=?)",
+        [cus_id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(BizError::NotFound(format!("客户{cus_id}不存在")).into());
+    }
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    if instant.start_time.is_some_and(|start| now < start) {
+        return Err(BizError::Conflict(format!("活动{act_id}的即时抽奖还没有开始")).into());
+    }
+    if instant.end_time.is_some_and(|end| now > end) {
+        return Err(BizError::Conflict(format!("活动{act_id}的即时抽奖已经结束")).into());
+    }
+    let played: usize = tx.query_row(
+        "select count(*) from ld_instant_play where act_id=? and cus_id=?",
+        [act_id, cus_id],
+        |row| row.get(0),
+    )?;
+    if played >= instant.max_plays {
+        return Err(BizError::Conflict(format!("客户{cus_id}的抽奖次数已用完")).into());
+    }
+
+    let seed = rng::new_seed()?;
+    let (act_seq, roll, ticket) = match instant.odds_mode {
+        OddsMode::Pool => {
+            let next_ticket = |losing: bool| {
+                tx.query_row(
+                    "select ticket_id,ticket_no,act_seq from ld_instant_ticket
+                      where act_id=? and play_id is null and (?=0 or act_seq is null)
+                      order by ticket_no limit 1",
+                    (act_id, losing),
+                    |row| {
+                        Ok((
+                            row.get::<_, usize>(0)?,
+                            row.get::<_, usize>(1)?,
+                            row.get::<_, Option<usize>>(2)?,
+                        ))
+                    },
+                )
+                .optional()
+            };
+            let mut ticket = next_ticket(false)?
+                .ok_or_else(|| BizError::Conflict(format!("活动{act_id}的奖池已经抽完")))?;
+            if let Some(act_seq) = ticket.2 {
+                if !winnable(&tx, act_id, act_seq, cus_id)? {
+                    info!("客户{cus_id}不能中奖项{act_seq}，改为揭开下一张未中奖券");
+                    ticket = next_ticket(true)?.ok_or_else(|| {
+                        BizError::Conflict(format!("活动{act_id}的奖池没有可以揭开的奖券"))
+                    })?;
+                }
+            }
+            (ticket.2, None, Some(ticket))
+        }
+        OddsMode::Spread => {
+            let roll = DrawRng::new(&seed).next_u64() % ODDS_BASE;
+            let mut bound = 0;
+            let mut landed = None;
+            for prize in &instant.prizes {
+                bound += prize.odds;
+                if roll < bound {
+                    landed = Some(prize.act_seq);
+                    break;
+                }
+            }
+            let act_seq = match landed {
+                Some(act_seq)
+                    if released(&tx, &instant, act_seq, now)? > 0
+                        && winnable(&tx, act_id, act_seq, cus_id)? =>
+                {
+                    Some(act_seq)
+                }
+                _ => None,
+            };
+            (act_seq, Some(roll), None)
+        }
+    };
+
+    let win_id = match act_seq {
+        Some(act_seq) => Some(winner::award(
+            &tx,
+            AuditEvent::InstantWin,
+            act_id,
+            act_seq,
+            cus_id,
+            &seed,
+            now,
+        )?),
+        None => None,
+    };
+    tx.execute(
+        "insert into ld_instant_play (act_id, cus_id, seed, roll, ticket_no, act_seq, win_id,
+                                      play_time)
+         values (?, ?, ?, ?, ?, ?, ?, ?)",
+        (
+            act_id,
+            cus_id,
+            hex::encode(seed),
+            roll.map(|roll| roll as i64),
+            ticket.map(|ticket| ticket.1),
+            act_seq,
+            win_id,
+            now,
+        ),
+    )?;
+    let play_id = tx.last_insert_rowid() as usize;
+    if let Some((ticket_id, ..)) = ticket {
+        tx.execute(
+            "update ld_instant_ticket set play_id=? where ticket_id=?",
+            [play_id, ticket_id],
+        )?;
+    }
+    let play = get_play(&tx, play_id)?;
+    tx.commit()?;
+    info!(
+        "活动{act_id} 客户{cus_id}即时抽奖，中奖: {:?}",
+        play.act_seq
+    );
+
+    Ok(play)
+}
+
+/// 凭签到码查询的抽奖信息
+#[derive(Debug, Serialize)]
+pub(crate) struct Player {
+    pub act_id: usize,
+    pub cus_id: usize,
+    pub cus_nickname: String,
+    /// 剩余的抽奖次数
+    pub plays_left: usize,
+    pub plays: Vec<Play>,
+}
+
+/// 凭签到码查询自己的抽奖次数和记录
+pub(crate) fn player(conn: &Connection, token: &str) -> Result<Player> {
+    let attendee = checkin::get(conn, token)?;
+    let instant = get(conn, attendee.act_id)?;
+    let plays = plays(conn, attendee.act_id, Some(attendee.cus_id))?;
+
+    Ok(Player {
+        act_id: attendee.act_id,
+        cus_id: attendee.cus_id,
+        cus_nickname: attendee.cus_nickname,
+        plays_left: instant.max_plays.saturating_sub(plays.len()),
+        plays,
+    })
+}
+
+/// 客户凭签到码自助抽奖
+pub(crate) fn play_with_token(conn: &mut Connection, token: &str) -> Result<Play> {
+    let attendee = checkin::get(conn, token)?;
+    play(conn, attendee.act_id, attendee.cus_id)
+}
@@ -7,12 +7,34 @@ use tracing_subscriber::fmt::time::OffsetTime;
 
 use crate::config::{Config, GLOBAL_CONFIG};
 
+mod activity;
+mod audit;
+mod checkin;
+mod cli;
 mod config;
+mod custom;
+mod draw;
+mod error;
+mod instant;
+mod partition;
+mod picture;
+mod schedule;
+#[cfg(test)]
+mod test_db;
 mod web;
+mod wheel;
+mod winner;
 
 fn main() {
     let _guard = init_log();
-    async_global_executor::block_on(web::listen());
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.is_empty() {
+        async_global_executor::block_on(web::listen());
+    } else if let Err(e) = cli::run(&args) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
 }
 
 fn init_log() -> WorkerGuard {
@@ -0,0 +1,597 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{Connection, ErrorCode, OptionalExtension, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+
+use crate::activity::state::{self, ActStatus};
+use crate::activity::{self, policy};
+use crate::audit::{self, AuditEvent};
+use crate::draw::engine::{self, FlagGroup, Layout};
+use crate::draw::rule::parse_flags;
+use crate::draw::{self, rng};
+use crate::error::BizError;
+
+/// 随机分组中的一个组（一桌、一队），对应 ld_partition_bucket 的一行
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Bucket {
+    pub bucket_idx: usize,
+    pub bucket_name: String,
+    pub capacity: usize,
+}
+
+/// 一次随机分组，对应 ld_partition 的一行
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Partition {
+    pub part_id: usize,
+    pub act_id: usize,
+    pub part_name: String,
+    /// 按该奖项的参与资格确定参与者，为空时为全部客户，活动要求签到时只包括已签到的
+    pub act_seq: Option<usize>,
+    /// 带有这些标签的客户分别分在同一组
+    pub keep_together: Vec<String>,
+    /// 带有这些标签的客户尽量分到不同的组
+    pub keep_apart: Vec<String>,
+    pub buckets: Vec<Bucket>,
+    /// 分组前公布的种子哈希
+    pub seed_hash: Option<String>,
+    pub commit_time: Option<i64>,
+    /// 分组后公开的随机种子
+    pub seed: Option<String>,
+    pub algorithm: Option<String>,
+    /// 分组时冻结的参与者和约束，与 `seed` 一起可以复算分组结果
+    pub layout: Option<Layout>,
+    pub run_time: Option<i64>,
+}
+
+/// 新建或修改分组的参数，组按数组顺序编号
+#[derive(Debug, Deserialize)]
+pub(crate) struct PartitionArgs {
+    pub part_name: String,
+    #[serde(default)]
+    pub act_seq: Option<usize>,
+    #[serde(default)]
+    pub keep_together: Vec<String>,
+    #[serde(default)]
+    pub keep_apart: Vec<String>,
+    pub buckets: Vec<BucketArgs>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct BucketArgs {
+    pub bucket_name: String,
+    pub capacity: usize,
+}
+
+impl PartitionArgs {
+    fn validate(&self) -> Result<()> {
+        if self.part_name.trim().is_empty() {
+            return Err(BizError::Invalid("分组名称不能为空".to_owned()).into());
+        }
+        if self.buckets.is_empty() {
+            return Err(BizError::Invalid("至少需要一个组".to_owned()).into());
+        }
+        if let Some(bucket) = self
+            .buckets
+            .iter()
+            .find(|b| b.bucket_name.trim().is_empty())
+        {
+            return Err(
+                BizError::Invalid(format!("容量为{}的组名称不能为空", bucket.capacity)).into(),
+            );
+        }
+        if let Some(bucket) = self.buckets.iter().find(|b| b.capacity == 0) {
+            return Err(
+                BizError::Invalid(format!("组{}的容量必须大于0", bucket.bucket_name)).into(),
+            );
+        }
+
+        let flags = self.keep_together.iter().chain(&self.keep_apart);
+        for (i, cus_flag) in flags.clone().enumerate() {
+            if cus_flag.trim().is_empty() {
+                return Err(BizError::Invalid("分组约束的标签不能为空".to_owned()).into());
+            }
+            if flags.clone().take(i).any(|f| f.trim() == cus_flag.trim()) {
+                return Err(BizError::Invalid(format!("分组约束的标签{cus_flag}重复")).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+const SELECT_PARTITION: &str =
+    "select part_id,act_id,part_name,act_seq,keep_together,keep_apart,seed,algorithm,layout,run_time,
+            seed_hash,commit_time
+       from ld_partition";
+
+impl Partition {
+    fn from_row(row: &Row) -> Result<Self> {
+        let keep_together: String = row.get(4)?;
+        let keep_apart: String = row.get(5)?;
+        let run_time: Option<i64> = row.get(9)?;
+        let layout: Option<String> = row.get(8)?;
+
+        Ok(Partition {
+            part_id: row.get(0)?,
+            act_id: row.get(1)?,
+            part_name: row.get(2)?,
+            act_seq: row.get(3)?,
+            keep_together: serde_json::from_str(&keep_together)?,
+            keep_apart: serde_json::from_str(&keep_apart)?,
+            buckets: Vec::new(),
+            seed_hash: row.get(10)?,
+            commit_time: row.get(11)?,
+            //分组完成前不能泄露种子
+            seed: run_time.and(row.get(6)?),
+            algorithm: row.get(7)?,
+            layout: layout.map(|json| serde_json::from_str(&json)).transpose()?,
+            run_time,
+        })
+    }
+
+    fn load_buckets(&mut self, conn: &Connection) -> Result<()> {
+        let mut stmt = conn.prepare(
+            "select bucket_idx,bucket_name,capacity from ld_partition_bucket
+              where part_id=? order by bucket_idx",
+        )?;
+        self.buckets = stmt
+            .query_map([self.part_id], |row| {
+                Ok(Bucket {
+                    bucket_idx: row.get(0)?,
+                    bucket_name: row.get(1)?,
+                    capacity: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(())
+    }
+}
+
+/// 活动的全部分组，按 part_id 排序
+pub(crate) fn list(conn: &Connection, act_id: usize) -> Result<Vec<Partition>> {
+    activity::get(conn, act_id)?;
+    let sql = format!("{SELECT_PARTITION} where act_id=? order by part_id");
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([act_id])?;
+
+    let mut partitions = Vec::new();
+    while let Some(row) = rows.next()? {
+        partitions.push(Partition::from_row(row)?);
+    }
+    for partition in &mut partitions {
+        partition.load_buckets(conn)?;
+    }
+
+    Ok(partitions)
+}
+
+pub(crate) fn get(conn: &Connection, act_id: usize, part_id: usize) -> Result<Partition> {
+    let sql = format!("{SELECT_PARTITION} where act_id=? and part_id=?");
+    let partition = conn
+        .query_row(&sql, [act_id, part_id], |row| Ok(Partition::from_row(row)))
+        .optional()?;
+
+    match partition {
+        Some(partition) => {
+            let mut partition = partition?;
+            partition.load_buckets(conn)?;
+            Ok(partition)
+        }
+        None => Err(BizError::NotFound(format!("活动{act_id}没有分组{part_id}")).into()),
+    }
+}
+
+fn trimmed(flags: &[String]) -> Result<String> {
+    Ok(serde_json::to_string(
+        &flags.iter().map(|f| f.trim()).collect::<Vec<_>>(),
+    )?)
+}
+
+/// 替换分组的全部组
+fn save_buckets(conn: &Connection, part_id: usize, buckets: &[BucketArgs]) -> Result<()> {
+    conn.execute("delete from ld_partition_bucket where part_id=?", [part_id])?;
+    let mut stmt = conn.prepare(
+        "insert into ld_partition_bucket (part_id, bucket_idx, bucket_name, capacity)
+         values (?, ?, ?, ?)",
+    )?;
+    for (bucket_idx, bucket) in buckets.iter().enumerate() {
+        stmt.execute((
+            part_id,
+            bucket_idx,
+            bucket.bucket_name.trim(),
+            bucket.capacity,
+        ))?;
+    }
+    Ok(())
+}
+
+fn conflict(
+    res: r2d2_sqlite::rusqlite::Result<usize>,
+    act_id: usize,
+    args: &PartitionArgs,
+) -> Result<()> {
+    match res {
+        Err(e) if e.sqlite_error_code() == Some(ErrorCode::ConstraintViolation) => Err(
+            BizError::Conflict(format!("活动{act_id}已存在分组{}", args.part_name.trim())).into(),
+        ),
+        res => Ok(res.map(|_| ())?),
+    }
+}
+
+pub(crate) fn create(
+    conn: &mut Connection,
+    act_id: usize,
+    args: &PartitionArgs,
+) -> Result<Partition> {
+    args.validate()?;
+    let tx = conn.transaction()?;
+    state::ensure(&tx, act_id, &EDITABLE, "新建分组")?;
+    if let Some(act_seq) = args.act_seq {
+        draw::load_plan(&tx, act_id, act_seq)?;
+    }
+
+    let res = tx.execute(
+        "insert into ld_partition (act_id, part_name, act_seq, keep_together, keep_apart)
+         values (?, ?, ?, ?, ?)",
+        (
+            act_id,
+            args.part_name.trim(),
+            args.act_seq,
+            trimmed(&args.keep_together)?,
+            trimmed(&args.keep_apart)?,
+        ),
+    );
+    conflict(res, act_id, args)?;
+    let part_id = tx.last_insert_rowid() as usize;
+    save_buckets(&tx, part_id, &args.buckets)?;
+    let partition = get(&tx, act_id, part_id)?;
+    tx.commit()?;
+    info!(
+        "活动{act_id}新建分组{part_id} {}，共{}个组",
+        partition.part_name,
+        partition.buckets.len()
+    );
+
+    Ok(partition)
+}
+
+/// 可以新建、修改和删除分组的活动状态，已结束和已归档的活动不能再改
+const EDITABLE: [ActStatus; 4] = [
+    ActStatus::Draft,
+    ActStatus::Published,
+    ActStatus::RegistrationOpen,
+    ActStatus::Drawing,
+];
+
+/// 公布种子哈希后分组的配置就冻结了，不能再修改或删除，避免反复重抽
+fn ensure_uncommitted(partition: &Partition) -> Result<()> {
+    if partition.seed_hash.is_some() {
+        return Err(BizError::Conflict(format!(
+            "分组{}已经公布种子哈希，不能再修改或删除",
+            partition.part_id
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// 修改分组的配置，公布种子哈希后不能修改
+pub(crate) fn update(
+    conn: &mut Connection,
+    act_id: usize,
+    part_id: usize,
+    args: &PartitionArgs,
+) -> Result<Partition> {
+    args.validate()?;
+    let tx = conn.transaction()?;
+    state::ensure(&tx, act_id, &EDITABLE, "修改分组")?;
+    ensure_uncommitted(&get(&tx, act_id, part_id)?)?;
+    if let Some(act_seq) = args.act_seq {
+        draw::load_plan(&tx, act_id, act_seq)?;
+    }
+
+    let res = tx.execute(
+        "update ld_partition set part_name=?, act_seq=?, keep_together=?, keep_apart=?
+          where part_id=?",
+        (
+            args.part_name.trim(),
+            args.act_seq,
+            trimmed(&args.keep_together)?,
+            trimmed(&args.keep_apart)?,
+            part_id,
+        ),
+    );
+    conflict(res, act_id, args)?;
+    save_buckets(&tx, part_id, &args.buckets)?;
+    let partition = get(&tx, act_id, part_id)?;
+    tx.commit()?;
+    info!("活动{act_id}修改分组{part_id}");
+
+    Ok(partition)
+}
+
+/// 删除还没有公布种子哈希的分组
+pub(crate) fn remove(conn: &mut Connection, act_id: usize, part_id: usize) -> Result<()> {
+    let tx = conn.transaction()?;
+    state::ensure(&tx, act_id, &EDITABLE, "删除分组")?;
+    ensure_uncommitted(&get(&tx, act_id, part_id)?)?;
+
+    for table in ["ld_partition_seat", "ld_partition_bucket", "ld_partition"] {
+        tx.execute(&format!("delete from {table} where part_id=?"), [part_id])?;
+    }
+    tx.commit()?;
+    info!("活动{act_id}删除分组{part_id}");
+
+    Ok(())
+}
+
+/// 参与分组的客户 cus_id 和标签，按 cus_id 升序
+fn participants(
+    conn: &Connection,
+    act_id: usize,
+    act_seq: Option<usize>,
+) -> Result<Vec<(usize, Option<String>)>> {
+    if let Some(act_seq) = act_seq {
+        let mut participants = draw::eligibility(conn, act_id, act_seq)?
+            .candidates
+            .into_iter()
+            .map(|c| (c.cus_id, c.cus_flag))
+            .collect::<Vec<_>>();
+        participants.sort_unstable_by_key(|(cus_id, _)| *cus_id);
+        return Ok(participants);
+    }
+
+    let policy = policy::get(conn, act_id)?;
+    let mut stmt = conn.prepare(
+        "select cus_id,cus_flag from ld_custom
+          where ?2=0 or cus_id in (
+                select cus_id from ld_checkin
+                 where act_id=?1 and checkin_time>=ifnull(?3,checkin_time)
+                   and checkin_time<=ifnull(?4,checkin_time))
+          order by cus_id",
+    )?;
+    let participants = stmt
+        .query_map(
+            (
+                act_id,
+                policy.require_checkin,
+                policy.checkin_from,
+                policy.checkin_to,
+            ),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(participants)
+}
+
+/// 每个标签的参与者
+fn flag_groups(flags: &[String], participants: &[(usize, Option<String>)]) -> Vec<FlagGroup> {
+    flags
+        .iter()
+        .map(|cus_flag| FlagGroup {
+            cus_flag: cus_flag.clone(),
+            members: participants
+                .iter()
+                .filter(|(_, flags)| {
+                    flags
+                        .as_deref()
+                        .is_some_and(|flags| parse_flags(flags).contains(&cus_flag.as_str()))
+                })
+                .map(|(cus_id, _)| *cus_id)
+                .collect(),
+        })
+        .collect()
+}
+
+/// 为分组生成种子并公布其哈希，已公布时直接返回
+///
+/// 与抽奖一样只能在活动进入抽奖中后公布，公布后分组的配置不能再修改或删除。
+pub(crate) fn commit(conn: &mut Connection, act_id: usize, part_id: usize) -> Result<Partition> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    state::ensure(&tx, act_id, &[ActStatus::Drawing], "公布分组种子")?;
+    let partition = get(&tx, act_id, part_id)?;
+    if partition.seed_hash.is_some() {
+        return Ok(partition);
+    }
+
+    let seed = rng::new_seed()?;
+    let seed_hash = rng::seed_hash(&seed);
+    tx.execute(
+        "update ld_partition set seed=?, seed_hash=?, commit_time=? where part_id=?",
+        (
+            hex::encode(seed),
+            &seed_hash,
+            time::OffsetDateTime::now_utc().unix_timestamp(),
+            part_id,
+        ),
+    )?;
+    let partition = get(&tx, act_id, part_id)?;
+    tx.commit()?;
+    info!("活动{act_id}分组{part_id}公布种子哈希: {seed_hash}");
+
+    Ok(partition)
+}
+
+/// 用事先公布的种子把参与者分到各组，每个分组只能分一次
+///
+/// 分组后公开种子和冻结的参与者、约束，可以用 verify 复算，结果同时写入审计日志。
+pub(crate) fn run(conn: &mut Connection, act_id: usize, part_id: usize) -> Result<Roster> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    state::ensure(&tx, act_id, &[ActStatus::Drawing], "随机分组")?;
+    let partition = get(&tx, act_id, part_id)?;
+    if partition.run_time.is_some() {
+        return Err(BizError::Conflict(format!("分组{part_id}已经完成")).into());
+    }
+    let Some(seed_hash) = &partition.seed_hash else {
+        return Err(BizError::Conflict(format!("分组{part_id}还没有公布种子哈希")).into());
+    };
+    let seed: String = tx.query_row(
+        "select seed from ld_partition where part_id=?",
+        [part_id],
+        |row| row.get(0),
+    )?;
+    let seed = hex::decode(seed)?;
+
+    let participants = participants(&tx, act_id, partition.act_seq)?;
+    if participants.is_empty() {
+        return Err(BizError::Conflict(format!("分组{part_id}没有符合条件的参与者")).into());
+    }
+    let layout = Layout {
+        participants: participants.iter().map(|(cus_id, _)| *cus_id).collect(),
+        capacities: partition.buckets.iter().map(|b| b.capacity).collect(),
+        together: flag_groups(&partition.keep_together, &participants),
+        apart: flag_groups(&partition.keep_apart, &participants),
+    };
+    let buckets = engine::partition(&seed, &layout)?;
+
+    {
+        let mut stmt = tx.prepare(
+            "insert into ld_partition_seat (part_id, cus_id, bucket_idx) values (?, ?, ?)",
+        )?;
+        for (cus_id, bucket_idx) in layout.participants.iter().zip(&buckets) {
+            stmt.execute((part_id, cus_id, bucket_idx))?;
+        }
+    }
+    tx.execute(
+        "update ld_partition set algorithm=?, layout=?, run_time=? where part_id=?",
+        (
+            engine::PARTITION,
+            serde_json::to_string(&layout)?,
+            time::OffsetDateTime::now_utc().unix_timestamp(),
+            part_id,
+        ),
+    )?;
+    audit::append(
+        &tx,
+        AuditEvent::Partition,
+        act_id,
+        &[],
+        json!({
+            "part_id": part_id,
+            "seed_hash": seed_hash,
+            "seed": hex::encode(&seed),
+            "algorithm": engine::PARTITION,
+            "seats": layout.participants.iter().zip(&buckets).collect::<Vec<_>>(),
+        }),
+    )?;
+    let roster = roster(&tx, act_id, part_id)?;
+    tx.commit()?;
+    info!(
+        "活动{act_id}分组{part_id}完成，{}人分到{}个组",
+        layout.participants.len(),
+        layout.capacities.len()
+    );
+
+    Ok(roster)
+}
+
+/// 分到某个组的客户
+#[derive(Debug, Serialize)]
+pub(crate) struct Seat {
+    pub cus_id: usize,
+    pub cus_nickname: String,
+    pub cus_name: Option<String>,
+    pub cus_flag: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct RosterBucket {
+    #[serde(flatten)]
+    pub bucket: Bucket,
+    /// 按 cus_id 升序
+    pub members: Vec<Seat>,
+}
+
+/// 分组名单，还没有分组时各组都没有成员
+#[derive(Debug, Serialize)]
+pub(crate) struct Roster {
+    pub partition: Partition,
+    pub buckets: Vec<RosterBucket>,
+}
+
+pub(crate) fn roster(conn: &Connection, act_id: usize, part_id: usize) -> Result<Roster> {
+    let partition = get(conn, act_id, part_id)?;
+    let mut buckets = partition
+        .buckets
+        .iter()
+        .map(|bucket| RosterBucket {
+            bucket: bucket.clone(),
+            members: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut stmt = conn.prepare(
+        "select s.bucket_idx,c.cus_id,c.cus_nickname,c.cus_name,c.cus_flag
+           from ld_partition_seat s
+           join ld_custom c on c.cus_id=s.cus_id
+          where s.part_id=? order by s.bucket_idx, c.cus_id",
+    )?;
+    let mut rows = stmt.query([part_id])?;
+    while let Some(row) = rows.next()? {
+        let bucket_idx: usize = row.get(0)?;
+        if let Some(bucket) = buckets.get_mut(bucket_idx) {
+            bucket.members.push(Seat {
+                cus_id: row.get(1)?,
+                cus_nickname: row.get(2)?,
+                cus_name: row.get(3)?,
+                cus_flag: row.get(4)?,
+            });
+        }
+    }
+
+    Ok(Roster { partition, buckets })
+}
+
+/// 分组结果的复算情况
+#[derive(Debug, Serialize)]
+pub(crate) struct PartitionCheck {
+    pub part_id: usize,
+    pub algorithm: String,
+    /// 公开的种子与事先公布的哈希一致
+    pub seed_matched: bool,
+    pub matched: bool,
+    /// 复算出的组下标与 ld_partition_seat 不一致的 cus_id
+    pub mismatched: Vec<usize>,
+}
+
+/// 用公开的种子和冻结的参与者、约束复算分组，并与 ld_partition_seat 比对
+pub(crate) fn verify(conn: &Connection, act_id: usize, part_id: usize) -> Result<PartitionCheck> {
+    let partition = get(conn, act_id, part_id)?;
+    let (Some(seed), Some(algorithm), Some(layout)) =
+        (partition.seed, partition.algorithm, partition.layout)
+    else {
+        return Err(BizError::Conflict(format!("分组{part_id}还没有分组")).into());
+    };
+    if algorithm != engine::PARTITION {
+        return Err(BizError::Invalid(format!("不支持的分组算法: {algorithm}")).into());
+    }
+    let seed = hex::decode(seed)?;
+    let seed_matched = partition.seed_hash.as_deref() == Some(rng::seed_hash(&seed).as_str());
+    let expected = engine::partition(&seed, &layout)?;
+
+    let mut stmt =
+        conn.prepare("select cus_id,bucket_idx from ld_partition_seat where part_id=?")?;
+    let mut actual = stmt
+        .query_map([part_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<BTreeMap<usize, usize>, _>>()?;
+    let mut mismatched = layout
+        .participants
+        .iter()
+        .zip(expected)
+        .filter(|(cus_id, bucket_idx)| actual.remove(cus_id) != Some(*bucket_idx))
+        .map(|(cus_id, _)| *cus_id)
+        .collect::<Vec<_>>();
+    mismatched.extend(actual.into_keys());
+    mismatched.sort_unstable();
+    info!("活动{act_id}分组{part_id}复算，不一致: {mismatched:?}");
+
+    Ok(PartitionCheck {
+        part_id,
+        algorithm,
+        seed_matched,
+        matched: seed_matched && mismatched.is_empty(),
+        mismatched,
+    })
+}
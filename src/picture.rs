@@ -0,0 +1,161 @@
+use std::fmt::{Display, Formatter};
+use std::io::Cursor;
+
+use anyhow::Result;
+use image::ImageFormat;
+use r2d2_sqlite::rusqlite::{params_from_iter, Connection, OptionalExtension, ToSql};
+use tracing::info;
+
+use crate::error::BizError;
+
+/// 图片大小上限
+pub(crate) const MAX_SIZE: usize = 5 * 1024 * 1024;
+/// 缩略图的最大宽高
+pub(crate) const THUMBNAIL_SIZE: u32 = 256;
+/// 允许上传的图片格式
+const FORMATS: [ImageFormat; 4] = [
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::Gif,
+    ImageFormat::WebP,
+];
+
+/// 图片所属的数据行
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Owner {
+    /// ld_activity.act_picture
+    Activity(usize),
+    /// ld_plan.prize_picture
+    Prize(usize, usize),
+    /// ld_custom.cus_picture
+    Custom(usize),
+}
+
+impl Owner {
+    /// (表名, 原图列, 缩略图列, 条件)
+    fn columns(&self) -> (&'static str, &'static str, &'static str, &'static str) {
+        match self {
+            Owner::Activity(_) => ("ld_activity", "act_picture", "act_thumbnail", "act_id=?"),
+            Owner::Prize(..) => (
+                "ld_plan",
+                "prize_picture",
+                "prize_thumbnail",
+                "act_id=? and act_seq=?",
+            ),
+            Owner::Custom(_) => ("ld_custom", "cus_picture", "cus_thumbnail", "cus_id=?"),
+        }
+    }
+
+    fn keys(&self) -> Vec<usize> {
+        match *self {
+            Owner::Activity(act_id) => vec![act_id],
+            Owner::Prize(act_id, act_seq) => vec![act_id, act_seq],
+            Owner::Custom(cus_id) => vec![cus_id],
+        }
+    }
+}
+
+impl Display for Owner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Owner::Activity(act_id) => write!(f, "活动{act_id}"),
+            Owner::Prize(act_id, act_seq) => write!(f, "奖项{act_id}-{act_seq}"),
+            Owner::Custom(cus_id) => write!(f, "客户{cus_id}"),
+        }
+    }
+}
+
+/// 从数据库读出的图片
+pub(crate) struct Picture {
+    pub data: Vec<u8>,
+    pub mime: &'static str,
+}
+
+/// 校验图片格式，声明的类型必须与实际内容一致
+pub(crate) fn check(data: &[u8], content_type: Option<&str>) -> Result<ImageFormat> {
+    if data.is_empty() {
+        return Err(BizError::Invalid("图片不能为空".to_owned()).into());
+    }
+    if data.len() > MAX_SIZE {
+        return Err(BizError::Invalid(format!("图片不能超过{}MB", MAX_SIZE / 1024 / 1024)).into());
+    }
+
+    let format = image::guess_format(data)
+        .ok()
+        .filter(|format| FORMATS.contains(format))
+        .ok_or_else(|| BizError::Invalid("只支持 png、jpeg、gif、webp 格式的图片".to_owned()))?;
+    if let Some(content_type) = content_type {
+        if content_type != format.to_mime_type() {
+            return Err(BizError::Invalid(format!(
+                "图片类型{content_type}与实际内容{}不一致",
+                format.to_mime_type()
+            ))
+            .into());
+        }
+    }
+
+    Ok(format)
+}
+
+/// 生成 png 格式的缩略图，保持宽高比
+pub(crate) fn thumbnail(data: &[u8], format: ImageFormat) -> Result<Vec<u8>> {
+    let image = image::load_from_memory_with_format(data, format)
+        .map_err(|e| BizError::Invalid(format!("图片无法解析: {e}")))?;
+
+    let mut thumbnail = Cursor::new(Vec::new());
+    image
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(&mut thumbnail, ImageFormat::Png)?;
+    Ok(thumbnail.into_inner())
+}
+
+/// 保存原图并生成缩略图
+pub(crate) fn save(
+    conn: &Connection,
+    owner: Owner,
+    data: &[u8],
+    content_type: Option<&str>,
+) -> Result<()> {
+    let format = check(data, content_type)?;
+    let thumbnail = thumbnail(data, format)?;
+
+    let (table, picture, thumb, filter) = owner.columns();
+    let sql = format!("update {table} set {picture}=?, {thumb}=? where {filter}");
+    let keys = owner.keys();
+    let mut params: Vec<&dyn ToSql> = vec![&data, &thumbnail];
+    params.extend(keys.iter().map(|key| key as &dyn ToSql));
+
+    if conn.execute(&sql, params.as_slice())? == 0 {
+        return Err(BizError::NotFound(format!("{owner}不存在")).into());
+    }
+    info!(
+        "{owner}保存图片 {} bytes, 缩略图 {} bytes",
+        data.len(),
+        thumbnail.len()
+    );
+
+    Ok(())
+}
+
+/// 读取图片，`thumbnail` 为 true 时读取缩略图
+pub(crate) fn load(conn: &Connection, owner: Owner, thumbnail: bool) -> Result<Picture> {
+    let (table, picture, thumb, filter) = owner.columns();
+    let column = if thumbnail { thumb } else { picture };
+    let sql = format!("select {column} from {table} where {filter}");
+
+    let keys = owner.keys();
+    let data: Option<Option<Vec<u8>>> = conn
+        .query_row(&sql, params_from_iter(keys.iter()), |row| row.get(0))
+        .optional()?;
+
+    let data = match data {
+        Some(Some(data)) if !data.is_empty() => data,
+        Some(_) => return Err(BizError::NotFound(format!("{owner}没有图片")).into()),
+        None => return Err(BizError::NotFound(format!("{owner}不存在")).into()),
+    };
+    let mime = image::guess_format(&data)
+        .map(|format| format.to_mime_type())
+        .unwrap_or("application/octet-stream");
+
+    Ok(Picture { data, mime })
+}
@@ -0,0 +1,153 @@
+use anyhow::Result;
+use time::{Date, Duration, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+use crate::error::BizError;
+
+/// 五段式 cron 表达式：分 时 日 月 周
+///
+/// 每段支持 `*`、数字、`a-b` 范围、`/n` 步长和逗号分隔的列表，周日可以写 0 或 7。
+/// 日和周都有限制时，与 crontab 一样满足其一即可。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Cron {
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// 查找下一次触发时间时最多向后看的天数
+const MAX_DAYS: i64 = 366 * 5;
+
+impl Cron {
+    pub(crate) fn parse(expr: &str) -> Result<Self> {
+        let fields = expr.split_whitespace().collect::<Vec<_>>();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(BizError::Invalid(format!("cron 表达式必须是5段: {expr}")).into());
+        };
+
+        let weekdays = parse_field(weekday, 0, 7)?;
+        Ok(Cron {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)? as u32,
+            days: parse_field(day, 1, 31)? as u32,
+            months: parse_field(month, 1, 12)? as u16,
+            //7 和 0 都表示周日
+            weekdays: ((weekdays | weekdays >> 7) & 0x7f) as u8,
+            //与 cron 一致，以 * 开头的字段（包括 */2）都视为不限制
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    fn matches_day(&self, date: Date) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().number_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// `after` 之后（不含）按 `offset` 时区计算的下一次触发时间
+    pub(crate) fn next_after(&self, after: i64, offset: UtcOffset) -> Option<i64> {
+        let start = OffsetDateTime::from_unix_timestamp(after)
+            .ok()?
+            .to_offset(offset);
+        //从下一分钟开始找
+        let start = start.replace_second(0).ok()?.replace_nanosecond(0).ok()? + Duration::MINUTE;
+        let mut current = PrimitiveDateTime::new(start.date(), start.time());
+        let limit = current.date() + Duration::days(MAX_DAYS);
+
+        while current.date() < limit {
+            let date = current.date();
+            if self.months & (1 << date.month() as u8) == 0 {
+                let (year, month) = match date.month() {
+                    Month::December => (date.year() + 1, Month::January),
+                    month => (date.year(), month.next()),
+                };
+                current = Date::from_calendar_date(year, month, 1)
+                    .ok()?
+                    .with_time(Time::MIDNIGHT);
+                continue;
+            }
+            if !self.matches_day(date) {
+                current = date.next_day()?.with_time(Time::MIDNIGHT);
+                continue;
+            }
+            if self.hours & (1 << current.hour()) == 0 {
+                current = current.replace_minute(0).ok()? + Duration::HOUR;
+                continue;
+            }
+            if self.minutes & (1 << current.minute()) == 0 {
+                current += Duration::MINUTE;
+                continue;
+            }
+            return Some(current.assume_offset(offset).unix_timestamp());
+        }
+
+        None
+    }
+}
+
+/// 把一段表达式解析成位图，第 n 位表示 n 是否命中
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let invalid = || BizError::Invalid(format!("cron 表达式中的“{field}”不合法"));
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (
+                    from.parse().map_err(|_| invalid())?,
+                    to.parse().map_err(|_| invalid())?,
+                ),
+                //带步长的单个数字表示从该数字到最大值
+                None if part.contains('/') => (range.parse().map_err(|_| invalid())?, max),
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    (value, value)
+                }
+            },
+        };
+        if step == 0 || from < min || to > max || from > to {
+            return Err(invalid().into());
+        }
+        for value in (from..=to).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+/// 解析 `+08:00`、`-05:30`、`Z` 形式的时区
+///
+/// 只支持固定的 UTC 偏移，不支持 `Asia/Shanghai` 这样的 IANA 时区名，也不处理夏令时，
+/// 实行夏令时的地区需要在切换前后自行修改定时抽奖的时区。
+pub(crate) fn parse_offset(timezone: &str) -> Result<UtcOffset> {
+    let invalid = || {
+        BizError::Invalid(format!(
+            "时区“{timezone}”不合法，应为 +08:00 的形式，不支持时区名和夏令时"
+        ))
+    };
+    let timezone = timezone.trim();
+    if timezone.eq_ignore_ascii_case("z") || timezone.eq_ignore_ascii_case("utc") {
+        return Ok(UtcOffset::UTC);
+    }
+
+    let (sign, rest) = match timezone.split_at_checked(1) {
+        Some(("+", rest)) => (1, rest),
+        Some(("-", rest)) => (-1, rest),
+        _ => return Err(invalid().into()),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let hours = hours.parse::<i8>().map_err(|_| invalid())?;
+    let minutes = minutes.parse::<i8>().map_err(|_| invalid())?;
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).map_err(|_| invalid().into())
+}
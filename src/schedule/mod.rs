@@ -0,0 +1,406 @@
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{Connection, OptionalExtension, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::activity;
+use crate::draw::load_plan;
+use crate::error::BizError;
+use crate::schedule::cron::{parse_offset, Cron};
+
+pub(crate) mod cron;
+pub(crate) mod runner;
+
+/// 定时抽奖，对应 ld_schedule 的一行
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Schedule {
+    pub sch_id: usize,
+    pub act_id: usize,
+    /// 为空时按序号从大到小抽取活动中所有还有名额的奖项
+    pub act_seq: Option<usize>,
+    /// 每次每个奖项抽取的人数，为空时抽完剩余名额
+    pub draw_count: Option<usize>,
+    /// 一次性抽奖的时间
+    pub run_at: Option<i64>,
+    /// 周期抽奖的 cron 表达式
+    pub cron: Option<String>,
+    /// cron 表达式使用的时区，只支持 +08:00 这样的固定偏移，不处理夏令时
+    pub timezone: String,
+    pub enabled: bool,
+    /// 下一次触发时间，没有后续触发时为空
+    pub next_run: Option<i64>,
+    /// 每次触发最多尝试的次数
+    pub max_attempts: usize,
+    /// 失败后重试的间隔秒数
+    pub retry_delay: i64,
+    pub create_time: i64,
+}
+
+/// 新建或修改定时抽奖的参数
+#[derive(Debug, Deserialize)]
+pub(crate) struct ScheduleArgs {
+    #[serde(default)]
+    pub act_seq: Option<usize>,
+    #[serde(default)]
+    pub draw_count: Option<usize>,
+    #[serde(default)]
+    pub run_at: Option<i64>,
+    #[serde(default)]
+    pub cron: Option<String>,
+    pub timezone: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: usize,
+    #[serde(default = "default_retry_delay")]
+    pub retry_delay: i64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_max_attempts() -> usize {
+    3
+}
+
+fn default_retry_delay() -> i64 {
+    60
+}
+
+impl ScheduleArgs {
+    /// 校验参数并计算第一次触发时间
+    fn validate(&self, conn: &Connection, act_id: usize, now: i64) -> Result<Option<i64>> {
+        activity::get(conn, act_id)?;
+        if let Some(act_seq) = self.act_seq {
+            load_plan(conn, act_id, act_seq)?;
+        }
+        if self.draw_count == Some(0) {
+            return Err(BizError::Invalid("抽奖人数必须大于0".to_owned()).into());
+        }
+        if self.max_attempts == 0 || self.retry_delay < 0 {
+            return Err(
+                BizError::Invalid("重试次数必须大于0，重试间隔不能为负数".to_owned()).into(),
+            );
+        }
+
+        let offset = parse_offset(&self.timezone)?;
+        match (self.run_at, self.cron.as_deref()) {
+            (Some(run_at), None) => Ok(Some(run_at)),
+            (None, Some(expr)) => match Cron::parse(expr)?.next_after(now, offset) {
+                Some(next_run) => Ok(Some(next_run)),
+                None => {
+                    Err(BizError::Invalid(format!("cron 表达式“{expr}”没有可触发的时间")).into())
+                }
+            },
+            _ => Err(BizError::Invalid("run_at 和 cron 必须且只能指定一个".to_owned()).into()),
+        }
+    }
+}
+
+const SELECT_SCHEDULE: &str =
+    "select sch_id,act_id,act_seq,draw_count,run_at,cron,timezone,enabled,next_run,
+            max_attempts,retry_delay,create_time
+       from ld_schedule";
+
+impl Schedule {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Schedule {
+            sch_id: row.get(0)?,
+            act_id: row.get(1)?,
+            act_seq: row.get(2)?,
+            draw_count: row.get(3)?,
+            run_at: row.get(4)?,
+            cron: row.get(5)?,
+            timezone: row.get(6)?,
+            enabled: row.get(7)?,
+            next_run: row.get(8)?,
+            max_attempts: row.get(9)?,
+            retry_delay: row.get(10)?,
+            create_time: row.get(11)?,
+        })
+    }
+
+    /// `fire_time` 触发之后的下一次触发时间，一次性抽奖没有下一次
+    pub(crate) fn next_after(&self, fire_time: i64) -> Result<Option<i64>> {
+        match &self.cron {
+            Some(expr) => {
+                Ok(Cron::parse(expr)?.next_after(fire_time, parse_offset(&self.timezone)?))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+pub(crate) fn list(conn: &Connection, act_id: usize) -> Result<Vec<Schedule>> {
+    let sql = format!("{SELECT_SCHEDULE} where act_id=? order by sch_id");
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([act_id])?;
+
+    let mut schedules = Vec::new();
+    while let Some(row) = rows.next()? {
+        schedules.push(Schedule::from_row(row)?);
+    }
+
+    Ok(schedules)
+}
+
+pub(crate) fn get(conn: &Connection, sch_id: usize) -> Result<Schedule> {
+    let sql = format!("{SELECT_SCHEDULE} where sch_id=?");
+    let schedule = conn
+        .query_row(&sql, [sch_id], |row| Ok(Schedule::from_row(row)))
+        .optional()?;
+
+    match schedule {
+        Some(schedule) => schedule,
+        None => Err(BizError::NotFound(format!("定时抽奖{sch_id}不存在")).into()),
+    }
+}
+
+pub(crate) fn create(conn: &Connection, act_id: usize, args: &ScheduleArgs) -> Result<Schedule> {
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    let next_run = args.validate(conn, act_id, now)?;
+
+    conn.execute(
+        "insert into ld_schedule (act_id, act_seq, draw_count, run_at, cron, timezone, enabled,
+                                  next_run, max_attempts, retry_delay, create_time)
+         values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        (
+            act_id,
+            args.act_seq,
+            args.draw_count,
+            args.run_at,
+            &args.cron,
+            args.timezone.trim(),
+            args.enabled,
+            next_run,
+            args.max_attempts,
+            args.retry_delay,
+            now,
+        ),
+    )?;
+    let sch_id = conn.last_insert_rowid() as usize;
+    info!("活动{act_id}新建定时抽奖{sch_id}，下一次触发时间{next_run:?}");
+
+    get(conn, sch_id)
+}
+
+/// 修改定时抽奖，下一次触发时间按新的配置从现在开始重新计算
+///
+/// 与执行器在同一把写锁下丢弃预先准备的执行记录，避免执行器在修改中途取走旧的记录。
+pub(crate) fn update(
+    conn: &mut Connection,
+    sch_id: usize,
+    args: &ScheduleArgs,
+) -> Result<Schedule> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let schedule = get(&tx, sch_id)?;
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    let next_run = args.validate(&tx, schedule.act_id, now)?;
+
+    discard_prepared(&tx, sch_id)?;
+    tx.execute(
+        "update ld_schedule set act_seq=?, draw_count=?, run_at=?, cron=?, timezone=?, enabled=?,
+                                next_run=?, max_attempts=?, retry_delay=?
+          where sch_id=?",
+        (
+            args.act_seq,
+            args.draw_count,
+            args.run_at,
+            &args.cron,
+            args.timezone.trim(),
+            args.enabled,
+            next_run,
+            args.max_attempts,
+            args.retry_delay,
+            sch_id,
+        ),
+    )?;
+    let schedule = get(&tx, sch_id)?;
+    tx.commit()?;
+    info!("修改定时抽奖{sch_id}，下一次触发时间{next_run:?}");
+
+    Ok(schedule)
+}
+
+/// 丢弃还没有开始执行的执行记录，预先公布的种子保留给之后的触发或手动抽奖使用
+fn discard_prepared(conn: &Connection, sch_id: usize) -> Result<()> {
+    conn.execute(
+        "update ld_draw set run_id=null where draw_time is null and run_id in (
+                select run_id from ld_schedule_run where sch_id=? and run_status=? and attempts=0)",
+        (sch_id, RunStatus::Pending as i64),
+    )?;
+    conn.execute(
+        "delete from ld_schedule_run where sch_id=? and run_status=? and attempts=0",
+        (sch_id, RunStatus::Pending as i64),
+    )?;
+    Ok(())
+}
+
+/// 删除定时抽奖，已经抽过奖的只能停用
+pub(crate) fn remove(conn: &mut Connection, sch_id: usize) -> Result<()> {
+    let tx = conn.transaction()?;
+    get(&tx, sch_id)?;
+
+    let drawn: bool = tx.query_row(
+        "select exists(select 1 from ld_draw d join ld_schedule_run r on r.run_id=d.run_id
+                        where r.sch_id=? and d.draw_time is not null)",
+        [sch_id],
+        |row| row.get(0),
+    )?;
+    if drawn {
+        return Err(BizError::Conflict(format!("定时抽奖{sch_id}已经抽过奖，只能停用")).into());
+    }
+
+    tx.execute(
+        "update ld_draw set run_id=null where run_id in (
+                select run_id from ld_schedule_run where sch_id=?)",
+        [sch_id],
+    )?;
+    tx.execute("delete from ld_schedule_run where sch_id=?", [sch_id])?;
+    tx.execute("delete from ld_schedule where sch_id=?", [sch_id])?;
+    tx.commit()?;
+    info!("删除定时抽奖{sch_id}");
+
+    Ok(())
+}
+
+/// ld_schedule_run.run_status 的取值
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RunStatus {
+    /// 0: 等待执行或等待重试
+    Pending = 0,
+    /// 1: 执行中
+    Running = 1,
+    /// 2: 执行成功
+    Succeeded = 2,
+    /// 3: 执行失败，不再重试
+    Failed = 3,
+}
+
+impl TryFrom<i64> for RunStatus {
+    type Error = BizError;
+
+    fn try_from(value: i64) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(RunStatus::Pending),
+            1 => Ok(RunStatus::Running),
+            2 => Ok(RunStatus::Succeeded),
+            3 => Ok(RunStatus::Failed),
+            _ => Err(BizError::Invalid(format!("未知的执行状态: {value}"))),
+        }
+    }
+}
+
+/// 一次触发的执行记录，对应 ld_schedule_run 的一行
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Run {
+    pub run_id: usize,
+    pub sch_id: usize,
+    /// 计划的触发时间，同一个定时抽奖的每个触发时间只有一条记录
+    pub fire_time: i64,
+    pub run_status: RunStatus,
+    /// 已尝试的次数
+    pub attempts: usize,
+    /// 下一次尝试的时间
+    pub next_attempt: i64,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    pub message: Option<String>,
+    /// 本次触发使用的抽奖批次，包括触发前预先公布、还没有使用的种子
+    pub draw_ids: Vec<usize>,
+    /// 各奖项预先公布的种子哈希，触发前即可查看
+    pub seeds: Vec<RunSeed>,
+}
+
+/// 执行记录使用的种子
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct RunSeed {
+    pub draw_id: usize,
+    pub act_seq: usize,
+    pub seed_hash: String,
+    pub commit_time: i64,
+    pub draw_time: Option<i64>,
+}
+
+const SELECT_RUN: &str =
+    "select run_id,sch_id,fire_time,run_status,attempts,next_attempt,start_time,end_time,message,
+            (select group_concat(draw_id) from ld_draw d where d.run_id=r.run_id)
+       from ld_schedule_run r";
+
+impl Run {
+    fn from_row(row: &Row) -> Result<Self> {
+        let draw_ids: Option<String> = row.get(9)?;
+        Ok(Run {
+            run_id: row.get(0)?,
+            sch_id: row.get(1)?,
+            fire_time: row.get(2)?,
+            run_status: RunStatus::try_from(row.get::<_, i64>(3)?)?,
+            attempts: row.get(4)?,
+            next_attempt: row.get(5)?,
+            start_time: row.get(6)?,
+            end_time: row.get(7)?,
+            message: row.get(8)?,
+            draw_ids: draw_ids
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|id| id.parse().ok())
+                .collect(),
+            seeds: Vec::new(),
+        })
+    }
+
+    fn load_seeds(&mut self, conn: &Connection) -> Result<()> {
+        let mut stmt = conn.prepare(
+            "select draw_id,act_seq,seed_hash,commit_time,draw_time from ld_draw
+              where run_id=? order by draw_id",
+        )?;
+        self.seeds = stmt
+            .query_map([self.run_id], |row| {
+                Ok(RunSeed {
+                    draw_id: row.get(0)?,
+                    act_seq: row.get(1)?,
+                    seed_hash: row.get(2)?,
+                    commit_time: row.get(3)?,
+                    draw_time: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(())
+    }
+}
+
+/// 定时抽奖的执行记录，最近的在前
+pub(crate) fn runs(conn: &Connection, sch_id: usize) -> Result<Vec<Run>> {
+    get(conn, sch_id)?;
+    let sql = format!("{SELECT_RUN} where sch_id=? order by fire_time desc");
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([sch_id])?;
+
+    let mut runs = Vec::new();
+    while let Some(row) = rows.next()? {
+        runs.push(Run::from_row(row)?);
+    }
+    for run in &mut runs {
+        run.load_seeds(conn)?;
+    }
+
+    Ok(runs)
+}
+
+pub(crate) fn get_run(conn: &Connection, run_id: usize) -> Result<Run> {
+    let sql = format!("{SELECT_RUN} where run_id=?");
+    let run = conn
+        .query_row(&sql, [run_id], |row| Ok(Run::from_row(row)))
+        .optional()?;
+
+    match run {
+        Some(run) => {
+            let mut run = run?;
+            run.load_seeds(conn)?;
+            Ok(run)
+        }
+        None => Err(BizError::NotFound(format!("执行记录{run_id}不存在")).into()),
+    }
+}
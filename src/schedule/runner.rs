@@ -0,0 +1,366 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::Result;
+use r2d2::Pool;
+use r2d2_sqlite::rusqlite::{Connection, TransactionBehavior};
+use r2d2_sqlite::SqliteConnectionManager;
+use tracing::{error, info, info_span, warn};
+
+use crate::activity::plan;
+use crate::activity::state::ActStatus;
+use crate::draw::{self, count_winners, seed, DrawResult, DrawUnit};
+use crate::error::BizError;
+use crate::schedule::{self, get_run, Run, RunStatus, Schedule};
+use crate::winner::claim;
+
+/// 检查到期任务的间隔
+const TICK: Duration = Duration::from_secs(5);
+
+/// 启动定时抽奖线程，每次抽奖成功后调用 `on_draw`，同时负责处理逾期未领奖
+pub(crate) fn spawn<F>(pool: Pool<SqliteConnectionManager>, on_draw: F)
+where
+    F: Fn(&DrawResult) + Send + 'static,
+{
+    let res = std::thread::Builder::new()
+        .name("scheduler".to_owned())
+        .spawn(move || {
+            let _enter = info_span!("定时抽奖").entered();
+            match pool.get() {
+                Ok(conn) => {
+                    if let Err(e) = recover(&conn) {
+                        error!("恢复中断的定时抽奖失败: {e:?}");
+                    }
+                }
+                Err(e) => error!("获取数据库连接失败: {e:?}"),
+            }
+
+            loop {
+                match pool.get() {
+                    Ok(mut conn) => {
+                        if let Err(e) = tick(&mut conn, &on_draw) {
+                            error!("定时抽奖调度失败: {e:?}");
+                        }
+                    }
+                    Err(e) => error!("获取数据库连接失败: {e:?}"),
+                }
+                std::thread::sleep(TICK);
+            }
+        });
+    if let Err(e) = res {
+        error!("启动定时抽奖线程失败: {e:?}");
+    }
+}
+
+/// 服务重启时，把上次执行中的记录放回队列
+///
+/// 抽奖和标记 run_id 在同一个事务中完成，重新执行时会跳过已经抽过的奖项，不会重复抽奖。
+fn recover(conn: &Connection) -> Result<()> {
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    let count = conn.execute(
+        "update ld_schedule_run set run_status=?, next_attempt=?, message='服务重启，重新执行'
+          where run_status=?",
+        (RunStatus::Pending as i64, now, RunStatus::Running as i64),
+    )?;
+    if count > 0 {
+        warn!("{count}个中断的定时抽奖将重新执行");
+    }
+    Ok(())
+}
+
+fn tick(conn: &mut Connection, on_draw: &dyn Fn(&DrawResult)) -> Result<()> {
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    claim::expire(conn, now)?;
+    prepare(conn, now)?;
+    enqueue(conn, now)?;
+
+    let due = {
+        let mut stmt = conn.prepare(
+            "select run_id from ld_schedule_run where run_status=? and next_attempt<=?
+              order by next_attempt,run_id",
+        )?;
+        let ids = stmt
+            .query_map((RunStatus::Pending as i64, now), |row| row.get(0))?
+            .collect::<Result<Vec<usize>, _>>()?;
+        ids
+    };
+    for run_id in due {
+        run(conn, run_id, on_draw)?;
+    }
+
+    Ok(())
+}
+
+/// 定时抽奖本次要抽的奖项及其剩余名额，抽取所有奖项时跳过已抽完的
+fn targets(conn: &Connection, schedule: &Schedule) -> Result<Vec<(usize, usize)>> {
+    let act_id = schedule.act_id;
+    let act_seqs = match schedule.act_seq {
+        Some(act_seq) => vec![act_seq],
+        None => plan::list(conn, act_id)?
+            .into_iter()
+            .rev()
+            .map(|detail| detail.plan.act_seq)
+            .collect(),
+    };
+
+    let mut targets = Vec::with_capacity(act_seqs.len());
+    for act_seq in act_seqs {
+        let remaining = draw::load_plan(conn, act_id, act_seq)?
+            .prize_amount
+            .saturating_sub(count_winners(conn, act_id, act_seq)?);
+        if schedule.act_seq.is_none() && remaining == 0 {
+            continue;
+        }
+        targets.push((act_seq, remaining));
+    }
+    Ok(targets)
+}
+
+/// 在下一次触发之前生成执行记录，并为要抽的奖项公布种子哈希
+///
+/// 种子只能在活动进入抽奖中后公布，所以只为抽奖中的活动准备。
+/// 同一个定时抽奖同时只有一条未完成的执行记录，上一次触发重试期间不会提前准备下一次。
+fn prepare(conn: &mut Connection, now: i64) -> Result<()> {
+    let upcoming = {
+        let sql = format!(
+            "{} where enabled=1 and next_run>?1
+                and act_id in (select act_id from ld_activity where act_status=?2)
+                and not exists (
+                    select 1 from ld_schedule_run r
+                     where r.sch_id=ld_schedule.sch_id
+                       and (r.fire_time=ld_schedule.next_run or r.run_status in (?3, ?4)))",
+            schedule::SELECT_SCHEDULE
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let mut rows = stmt.query((
+            now,
+            ActStatus::Drawing as i64,
+            RunStatus::Pending as i64,
+            RunStatus::Running as i64,
+        ))?;
+        let mut upcoming = Vec::new();
+        while let Some(row) = rows.next()? {
+            upcoming.push(Schedule::from_row(row)?);
+        }
+        upcoming
+    };
+
+    for schedule in upcoming {
+        if let Some(fire_time) = schedule.next_run {
+            if let Err(e) = prepare_run(conn, &schedule, fire_time) {
+                warn!("定时抽奖{}公布种子失败: {e:?}", schedule.sch_id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn prepare_run(conn: &mut Connection, schedule: &Schedule, fire_time: i64) -> Result<()> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    tx.execute(
+        "insert into ld_schedule_run (sch_id, fire_time, run_status, attempts, next_attempt)
+         values (?, ?, ?, 0, ?)",
+        (
+            schedule.sch_id,
+            fire_time,
+            RunStatus::Pending as i64,
+            fire_time,
+        ),
+    )?;
+    let run_id = tx.last_insert_rowid() as usize;
+    for (act_seq, remaining) in targets(&tx, schedule)? {
+        if remaining == 0 {
+            continue;
+        }
+        let record = seed::commit_in(&tx, schedule.act_id, act_seq)?;
+        tx.execute(
+            "update ld_draw set run_id=? where draw_id=?",
+            (run_id, record.draw_id),
+        )?;
+    }
+    tx.commit()?;
+    info!(
+        "定时抽奖{}已为计划时间{fire_time}公布种子哈希",
+        schedule.sch_id
+    );
+
+    Ok(())
+}
+
+/// 为到期的定时抽奖生成执行记录，并推进下一次触发时间
+///
+/// 两步在同一个事务中完成，每个触发时间只会生成一条执行记录。
+/// 服务停机期间错过的多次触发只补执行最近的一次。
+fn enqueue(conn: &mut Connection, now: i64) -> Result<()> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let due = {
+        let sql = format!(
+            "{} where enabled=1 and next_run<=?",
+            schedule::SELECT_SCHEDULE
+        );
+        let mut stmt = tx.prepare(&sql)?;
+        let mut rows = stmt.query([now])?;
+        let mut due = Vec::new();
+        while let Some(row) = rows.next()? {
+            due.push(Schedule::from_row(row)?);
+        }
+        due
+    };
+
+    for schedule in due {
+        let Some(mut fire_time) = schedule.next_run else {
+            continue;
+        };
+        let mut next_run = schedule.next_after(fire_time)?;
+        while let Some(next) = next_run.filter(|next| *next <= now) {
+            fire_time = next;
+            next_run = schedule.next_after(fire_time)?;
+        }
+
+        //错过多次触发时，提前准备的执行记录改为最近的一次，预先公布的种子继续有效
+        if Some(fire_time) != schedule.next_run {
+            tx.execute(
+                "update ld_schedule_run set fire_time=?, next_attempt=?
+                  where sch_id=? and fire_time=? and run_status=? and attempts=0",
+                (
+                    fire_time,
+                    now,
+                    schedule.sch_id,
+                    schedule.next_run,
+                    RunStatus::Pending as i64,
+                ),
+            )?;
+        }
+        tx.execute(
+            "insert or ignore into ld_schedule_run
+                    (sch_id, fire_time, run_status, attempts, next_attempt)
+             values (?, ?, ?, 0, ?)",
+            (schedule.sch_id, fire_time, RunStatus::Pending as i64, now),
+        )?;
+        tx.execute(
+            "update ld_schedule set next_run=? where sch_id=?",
+            (next_run, schedule.sch_id),
+        )?;
+        info!(
+            "定时抽奖{}触发，计划时间{fire_time}，下一次{next_run:?}",
+            schedule.sch_id
+        );
+    }
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// 执行一条待执行记录，业务错误不重试，其他错误按配置的次数和间隔重试
+fn run(conn: &mut Connection, run_id: usize, on_draw: &dyn Fn(&DrawResult)) -> Result<()> {
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    let claimed = conn.execute(
+        "update ld_schedule_run set run_status=?, attempts=attempts+1, start_time=?
+          where run_id=? and run_status=?",
+        (
+            RunStatus::Running as i64,
+            now,
+            run_id,
+            RunStatus::Pending as i64,
+        ),
+    )?;
+    if claimed == 0 {
+        return Ok(());
+    }
+    let run = get_run(conn, run_id)?;
+    let schedule = schedule::get(conn, run.sch_id)?;
+    info!(
+        "执行定时抽奖{} 第{}次尝试，计划时间{}",
+        schedule.sch_id, run.attempts, run.fire_time
+    );
+
+    let res = execute(conn, &run, &schedule, on_draw);
+    let end_time = time::OffsetDateTime::now_utc().unix_timestamp();
+    let (status, next_attempt, message) = match res {
+        Ok(message) => (RunStatus::Succeeded, run.next_attempt, message),
+        Err(e) if e.downcast_ref::<BizError>().is_some() => {
+            (RunStatus::Failed, run.next_attempt, e.to_string())
+        }
+        Err(e) if run.attempts < schedule.max_attempts => {
+            warn!("定时抽奖{}执行失败，稍后重试: {e:?}", schedule.sch_id);
+            (
+                RunStatus::Pending,
+                end_time + schedule.retry_delay,
+                e.to_string(),
+            )
+        }
+        Err(e) => (RunStatus::Failed, run.next_attempt, e.to_string()),
+    };
+    conn.execute(
+        "update ld_schedule_run set run_status=?, next_attempt=?, end_time=?, message=?
+          where run_id=?",
+        (status as i64, next_attempt, end_time, &message, run_id),
+    )?;
+    info!("定时抽奖{}执行结果 {status:?}: {message}", schedule.sch_id);
+
+    Ok(())
+}
+
+/// 按定时抽奖的配置抽奖，返回执行摘要
+///
+/// 只使用在触发时间之前公布的种子，没有预先公布种子的奖项不会抽奖。
+fn execute(
+    conn: &mut Connection,
+    run: &Run,
+    schedule: &Schedule,
+    on_draw: &dyn Fn(&DrawResult),
+) -> Result<String> {
+    let act_id = schedule.act_id;
+    let run_id = run.run_id;
+    //重试时跳过本次触发已经抽过的奖项
+    let drawn = {
+        let mut stmt =
+            conn.prepare("select act_seq from ld_draw where run_id=? and draw_time is not null")?;
+        let seqs = stmt
+            .query_map([run_id], |row| row.get(0))?
+            .collect::<Result<HashSet<usize>, _>>()?;
+        seqs
+    };
+
+    let mut summary = Vec::new();
+    for (act_seq, remaining) in targets(conn, schedule)? {
+        if drawn.contains(&act_seq) {
+            continue;
+        }
+
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let published = seed::pending(&tx, act_id, act_seq)?
+            .is_some_and(|pending| pending.record.commit_time < run.fire_time);
+        if remaining > 0 && !published {
+            return Err(
+                BizError::Conflict(format!("奖项{act_seq}没有在触发时间之前公布种子哈希")).into(),
+            );
+        }
+        let count = schedule.draw_count.map(|count| count.min(remaining));
+        let result = draw::draw_in(&tx, act_id, act_seq, count, 0)?;
+        tx.execute(
+            "update ld_draw set run_id=? where draw_id=?",
+            (run_id, result.seed.draw_id),
+        )?;
+        tx.commit()?;
+
+        on_draw(&result);
+        summary.push(match result.plan.draw_unit {
+            DrawUnit::Customer => format!("奖项{act_seq}抽出{}人", result.drawn),
+            DrawUnit::Team => format!(
+                "奖项{act_seq}抽出{}个团体共{}人",
+                result.drawn,
+                result.winners.len()
+            ),
+        });
+    }
+
+    if summary.is_empty() && drawn.is_empty() {
+        return Err(BizError::Conflict(format!("活动{act_id}没有剩余名额")).into());
+    }
+    if summary.is_empty() {
+        return Ok("奖项已在之前的尝试中抽完".to_owned());
+    }
+    Ok(summary.join("，"))
+}
@@ -0,0 +1,22 @@
+//! 测试用的内存数据库，表结构来自 init_data/sql/table
+
+use std::path::Path;
+
+use r2d2_sqlite::rusqlite::Connection;
+
+/// 打开一个建好全部表的内存数据库
+pub(crate) fn open() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("init_data/sql/table");
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let sql = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+        //脚本开头的 drop table 在空库中会失败
+        let sql = sql
+            .lines()
+            .filter(|line| !line.starts_with("drop table"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        conn.execute_batch(&sql).unwrap();
+    }
+    conn
+}
@@ -0,0 +1,387 @@
+use serde::Deserialize;
+use tide::{Response, StatusCode};
+use tracing::{info, info_span, Span};
+
+use crate::activity;
+use crate::activity::plan::{self, PlanArgs, Range};
+use crate::activity::policy::{self, Policy};
+use crate::activity::state::{self, ActStatus};
+use crate::activity::team::{self, TeamArgs};
+use crate::activity::ActivityArgs;
+use crate::draw::quota::Quota;
+use crate::web::session::SessionExt;
+use crate::web::{param, reply, WebRequest};
+
+#[derive(Deserialize)]
+struct StatusReq {
+    status: ActStatus,
+}
+
+pub(crate) async fn status(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询活动状态").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        state::history(&conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn transit(mut req: WebRequest) -> tide::Result {
+    let userid: usize = match req.session().get("userid") {
+        Some(id) => id,
+        None => return Ok(Response::from(StatusCode::Unauthorized)),
+    };
+    let act_id: usize = param(&req, "act_id")?;
+    let status_req = req.body_json::<StatusReq>().await?;
+    info!(
+        "act_id: {act_id}, status: {:?}, userid: {userid}",
+        status_req.status
+    );
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "变更活动状态").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        state::transit(&mut conn, act_id, status_req.status, userid)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn list(req: WebRequest) -> tide::Result {
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询活动列表").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        activity::list(&conn)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn create(mut req: WebRequest) -> tide::Result {
+    let args = req.body_json::<ActivityArgs>().await?;
+    info!("args: {args:?}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "新建活动").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        activity::create(&conn, &args)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn detail(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询活动详情").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        activity::detail(&conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn update(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let args = req.body_json::<ActivityArgs>().await?;
+    info!("act_id: {act_id}, args: {args:?}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "修改活动").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        activity::update(&conn, act_id, &args)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn remove(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "删除活动").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        activity::remove(&mut conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn plans(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询奖项列表").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        activity::get(&conn, act_id)?;
+        plan::list(&conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn create_plan(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let args = req.body_json::<PlanArgs>().await?;
+    info!("act_id: {act_id}, args: {args:?}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "新建奖项").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        plan::create(&conn, act_id, &args)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn plan(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let act_seq: usize = param(&req, "act_seq")?;
+    info!("act_id: {act_id}, act_seq: {act_seq}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询奖项").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        plan::get(&conn, act_id, act_seq)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn update_plan(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let act_seq: usize = param(&req, "act_seq")?;
+    let args = req.body_json::<PlanArgs>().await?;
+    info!("act_id: {act_id}, act_seq: {act_seq}, args: {args:?}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "修改奖项").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        plan::update(&mut conn, act_id, act_seq, &args)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn remove_plan(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let act_seq: usize = param(&req, "act_seq")?;
+    info!("act_id: {act_id}, act_seq: {act_seq}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "删除奖项").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        plan::remove(&mut conn, act_id, act_seq)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn ranges(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let act_seq: usize = param(&req, "act_seq")?;
+    info!("act_id: {act_id}, act_seq: {act_seq}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询参与规则").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        plan::get(&conn, act_id, act_seq).map(|detail| detail.ranges)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn save_ranges(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let act_seq: usize = param(&req, "act_seq")?;
+    let ranges = req.body_json::<Vec<Range>>().await?;
+    info!("act_id: {act_id}, act_seq: {act_seq}, ranges: {ranges:?}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "保存参与规则").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        plan::save_ranges(&mut conn, act_id, act_seq, &ranges)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn quotas(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let act_seq: usize = param(&req, "act_seq")?;
+    info!("act_id: {act_id}, act_seq: {act_seq}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询分组配额").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        plan::get(&conn, act_id, act_seq).map(|detail| detail.quotas)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn save_quotas(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let act_seq: usize = param(&req, "act_seq")?;
+    let quotas = req.body_json::<Vec<Quota>>().await?;
+    info!("act_id: {act_id}, act_seq: {act_seq}, quotas: {quotas:?}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "保存分组配额").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        plan::save_quotas(&mut conn, act_id, act_seq, &quotas)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn teams(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询团体列表").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        team::list(&conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn create_team(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let args = req.body_json::<TeamArgs>().await?;
+    info!("act_id: {act_id}, args: {args:?}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "新建团体").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        team::create(&mut conn, act_id, &args)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn team(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let team_id: usize = param(&req, "team_id")?;
+    info!("act_id: {act_id}, team_id: {team_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询团体").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        team::get(&conn, act_id, team_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn update_team(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let team_id: usize = param(&req, "team_id")?;
+    let args = req.body_json::<TeamArgs>().await?;
+    info!("act_id: {act_id}, team_id: {team_id}, args: {args:?}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "修改团体").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        team::update(&mut conn, act_id, team_id, &args)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn remove_team(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let team_id: usize = param(&req, "team_id")?;
+    info!("act_id: {act_id}, team_id: {team_id}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "删除团体").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        team::remove(&mut conn, act_id, team_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn policy(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询中奖限制").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        policy::get(&conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn save_policy(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let args = req.body_json::<Policy>().await?;
+    info!("act_id: {act_id}, args: {args:?}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "保存中奖限制").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        policy::save(&conn, act_id, &args)
+    })
+    .await;
+
+    reply(res)
+}
@@ -0,0 +1,33 @@
+use tracing::{info, info_span, Span};
+
+use crate::audit;
+use crate::web::{param, reply, WebRequest};
+
+/// 活动的审计记录
+pub(crate) async fn list(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询审计记录").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        audit::list(&conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+/// 校验审计日志的哈希链，并与中奖名单比对
+pub(crate) async fn verify(req: WebRequest) -> tide::Result {
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "校验审计日志").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        audit::verify(&conn)
+    })
+    .await;
+
+    reply(res)
+}
@@ -0,0 +1,103 @@
+use serde::Deserialize;
+use tide::{Response, StatusCode};
+use tracing::{info, info_span, Span};
+
+use crate::checkin;
+use crate::web::session::SessionExt;
+use crate::web::{param, reply, WebRequest};
+
+/// 生成缺少的签到码，返回活动的全部签到码
+pub(crate) async fn issue_tokens(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "生成签到码").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        checkin::issue_tokens(&mut conn, act_id)?;
+        checkin::list(&conn, act_id, None)
+    })
+    .await;
+
+    reply(res)
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ListReq {
+    checked_in: Option<bool>,
+}
+
+/// 签到名单，`?checked_in=true|false` 按是否签到过滤
+pub(crate) async fn list(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let ListReq { checked_in } = req.query()?;
+    info!("act_id: {act_id}, checked_in: {checked_in:?}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询签到名单").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        checkin::list(&conn, act_id, checked_in)
+    })
+    .await;
+
+    reply(res)
+}
+
+#[derive(Deserialize)]
+struct CheckInReq {
+    token: String,
+}
+
+/// 工作人员扫码签到
+pub(crate) async fn check_in(mut req: WebRequest) -> tide::Result {
+    let userid: usize = match req.session().get("userid") {
+        Some(id) => id,
+        None => return Ok(Response::from(StatusCode::Unauthorized)),
+    };
+    let act_id: usize = param(&req, "act_id")?;
+    let CheckInReq { token } = req.body_json().await?;
+    info!("act_id: {act_id}, userid: {userid}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "签到").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        checkin::check_in(&mut conn, Some(act_id), &token, Some(userid))
+    })
+    .await;
+
+    reply(res)
+}
+
+/// 自助签到页面查询签到信息，不需要登录
+pub(crate) async fn get_public(req: WebRequest) -> tide::Result {
+    let token: String = param(&req, "token")?;
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询自助签到").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        checkin::get_public(&conn, &token)
+    })
+    .await;
+
+    reply(res)
+}
+
+/// 客户通过公开链接自助签到，不需要登录
+pub(crate) async fn self_check_in(req: WebRequest) -> tide::Result {
+    let token: String = param(&req, "token")?;
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "自助签到").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        checkin::check_in(&mut conn, None, &token, None)
+    })
+    .await;
+
+    reply(res)
+}
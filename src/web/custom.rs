@@ -0,0 +1,34 @@
+use serde::Deserialize;
+use tracing::{info, info_span, Span};
+
+use crate::custom::import::{self, Mapping};
+use crate::web::{read_upload, reply, UploadFile, WebRequest};
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ImportReq {
+    dry_run: bool,
+}
+
+/// 从 csv 或 xlsx 批量导入客户，列映射通过查询参数指定，如 `?cus_phone=联系电话`
+pub(crate) async fn import(mut req: WebRequest) -> tide::Result {
+    let ImportReq { dry_run } = req.query()?;
+    let mapping: Mapping = req.query()?;
+    let UploadFile {
+        data, file_name, ..
+    } = read_upload(&mut req, import::MAX_FILE_SIZE).await?;
+    info!(
+        "导入客户 {file_name:?} {} bytes dry_run={dry_run}",
+        data.len()
+    );
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "导入客户").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        import::import(&mut conn, &data, &mapping, dry_run)
+    })
+    .await;
+
+    reply(res)
+}
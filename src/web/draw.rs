@@ -0,0 +1,222 @@
+use serde::Deserialize;
+use tracing::{info, info_span, Span};
+
+use crate::draw;
+use crate::draw::weight::Weight;
+use crate::draw::{quota, seed, simulate, verify, weight};
+use crate::web::{param, reply, WebRequest};
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct DrawReq {
+    count: Option<usize>,
+    /// 同时抽出的候补人数
+    alternates: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct PreviewReq {
+    page: usize,
+    size: usize,
+}
+
+impl Default for PreviewReq {
+    fn default() -> Self {
+        PreviewReq { page: 1, size: 20 }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct SimulateReq {
+    runs: usize,
+}
+
+impl Default for SimulateReq {
+    fn default() -> Self {
+        SimulateReq { runs: 1000 }
+    }
+}
+
+pub(crate) async fn draw(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let act_seq: usize = param(&req, "act_seq")?;
+    let draw_req: DrawReq = req.query()?;
+    info!(
+        "act_id: {act_id}, act_seq: {act_seq}, count: {:?}, alternates: {}",
+        draw_req.count, draw_req.alternates
+    );
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "抽奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        draw::draw(
+            &mut conn,
+            act_id,
+            act_seq,
+            draw_req.count,
+            draw_req.alternates,
+        )
+    })
+    .await;
+
+    if let Ok(result) = &res {
+        req.state().live.publish_draw(result);
+    }
+    reply(res)
+}
+
+pub(crate) async fn preview(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let act_seq: usize = param(&req, "act_seq")?;
+    let preview_req: PreviewReq = req.query()?;
+    info!(
+        "act_id: {act_id}, act_seq: {act_seq}, page: {}, size: {}",
+        preview_req.page, preview_req.size
+    );
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "预览候选人").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        draw::preview(
+            &conn,
+            act_id,
+            act_seq,
+            preview_req.page.max(1),
+            preview_req.size.clamp(1, 500),
+        )
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn commit(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let act_seq: usize = param(&req, "act_seq")?;
+    info!("act_id: {act_id}, act_seq: {act_seq}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "公布种子哈希").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        seed::commit(&mut conn, act_id, act_seq)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn seeds(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询种子记录").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        seed::list(&conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn verify(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let act_seq: usize = param(&req, "act_seq")?;
+    info!("act_id: {act_id}, act_seq: {act_seq}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "复算抽奖结果").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        verify::verify(&conn, act_id, act_seq)
+    })
+    .await;
+
+    reply(res)
+}
+
+/// 奖项各分组的配额执行情况
+pub(crate) async fn quotas(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let act_seq: usize = param(&req, "act_seq")?;
+    info!("act_id: {act_id}, act_seq: {act_seq}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询配额执行情况").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        quota::outcome(&conn, act_id, act_seq)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn weights(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询权重").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        weight::list(&conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn save_weights(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let weights = req.body_json::<Vec<Weight>>().await?;
+    info!("act_id: {act_id}, weights: {}", weights.len());
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "保存权重").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        weight::save(&mut conn, act_id, &weights)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn remove_weight(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let cus_id: usize = param(&req, "cus_id")?;
+    info!("act_id: {act_id}, cus_id: {cus_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "删除权重").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        weight::remove(&conn, act_id, cus_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn simulate(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let SimulateReq { runs } = req.query()?;
+    info!("act_id: {act_id}, runs: {runs}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "模拟抽奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        simulate::simulate(&conn, act_id, runs)
+    })
+    .await;
+
+    reply(res)
+}
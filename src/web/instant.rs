@@ -0,0 +1,111 @@
+use serde::Deserialize;
+use tracing::{info, info_span, Span};
+
+use crate::instant::{self, InstantArgs};
+use crate::web::{param, reply, WebRequest};
+
+pub(crate) async fn get(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询即时抽奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        instant::get(&conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn save(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let args = req.body_json::<InstantArgs>().await?;
+    info!("act_id: {act_id}, args: {args:?}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "保存即时抽奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        instant::save(&mut conn, act_id, &args)
+    })
+    .await;
+
+    reply(res)
+}
+
+#[derive(Deserialize)]
+struct PlayReq {
+    cus_id: usize,
+}
+
+/// 工作人员代客户抽奖
+pub(crate) async fn play(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let PlayReq { cus_id } = req.body_json().await?;
+    info!("act_id: {act_id}, cus_id: {cus_id}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "即时抽奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        instant::play(&mut conn, act_id, cus_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct PlaysReq {
+    cus_id: Option<usize>,
+}
+
+/// 即时抽奖记录，`?cus_id=` 按客户过滤
+pub(crate) async fn plays(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let PlaysReq { cus_id } = req.query()?;
+    info!("act_id: {act_id}, cus_id: {cus_id:?}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询即时抽奖记录").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        instant::plays(&conn, act_id, cus_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+/// 客户凭签到码查询剩余次数和抽奖记录，不需要登录
+pub(crate) async fn player(req: WebRequest) -> tide::Result {
+    let token: String = param(&req, "token")?;
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询自助抽奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        instant::player(&conn, &token)
+    })
+    .await;
+
+    reply(res)
+}
+
+/// 客户凭签到码自助抽奖，不需要登录
+pub(crate) async fn play_with_token(req: WebRequest) -> tide::Result {
+    let token: String = param(&req, "token")?;
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "自助即时抽奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        instant::play_with_token(&mut conn, &token)
+    })
+    .await;
+
+    reply(res)
+}
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_channel::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use tracing::{info, info_span, Span};
+
+use crate::activity;
+use crate::activity::state::{self, ActStatus};
+use crate::draw::{self, Candidate, DrawResult};
+use crate::web::{param, reply, WebRequest};
+
+/// 推送给大屏和手机的抽奖事件
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum LiveEvent {
+    /// 开始滚动候选人名单
+    Rolling { act_seq: usize },
+    /// 公布本次抽奖的中奖者
+    WinnerRevealed {
+        act_seq: usize,
+        draw_id: usize,
+        seed_hash: String,
+        winners: Vec<Candidate>,
+        /// 红包奖项中与 winners 一一对应的金额（分）
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        amounts: Vec<i64>,
+    },
+    /// 奖项名额已抽完
+    TierFinished { act_seq: usize },
+    /// 暂停滚动
+    Paused { act_seq: Option<usize> },
+}
+
+impl LiveEvent {
+    /// sse 的事件名
+    fn name(&self) -> &'static str {
+        match self {
+            LiveEvent::Rolling { .. } => "rolling",
+            LiveEvent::WinnerRevealed { .. } => "winner_revealed",
+            LiveEvent::TierFinished { .. } => "tier_finished",
+            LiveEvent::Paused { .. } => "paused",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Phase {
+    #[default]
+    Idle,
+    Rolling,
+    Revealed,
+    Paused,
+}
+
+/// 现场的当前状态，新连接或重连时先收到这个状态
+#[derive(Clone, Debug, Default, Serialize)]
+pub(crate) struct LiveState {
+    pub act_id: usize,
+    /// 最后一个事件的编号，同时作为 sse 的事件 id
+    pub seq: u64,
+    pub phase: Phase,
+    /// 当前奖项
+    pub act_seq: Option<usize>,
+    /// 最近一次公布的中奖者
+    pub winners: Vec<Candidate>,
+    /// 最近一次公布的红包金额
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub amounts: Vec<i64>,
+    /// 已抽完的奖项
+    pub finished: Vec<usize>,
+}
+
+impl LiveState {
+    /// 更新状态，没有指定奖项的暂停事件补上当前奖项
+    fn apply(&mut self, event: &mut LiveEvent) {
+        self.seq += 1;
+        match event {
+            LiveEvent::Rolling { act_seq } => {
+                self.phase = Phase::Rolling;
+                self.act_seq = Some(*act_seq);
+                self.winners.clear();
+                self.amounts.clear();
+            }
+            LiveEvent::WinnerRevealed {
+                act_seq,
+                winners,
+                amounts,
+                ..
+            } => {
+                self.phase = Phase::Revealed;
+                self.act_seq = Some(*act_seq);
+                self.winners = winners.clone();
+                self.amounts = amounts.clone();
+            }
+            LiveEvent::TierFinished { act_seq } => {
+                if !self.finished.contains(act_seq) {
+                    self.finished.push(*act_seq);
+                }
+            }
+            LiveEvent::Paused { act_seq } => {
+                self.phase = Phase::Paused;
+                self.act_seq = act_seq.or(self.act_seq);
+                *act_seq = self.act_seq;
+            }
+        }
+    }
+}
+
+/// 已序列化的 sse 消息
+#[derive(Clone, Debug)]
+struct Message {
+    id: String,
+    name: &'static str,
+    data: String,
+}
+
+#[derive(Debug, Default)]
+struct Channel {
+    state: LiveState,
+    subscribers: Vec<Sender<Message>>,
+}
+
+/// 按活动分组的直播频道，只保存在内存中
+#[derive(Debug, Default)]
+pub(crate) struct Hub {
+    channels: Mutex<HashMap<usize, Channel>>,
+}
+
+impl Hub {
+    /// 广播事件，返回更新后的状态
+    pub(crate) fn publish(&self, act_id: usize, mut event: LiveEvent) -> LiveState {
+        let mut channels = self.channels.lock().unwrap();
+        let channel = channels.entry(act_id).or_default();
+        channel.state.act_id = act_id;
+        channel.state.apply(&mut event);
+
+        let message = Message {
+            id: channel.state.seq.to_string(),
+            name: event.name(),
+            data: serde_json::to_string(&event).unwrap_or_default(),
+        };
+        //连接断开后接收端会被丢弃，顺便清理
+        channel
+            .subscribers
+            .retain(|subscriber| subscriber.try_send(message.clone()).is_ok());
+        info!(
+            "活动{act_id}广播 {} 给{}个连接",
+            message.name,
+            channel.subscribers.len()
+        );
+
+        channel.state.clone()
+    }
+
+    fn subscribe(&self, act_id: usize) -> (LiveState, Receiver<Message>) {
+        let mut channels = self.channels.lock().unwrap();
+        let channel = channels.entry(act_id).or_default();
+        channel.state.act_id = act_id;
+
+        let (sender, receiver) = async_channel::unbounded();
+        channel.subscribers.push(sender);
+        (channel.state.clone(), receiver)
+    }
+
+    /// 抽奖完成后公布中奖者，名额抽完时再通知奖项结束
+    pub(crate) fn publish_draw(&self, result: &DrawResult) {
+        let plan = &result.plan;
+        self.publish(
+            plan.act_id,
+            LiveEvent::WinnerRevealed {
+                act_seq: plan.act_seq,
+                draw_id: result.seed.draw_id,
+                seed_hash: result.seed.seed_hash.clone(),
+                winners: result.winners.clone(),
+                amounts: result.amounts.clone(),
+            },
+        );
+        if result.drawn_before + result.drawn >= plan.prize_amount {
+            self.publish(
+                plan.act_id,
+                LiveEvent::TierFinished {
+                    act_seq: plan.act_seq,
+                },
+            );
+        }
+    }
+}
+
+/// 订阅活动的抽奖事件（sse），连接后先推送当前状态
+pub(crate) async fn stream(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+
+    let conn = req.state().pool.get()?;
+    let res = async_global_executor::spawn_blocking(move || activity::get(&conn, act_id)).await;
+    if let Err(e) = res {
+        return reply::<()>(Err(e));
+    }
+
+    Ok(tide::sse::upgrade(
+        req,
+        move |req: WebRequest, sender| async move {
+            let (state, receiver) = req.state().live.subscribe(act_id);
+            info!("活动{act_id}新增直播连接，当前事件{}", state.seq);
+
+            sender
+                .send(
+                    "state",
+                    serde_json::to_string(&state)?,
+                    Some(&state.seq.to_string()),
+                )
+                .await?;
+            while let Ok(message) = receiver.recv().await {
+                sender
+                    .send(message.name, &message.data, Some(&message.id))
+                    .await?;
+            }
+            Ok(())
+        },
+    ))
+}
+
+/// 主持人控制现场：开始滚动或暂停
+#[derive(Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Control {
+    Rolling { act_seq: usize },
+    Paused,
+}
+
+pub(crate) async fn control(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let control: Control = req.body_json().await?;
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "控制现场").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        match control {
+            Control::Rolling { act_seq } => {
+                info!("act_id: {act_id}, 开始滚动奖项{act_seq}");
+                state::ensure(&conn, act_id, &[ActStatus::Drawing], "滚动名单")?;
+                draw::load_plan(&conn, act_id, act_seq)?;
+                Ok(LiveEvent::Rolling { act_seq })
+            }
+            Control::Paused => {
+                info!("act_id: {act_id}, 暂停");
+                activity::get(&conn, act_id)?;
+                Ok(LiveEvent::Paused { act_seq: None })
+            }
+        }
+    })
+    .await;
+
+    reply(res.map(|event| req.state().live.publish(act_id, event)))
+}
@@ -1,25 +1,45 @@
-use std::fmt::Debug;
-use std::iter::repeat_with;
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+use std::sync::Arc;
 
 use crate::config::{Config, GLOBAL_CONFIG};
+use crate::error::BizError;
+use crate::schedule::runner;
 use anyhow::Result;
 use arc_swap::access::Access;
 use async_session::MemoryStore;
+use futures_lite::{stream, AsyncReadExt};
+use multer::Multipart;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use tide::{Request, Server};
+use serde::Serialize;
+use tide::utils::After;
+use tide::{Body, Request, Response, Server, StatusCode};
 use tide_rustls::TlsListener;
 use time::Duration;
 
+pub(crate) mod activity;
+pub(crate) mod audit;
 pub(crate) mod auth;
+pub(crate) mod checkin;
+pub(crate) mod custom;
+pub(crate) mod draw;
+pub(crate) mod instant;
+pub(crate) mod live;
 pub(crate) mod log_ext;
 pub(crate) mod menu;
+pub(crate) mod partition;
+pub(crate) mod picture;
+pub(crate) mod schedule;
 pub(crate) mod session;
 pub(crate) mod static_file;
+pub(crate) mod wheel;
+pub(crate) mod winner;
 
 #[derive(Clone, Debug)]
 pub(crate) struct WebState {
     pub(crate) pool: Pool<SqliteConnectionManager>,
+    pub(crate) live: Arc<live::Hub>,
 }
 
 impl Default for WebState {
@@ -28,6 +48,7 @@ impl Default for WebState {
         let sqlite = SqliteConnectionManager::file(&*sqlite_cfg.path);
         WebState {
             pool: Pool::new(sqlite).unwrap(),
+            live: Arc::new(live::Hub::default()),
         }
     }
 }
@@ -36,7 +57,10 @@ pub(crate) type WebServer = Server<WebState>;
 pub(crate) type WebRequest = Request<WebState>;
 
 pub(crate) async fn listen() {
-    let app = new(WebState::default()).unwrap();
+    let state = WebState::default();
+    let live = state.live.clone();
+    runner::spawn(state.pool.clone(), move |result| live.publish_draw(result));
+    let app = new(state).unwrap();
 
     let web_cfg = GLOBAL_CONFIG.map(|cfg: &Config| &cfg.web).load();
     let listener = TlsListener::build()
@@ -49,9 +73,8 @@ pub(crate) async fn listen() {
 }
 
 fn new(state: WebState) -> Result<WebServer> {
-    let secret = repeat_with(|| fastrand::u8(..))
-        .take(64)
-        .collect::<Vec<u8>>();
+    let mut secret = [0u8; 64];
+    getrandom::fill(&mut secret).map_err(|e| anyhow::anyhow!("生成会话密钥失败: {e}"))?;
     let session = session::SessionMiddleware::new(MemoryStore::default(), &secret)
         .with_session_ttl(Some(Duration::seconds(3600)));
 
@@ -65,6 +88,14 @@ fn new(state: WebState) -> Result<WebServer> {
     app.with(auth::Authentication::new());
     // login
     app.at("/login").post(auth::login);
+    // 自助签到
+    app.at("/checkin/:token")
+        .get(checkin::get_public)
+        .post(checkin::self_check_in);
+    // 凭签到码自助即时抽奖
+    app.at("/instant/:token")
+        .get(instant::player)
+        .post(instant::play_with_token);
 
     Ok(route(app))
 }
@@ -74,7 +105,124 @@ pub(crate) fn route(mut app: Server<WebState>) -> Server<WebState> {
     // app.at("/api/sqlite/query").post(sqlite::query);
 
     let mut api = tide::with_state(app.state().clone());
+    api.with(After(error_body));
     api.at("/menu").get(menu::get);
+    api.at("/activity")
+        .get(activity::list)
+        .post(activity::create);
+    api.at("/activity/:act_id")
+        .get(activity::detail)
+        .put(activity::update)
+        .delete(activity::remove);
+    api.at("/activity/:act_id/plan")
+        .get(activity::plans)
+        .post(activity::create_plan);
+    api.at("/activity/:act_id/plan/:act_seq")
+        .get(activity::plan)
+        .put(activity::update_plan)
+        .delete(activity::remove_plan);
+    api.at("/activity/:act_id/plan/:act_seq/range")
+        .get(activity::ranges)
+        .put(activity::save_ranges);
+    api.at("/activity/:act_id/plan/:act_seq/quota")
+        .get(activity::quotas)
+        .put(activity::save_quotas);
+    api.at("/activity/:act_id/team")
+        .get(activity::teams)
+        .post(activity::create_team);
+    api.at("/activity/:act_id/team/:team_id")
+        .get(activity::team)
+        .put(activity::update_team)
+        .delete(activity::remove_team);
+    api.at("/activity/:act_id/policy")
+        .get(activity::policy)
+        .put(activity::save_policy);
+    api.at("/activity/:act_id/checkin")
+        .get(checkin::list)
+        .post(checkin::check_in);
+    api.at("/activity/:act_id/checkin/tokens")
+        .post(checkin::issue_tokens);
+    api.at("/activity/:act_id/picture")
+        .get(picture::get_activity)
+        .post(picture::upload_activity);
+    api.at("/activity/:act_id/plan/:act_seq/picture")
+        .get(picture::get_prize)
+        .post(picture::upload_prize);
+    api.at("/custom/import").post(custom::import);
+    api.at("/custom/:cus_id/picture")
+        .get(picture::get_custom)
+        .post(picture::upload_custom);
+    api.at("/activity/:act_id/status")
+        .get(activity::status)
+        .post(activity::transit);
+    api.at("/activity/:act_id/live")
+        .get(live::stream)
+        .post(live::control);
+    api.at("/activity/:act_id/seeds").get(draw::seeds);
+    api.at("/activity/:act_id/weights")
+        .get(draw::weights)
+        .put(draw::save_weights);
+    api.at("/activity/:act_id/weights/:cus_id")
+        .delete(draw::remove_weight);
+    api.at("/activity/:act_id/draw/:act_seq").post(draw::draw);
+    api.at("/activity/:act_id/draw/:act_seq/commit")
+        .post(draw::commit);
+    api.at("/activity/:act_id/draw/:act_seq/verify")
+        .get(draw::verify);
+    api.at("/activity/:act_id/draw/:act_seq/eligible")
+        .get(draw::preview);
+    api.at("/activity/:act_id/draw/:act_seq/quota")
+        .get(draw::quotas);
+    api.at("/activity/:act_id/schedules")
+        .get(schedule::list)
+        .post(schedule::create);
+    api.at("/schedules/:sch_id")
+        .get(schedule::get)
+        .put(schedule::update)
+        .delete(schedule::remove);
+    api.at("/schedules/:sch_id/runs").get(schedule::runs);
+    api.at("/activity/:act_id/simulate").get(draw::simulate);
+    api.at("/activity/:act_id/partition")
+        .get(partition::list)
+        .post(partition::create);
+    api.at("/activity/:act_id/partition/:part_id")
+        .get(partition::get)
+        .put(partition::update)
+        .delete(partition::remove);
+    api.at("/activity/:act_id/partition/:part_id/commit")
+        .post(partition::commit);
+    api.at("/activity/:act_id/partition/:part_id/run")
+        .post(partition::run);
+    api.at("/activity/:act_id/partition/:part_id/roster")
+        .get(partition::roster);
+    api.at("/activity/:act_id/partition/:part_id/verify")
+        .get(partition::verify);
+    api.at("/activity/:act_id/partition/:part_id/export")
+        .get(partition::export);
+    api.at("/activity/:act_id/wheel")
+        .get(wheel::sectors)
+        .put(wheel::save_sectors);
+    api.at("/activity/:act_id/wheel/spin").post(wheel::spin);
+    api.at("/activity/:act_id/wheel/spins").get(wheel::spins);
+    api.at("/activity/:act_id/instant")
+        .get(instant::get)
+        .put(instant::save);
+    api.at("/activity/:act_id/instant/play").post(instant::play);
+    api.at("/activity/:act_id/instant/plays")
+        .get(instant::plays);
+    api.at("/activity/:act_id/winners").get(winner::list);
+    api.at("/activity/:act_id/winners/export")
+        .get(winner::export);
+    api.at("/winners/:win_id/forfeit").post(winner::forfeit);
+    api.at("/winners/:win_id/promote").post(winner::promote);
+    api.at("/winners/:win_id/redraw").post(winner::redraw);
+    api.at("/activity/:act_id/claims").get(winner::claims);
+    api.at("/winners/:win_id/claim").get(winner::claim);
+    api.at("/winners/:win_id/notify").post(winner::notify);
+    api.at("/winners/:win_id/ship").post(winner::ship);
+    api.at("/claims/redeem").post(winner::redeem);
+    api.at("/activity/:act_id/audit").get(audit::list);
+    api.at("/audit/verify").get(audit::verify);
 
     let mut static_file = tide::with_state(app.state().clone());
     static_file.at("*").get(static_file::get);
@@ -85,3 +233,125 @@ pub(crate) fn route(mut app: Server<WebState>) -> Server<WebState> {
 
     app
 }
+
+/// 解析路径参数，格式错误时返回 400
+pub(crate) fn param<T>(req: &WebRequest, name: &str) -> tide::Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    req.param(name)?.parse::<T>().map_err(|e| {
+        tide::Error::from_str(StatusCode::BadRequest, format!("参数{name}格式错误: {e}"))
+    })
+}
+
+#[derive(Serialize)]
+struct ErrorReply<'a> {
+    code: &'a str,
+    message: &'a str,
+}
+
+/// 给 tide 产生的错误响应（参数、请求体解析失败等）补上 JSON 错误体，
+/// 服务端内部错误不向客户端暴露细节
+async fn error_body(mut res: Response) -> tide::Result {
+    if let Some(err) = res.error() {
+        let status = res.status();
+        let (code, message) = match status {
+            StatusCode::NotFound => ("not_found", err.to_string()),
+            _ if status.is_client_error() => ("invalid", err.to_string()),
+            _ => ("internal", "服务器内部错误".to_owned()),
+        };
+        let body = Body::from_json(&ErrorReply {
+            code,
+            message: &message,
+        })?;
+        res.set_body(body);
+    }
+    Ok(res)
+}
+
+/// 把业务处理结果转换成 JSON 响应，业务错误转换成对应状态码的错误体
+pub(crate) fn reply<T: Serialize>(res: Result<T>) -> tide::Result {
+    match res {
+        Ok(data) => {
+            let body = Body::from_json(&data)?;
+            Ok(Response::builder(StatusCode::Ok).body(body).build())
+        }
+        Err(e) => match e.downcast_ref::<BizError>() {
+            Some(biz) => {
+                let status = match biz {
+                    BizError::NotFound(_) => StatusCode::NotFound,
+                    BizError::Invalid(_) => StatusCode::BadRequest,
+                    BizError::Conflict(_) => StatusCode::Conflict,
+                };
+                let body = Body::from_json(&ErrorReply {
+                    code: biz.code(),
+                    message: biz.message(),
+                })?;
+                Ok(Response::builder(status).body(body).build())
+            }
+            None => Err(e.into()),
+        },
+    }
+}
+
+/// multipart 除文件以外的头部等内容预留的大小
+const MULTIPART_OVERHEAD: usize = 64 * 1024;
+
+/// multipart 请求中上传的文件
+pub(crate) struct UploadFile {
+    pub data: Vec<u8>,
+    pub content_type: Option<String>,
+    pub file_name: Option<String>,
+}
+
+/// 读取 multipart 请求中名为 file 的文件，`limit` 为文件大小上限
+pub(crate) async fn read_upload(req: &mut WebRequest, limit: usize) -> tide::Result<UploadFile> {
+    let bad_request =
+        |e: multer::Error| tide::Error::from_str(StatusCode::BadRequest, e.to_string());
+    let content_type = req
+        .content_type()
+        .map(|mime| mime.to_string())
+        .unwrap_or_default();
+    let boundary = multer::parse_boundary(content_type).map_err(bad_request)?;
+
+    let limit = limit + MULTIPART_OVERHEAD;
+    if req.len().unwrap_or_default() > limit {
+        return Err(tide::Error::from_str(
+            StatusCode::PayloadTooLarge,
+            "上传的文件太大",
+        ));
+    }
+    let mut body = Vec::new();
+    req.take_body()
+        .take(limit as u64 + 1)
+        .read_to_end(&mut body)
+        .await?;
+    if body.len() > limit {
+        return Err(tide::Error::from_str(
+            StatusCode::PayloadTooLarge,
+            "上传的文件太大",
+        ));
+    }
+
+    let mut multipart = Multipart::new(stream::once(Ok::<_, std::io::Error>(body)), boundary);
+    while let Some(field) = multipart.next_field().await.map_err(bad_request)? {
+        if field.name() == Some("file") {
+            let content_type = field
+                .content_type()
+                .map(|mime| mime.essence_str().to_owned());
+            let file_name = field.file_name().map(str::to_owned);
+            let data = field.bytes().await.map_err(bad_request)?;
+            return Ok(UploadFile {
+                data: data.to_vec(),
+                content_type,
+                file_name,
+            });
+        }
+    }
+
+    Err(tide::Error::from_str(
+        StatusCode::BadRequest,
+        "缺少 file 字段",
+    ))
+}
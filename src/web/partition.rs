@@ -0,0 +1,193 @@
+use serde::Deserialize;
+use tide::http::headers::CONTENT_TYPE;
+use tide::{Response, StatusCode};
+use tracing::{info, info_span, Span};
+
+use crate::partition::{self, PartitionArgs};
+use crate::web::{param, reply, WebRequest};
+use crate::winner::export::{self, Export, ExportFormat};
+
+pub(crate) async fn list(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询分组列表").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        partition::list(&conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn create(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let args = req.body_json::<PartitionArgs>().await?;
+    info!("act_id: {act_id}, args: {args:?}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "新建分组").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        partition::create(&mut conn, act_id, &args)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn get(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let part_id: usize = param(&req, "part_id")?;
+    info!("act_id: {act_id}, part_id: {part_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询分组").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        partition::get(&conn, act_id, part_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn update(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let part_id: usize = param(&req, "part_id")?;
+    let args = req.body_json::<PartitionArgs>().await?;
+    info!("act_id: {act_id}, part_id: {part_id}, args: {args:?}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "修改分组").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        partition::update(&mut conn, act_id, part_id, &args)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn remove(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let part_id: usize = param(&req, "part_id")?;
+    info!("act_id: {act_id}, part_id: {part_id}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "删除分组").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        partition::remove(&mut conn, act_id, part_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+/// 执行随机分组，返回分组名单
+pub(crate) async fn commit(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let part_id: usize = param(&req, "part_id")?;
+    info!("act_id: {act_id}, part_id: {part_id}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "公布分组种子哈希").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        partition::commit(&mut conn, act_id, part_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn run(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let part_id: usize = param(&req, "part_id")?;
+    info!("act_id: {act_id}, part_id: {part_id}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "随机分组").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        partition::run(&mut conn, act_id, part_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn roster(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let part_id: usize = param(&req, "part_id")?;
+    info!("act_id: {act_id}, part_id: {part_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询分组名单").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        partition::roster(&conn, act_id, part_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn verify(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let part_id: usize = param(&req, "part_id")?;
+    info!("act_id: {act_id}, part_id: {part_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "复算分组").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        partition::verify(&conn, act_id, part_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ExportReq {
+    format: ExportFormat,
+}
+
+/// 导出分组名单，`?format=csv|xlsx|pdf`
+pub(crate) async fn export(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let part_id: usize = param(&req, "part_id")?;
+    let ExportReq { format } = req.query()?;
+    info!("act_id: {act_id}, part_id: {part_id}, format: {format:?}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "导出分组名单").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        export::roster(&conn, act_id, part_id, format)
+    })
+    .await;
+
+    let Export {
+        file_name,
+        mime,
+        data,
+    } = match res {
+        Ok(export) => export,
+        Err(e) => return reply::<()>(Err(e)),
+    };
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header(CONTENT_TYPE, mime)
+        .header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{file_name}\""),
+        )
+        .body(data)
+        .build())
+}
@@ -0,0 +1,99 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tide::http::headers::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use tide::{Response, StatusCode};
+use tracing::{info, info_span, Span};
+
+use crate::picture::{self, Owner, Picture};
+use crate::web::{param, read_upload, reply, UploadFile, WebRequest};
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct PictureReq {
+    thumbnail: bool,
+}
+
+pub(crate) async fn upload_activity(req: WebRequest) -> tide::Result {
+    let owner = Owner::Activity(param(&req, "act_id")?);
+    upload(req, owner).await
+}
+
+pub(crate) async fn get_activity(req: WebRequest) -> tide::Result {
+    let owner = Owner::Activity(param(&req, "act_id")?);
+    get(req, owner).await
+}
+
+pub(crate) async fn upload_prize(req: WebRequest) -> tide::Result {
+    let owner = Owner::Prize(param(&req, "act_id")?, param(&req, "act_seq")?);
+    upload(req, owner).await
+}
+
+pub(crate) async fn get_prize(req: WebRequest) -> tide::Result {
+    let owner = Owner::Prize(param(&req, "act_id")?, param(&req, "act_seq")?);
+    get(req, owner).await
+}
+
+pub(crate) async fn upload_custom(req: WebRequest) -> tide::Result {
+    let owner = Owner::Custom(param(&req, "cus_id")?);
+    upload(req, owner).await
+}
+
+pub(crate) async fn get_custom(req: WebRequest) -> tide::Result {
+    let owner = Owner::Custom(param(&req, "cus_id")?);
+    get(req, owner).await
+}
+
+async fn upload(mut req: WebRequest, owner: Owner) -> tide::Result {
+    let UploadFile {
+        data, content_type, ..
+    } = read_upload(&mut req, picture::MAX_SIZE).await?;
+    info!("{owner} 上传图片 {content_type:?} {} bytes", data.len());
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "保存图片").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        picture::save(&conn, owner, &data, content_type.as_deref())
+    })
+    .await;
+
+    reply(res)
+}
+
+async fn get(req: WebRequest, owner: Owner) -> tide::Result {
+    let picture_req: PictureReq = req.query()?;
+    info!("{owner} thumbnail: {}", picture_req.thumbnail);
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "读取图片").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        picture::load(&conn, owner, picture_req.thumbnail)
+    })
+    .await;
+
+    let Picture { data, mime } = match res {
+        Ok(picture) => picture,
+        Err(e) => return reply::<()>(Err(e)),
+    };
+
+    //图片内容不变时浏览器可以直接用缓存
+    let etag = format!("\"{}\"", &hex::encode(Sha256::digest(&data))[..32]);
+    let cache_control = "private, max-age=300";
+    if req
+        .header(IF_NONE_MATCH)
+        .is_some_and(|tags| tags.iter().any(|tag| tag.as_str() == etag))
+    {
+        return Ok(Response::builder(StatusCode::NotModified)
+            .header(ETAG, etag)
+            .header(CACHE_CONTROL, cache_control)
+            .build());
+    }
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header(CONTENT_TYPE, mime)
+        .header(ETAG, etag)
+        .header(CACHE_CONTROL, cache_control)
+        .body(data)
+        .build())
+}
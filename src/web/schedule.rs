@@ -0,0 +1,98 @@
+use tracing::{info, info_span, Span};
+
+use crate::activity;
+use crate::schedule::{self, ScheduleArgs};
+use crate::web::{param, reply, WebRequest};
+
+pub(crate) async fn list(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询定时抽奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        activity::get(&conn, act_id)?;
+        schedule::list(&conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn create(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let args = req.body_json::<ScheduleArgs>().await?;
+    info!("act_id: {act_id}, args: {args:?}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "新建定时抽奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        schedule::create(&conn, act_id, &args)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn get(req: WebRequest) -> tide::Result {
+    let sch_id: usize = param(&req, "sch_id")?;
+    info!("sch_id: {sch_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询定时抽奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        schedule::get(&conn, sch_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn update(mut req: WebRequest) -> tide::Result {
+    let sch_id: usize = param(&req, "sch_id")?;
+    let args = req.body_json::<ScheduleArgs>().await?;
+    info!("sch_id: {sch_id}, args: {args:?}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "修改定时抽奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        schedule::update(&mut conn, sch_id, &args)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn remove(req: WebRequest) -> tide::Result {
+    let sch_id: usize = param(&req, "sch_id")?;
+    info!("sch_id: {sch_id}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "删除定时抽奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        schedule::remove(&mut conn, sch_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn runs(req: WebRequest) -> tide::Result {
+    let sch_id: usize = param(&req, "sch_id")?;
+    info!("sch_id: {sch_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询执行记录").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        schedule::runs(&conn, sch_id)
+    })
+    .await;
+
+    reply(res)
+}
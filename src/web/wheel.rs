@@ -0,0 +1,74 @@
+use serde::Deserialize;
+use tracing::{info, info_span, Span};
+
+use crate::web::{param, reply, WebRequest};
+use crate::wheel::{self, SectorArgs};
+
+pub(crate) async fn sectors(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询转盘").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        wheel::sectors(&conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+/// 整体替换转盘的扇区
+pub(crate) async fn save_sectors(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let sectors = req.body_json::<Vec<SectorArgs>>().await?;
+    info!("act_id: {act_id}, sectors: {sectors:?}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "保存转盘").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        wheel::save_sectors(&mut conn, act_id, &sectors)
+    })
+    .await;
+
+    reply(res)
+}
+
+#[derive(Deserialize)]
+struct SpinReq {
+    cus_id: usize,
+}
+
+/// 客户转转盘，返回的 sector_idx 供客户端播放动画
+pub(crate) async fn spin(mut req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let SpinReq { cus_id } = req.body_json().await?;
+    info!("act_id: {act_id}, cus_id: {cus_id}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "转盘抽奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        wheel::spin(&mut conn, act_id, cus_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn spins(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    info!("act_id: {act_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询转盘记录").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        wheel::spins(&conn, act_id)
+    })
+    .await;
+
+    reply(res)
+}
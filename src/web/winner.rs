@@ -0,0 +1,247 @@
+use serde::Deserialize;
+use tide::http::headers::CONTENT_TYPE;
+use tide::{Response, StatusCode};
+use tracing::{info, info_span, Span};
+
+use crate::web::session::SessionExt;
+use crate::web::{param, reply, WebRequest};
+use crate::winner::claim::{self, ClaimStatus};
+use crate::winner::export::{self, Export, ExportFormat};
+use crate::winner::{self, replace};
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ExportReq {
+    act_seq: Option<usize>,
+    format: ExportFormat,
+}
+
+/// 导出中奖名单，`?format=csv|xlsx|pdf&act_seq=1`
+pub(crate) async fn export(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let ExportReq { act_seq, format } = req.query()?;
+    info!("act_id: {act_id}, act_seq: {act_seq:?}, format: {format:?}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "导出中奖名单").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        export::export(&conn, act_id, act_seq, format)
+    })
+    .await;
+
+    let Export {
+        file_name,
+        mime,
+        data,
+    } = match res {
+        Ok(export) => export,
+        Err(e) => return reply::<()>(Err(e)),
+    };
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header(CONTENT_TYPE, mime)
+        .header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{file_name}\""),
+        )
+        .body(data)
+        .build())
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ListReq {
+    act_seq: Option<usize>,
+}
+
+/// 中奖记录，包括作废和候补
+pub(crate) async fn list(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let ListReq { act_seq } = req.query()?;
+    info!("act_id: {act_id}, act_seq: {act_seq:?}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询中奖记录").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        winner::list(&conn, act_id, act_seq)
+    })
+    .await;
+
+    reply(res)
+}
+
+#[derive(Deserialize)]
+struct ForfeitReq {
+    reason: String,
+}
+
+pub(crate) async fn forfeit(mut req: WebRequest) -> tide::Result {
+    let userid: usize = match req.session().get("userid") {
+        Some(id) => id,
+        None => return Ok(Response::from(StatusCode::Unauthorized)),
+    };
+    let win_id: usize = param(&req, "win_id")?;
+    let ForfeitReq { reason } = req.body_json().await?;
+    info!("win_id: {win_id}, reason: {reason}, userid: {userid}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "作废中奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        replace::forfeit(&mut conn, win_id, &reason, userid)
+    })
+    .await;
+
+    reply(res)
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct PromoteReq {
+    /// 指定递补的候补 win_id，为空时按抽取顺序
+    alternate: Option<usize>,
+}
+
+pub(crate) async fn promote(req: WebRequest) -> tide::Result {
+    let win_id: usize = param(&req, "win_id")?;
+    let PromoteReq { alternate } = req.query()?;
+    info!("win_id: {win_id}, alternate: {alternate:?}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "候补递补").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        replace::promote(&mut conn, win_id, alternate)
+    })
+    .await;
+
+    reply(res)
+}
+
+pub(crate) async fn redraw(req: WebRequest) -> tide::Result {
+    let win_id: usize = param(&req, "win_id")?;
+    info!("win_id: {win_id}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "重抽").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        replace::redraw(&mut conn, win_id)
+    })
+    .await;
+
+    if let Ok(result) = &res {
+        req.state().live.publish_draw(result);
+    }
+    reply(res)
+}
+
+pub(crate) async fn claim(req: WebRequest) -> tide::Result {
+    let win_id: usize = param(&req, "win_id")?;
+    info!("win_id: {win_id}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询领奖信息").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        claim::get(&conn, win_id)
+    })
+    .await;
+
+    reply(res)
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ClaimsReq {
+    status: Option<ClaimStatus>,
+}
+
+/// 活动的领奖情况，`?status=notified` 按状态过滤
+pub(crate) async fn claims(req: WebRequest) -> tide::Result {
+    let act_id: usize = param(&req, "act_id")?;
+    let ClaimsReq { status } = req.query()?;
+    info!("act_id: {act_id}, status: {status:?}");
+
+    let conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "查询领奖情况").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        claim::list(&conn, act_id, status)
+    })
+    .await;
+
+    reply(res)
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct NotifyReq {
+    /// 新的领奖截止时间，为空时不变
+    deadline: Option<i64>,
+}
+
+pub(crate) async fn notify(mut req: WebRequest) -> tide::Result {
+    let win_id: usize = param(&req, "win_id")?;
+    let NotifyReq { deadline } = req.body_json().await?;
+    info!("win_id: {win_id}, deadline: {deadline:?}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "通知中奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        claim::notify(&mut conn, win_id, deadline)
+    })
+    .await;
+
+    reply(res)
+}
+
+#[derive(Deserialize)]
+struct RedeemReq {
+    code: String,
+}
+
+/// 核销兑奖码
+pub(crate) async fn redeem(mut req: WebRequest) -> tide::Result {
+    let userid: usize = match req.session().get("userid") {
+        Some(id) => id,
+        None => return Ok(Response::from(StatusCode::Unauthorized)),
+    };
+    let RedeemReq { code } = req.body_json().await?;
+    info!("userid: {userid}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "兑奖").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        claim::redeem(&mut conn, &code, userid)
+    })
+    .await;
+
+    reply(res)
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ShipReq {
+    note: Option<String>,
+}
+
+pub(crate) async fn ship(mut req: WebRequest) -> tide::Result {
+    let win_id: usize = param(&req, "win_id")?;
+    let ShipReq { note } = req.body_json().await?;
+    info!("win_id: {win_id}, note: {note:?}");
+
+    let mut conn = req.state().pool.get()?;
+    let span = info_span!(parent: Span::current(), "寄出奖品").or_current();
+    let res = async_global_executor::spawn_blocking(move || {
+        let _enter = span.enter();
+        claim::ship(&mut conn, win_id, note.as_deref())
+    })
+    .await;
+
+    reply(res)
+}
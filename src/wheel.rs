@@ -0,0 +1,293 @@
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{Connection, ErrorCode, OptionalExtension, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::activity;
+use crate::activity::state::{self, ActStatus};
+use crate::audit::AuditEvent;
+use crate::draw::weight::MAX_WEIGHT;
+use crate::draw::{self, count_winners, engine, load_plan, rng, DrawUnit};
+use crate::error::BizError;
+use crate::winner;
+
+/// 转盘的一个扇区，对应 ld_wheel_sector 的一行
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Sector {
+    /// 扇区下标，从 0 开始按顺时针排列，客户端按它转到对应位置
+    pub sector_idx: usize,
+    pub label: String,
+    /// 关联的奖项，为空表示“谢谢参与”
+    pub act_seq: Option<usize>,
+    /// 落在该扇区的概率为 weight / 总权重
+    pub weight: u64,
+    /// 奖项剩余的名额，即 prize_amount 减去有效中奖数，“谢谢参与”为空
+    pub stock: Option<usize>,
+}
+
+/// 保存扇区的参数，按数组顺序编号
+#[derive(Debug, Deserialize)]
+pub(crate) struct SectorArgs {
+    pub label: String,
+    #[serde(default)]
+    pub act_seq: Option<usize>,
+    pub weight: u64,
+}
+
+pub(crate) fn sectors(conn: &Connection, act_id: usize) -> Result<Vec<Sector>> {
+    activity::get(conn, act_id)?;
+    let mut stmt = conn.prepare(
+        "select sector_idx,label,act_seq,weight from ld_wheel_sector
+          where act_id=? order by sector_idx",
+    )?;
+    let mut rows = stmt.query([act_id])?;
+
+    let mut sectors = Vec::new();
+    while let Some(row) = rows.next()? {
+        let act_seq: Option<usize> = row.get(2)?;
+        let stock = match act_seq {
+            Some(act_seq) => Some(
+                load_plan(conn, act_id, act_seq)?
+                    .prize_amount
+                    .saturating_sub(count_winners(conn, act_id, act_seq)?),
+            ),
+            None => None,
+        };
+        sectors.push(Sector {
+            sector_idx: row.get(0)?,
+            label: row.get(1)?,
+            act_seq,
+            weight: row.get::<_, i64>(3)?.max(0) as u64,
+            stock,
+        });
+    }
+
+    Ok(sectors)
+}
+
+/// 整体替换转盘的扇区，开始抽奖后不能修改
+///
+/// 至少要有一个“谢谢参与”扇区，奖品抽完后落到该奖项的客户会改为第一个“谢谢参与”。
+pub(crate) fn save_sectors(
+    conn: &mut Connection,
+    act_id: usize,
+    args: &[SectorArgs],
+) -> Result<Vec<Sector>> {
+    if args.iter().any(|sector| sector.label.trim().is_empty()) {
+        return Err(BizError::Invalid("扇区名称不能为空".to_owned()).into());
+    }
+    //先限制每个扇区的权重，总和才不会溢出
+    if let Some(sector) = args.iter().find(|sector| sector.weight > MAX_WEIGHT) {
+        return Err(BizError::Invalid(format!(
+            "扇区{}的权重{}超过上限{MAX_WEIGHT}",
+            sector.label.trim(),
+            sector.weight
+        ))
+        .into());
+    }
+    if !args.is_empty() {
+        if args.iter().map(|sector| sector.weight).sum::<u64>() == 0 {
+            return Err(BizError::Invalid("扇区的总权重必须大于0".to_owned()).into());
+        }
+        if args.iter().all(|sector| sector.act_seq.is_some()) {
+            return Err(BizError::Invalid("至少需要一个“谢谢参与”扇区".to_owned()).into());
+        }
+    }
+
+    let tx = conn.transaction()?;
+    state::ensure_plan_editable(&tx, act_id)?;
+    for act_seq in args.iter().filter_map(|sector| sector.act_seq) {
+        if load_plan(&tx, act_id, act_seq)?.draw_unit == DrawUnit::Team {
+            return Err(BizError::Invalid(format!("奖项{act_seq}是团体奖项，不能用于转盘")).into());
+        }
+    }
+
+    tx.execute("delete from ld_wheel_sector where act_id=?", [act_id])?;
+    {
+        let mut stmt = tx.prepare(
+            "insert into ld_wheel_sector (act_id, sector_idx, label, act_seq, weight)
+             values (?, ?, ?, ?, ?)",
+        )?;
+        for (sector_idx, sector) in args.iter().enumerate() {
+            stmt.execute((
+                act_id,
+                sector_idx,
+                sector.label.trim(),
+                sector.act_seq,
+                sector.weight as i64,
+            ))?;
+        }
+    }
+    let sectors = self::sectors(&tx, act_id)?;
+    tx.commit()?;
+    info!("活动{act_id}保存{}个转盘扇区", sectors.len());
+
+    Ok(sectors)
+}
+
+/// 一次转盘抽奖的结果，对应 ld_wheel_spin 的一行
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Spin {
+    pub spin_id: usize,
+    pub act_id: usize,
+    pub cus_id: usize,
+    /// 本次使用的随机种子，转完即公开
+    pub seed: String,
+    /// 转动时各扇区的权重，按扇区下标排列
+    pub weights: Vec<u64>,
+    /// 随机数落到的扇区
+    pub landed: usize,
+    /// 最终结果，落到的奖品已抽完或客户不符合该奖项的条件时为“谢谢参与”
+    pub sector_idx: usize,
+    pub act_seq: Option<usize>,
+    pub win_id: Option<usize>,
+    /// 红包奖项的金额（分）
+    pub amount: Option<i64>,
+    pub spin_time: i64,
+}
+
+const SELECT_SPIN: &str =
+    "select s.spin_id,s.act_id,s.cus_id,s.seed,s.weights,s.landed,s.sector_idx,s.act_seq,s.win_id,
+            w.amount,s.spin_time
+       from ld_wheel_spin s
+       left join ld_win_list w on w.win_id=s.win_id";
+
+impl Spin {
+    fn from_row(row: &Row) -> Result<Self> {
+        let weights: String = row.get(4)?;
+        Ok(Spin {
+            spin_id: row.get(0)?,
+            act_id: row.get(1)?,
+            cus_id: row.get(2)?,
+            seed: row.get(3)?,
+            weights: serde_json::from_str(&weights)?,
+            landed: row.get(5)?,
+            sector_idx: row.get(6)?,
+            act_seq: row.get(7)?,
+            win_id: row.get(8)?,
+            amount: row.get(9)?,
+            spin_time: row.get(10)?,
+        })
+    }
+}
+
+pub(crate) fn get(conn: &Connection, spin_id: usize) -> Result<Spin> {
+    let sql = format!("{SELECT_SPIN} where s.spin_id=?");
+    let spin = conn
+        .query_row(&sql, [spin_id], |row| Ok(Spin::from_row(row)))
+        .optional()?;
+
+    match spin {
+        Some(spin) => spin,
+        None => Err(BizError::NotFound(format!("转盘记录{spin_id}不存在")).into()),
+    }
+}
+
+/// 活动的转盘记录
+pub(crate) fn spins(conn: &Connection, act_id: usize) -> Result<Vec<Spin>> {
+    activity::get(conn, act_id)?;
+    let sql = format!("{SELECT_SPIN} where s.act_id=? order by s.spin_id");
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([act_id])?;
+
+    let mut spins = Vec::new();
+    while let Some(row) = rows.next()? {
+        spins.push(Spin::from_row(row)?);
+    }
+
+    Ok(spins)
+}
+
+/// 客户转一次转盘，每个客户每个活动只能转一次
+///
+/// 结果由服务端用新的随机种子按扇区权重决定，与按权重抽奖使用同一个算法，
+/// 可以用 `luckydraw replay sha256-ctr-weighted-v1 <seed> 1 <扇区下标> <权重>` 复算。
+/// 落到奖品扇区时在同一个写事务中检查库存并写入中奖记录，库存不会超卖。
+pub(crate) fn spin(conn: &mut Connection, act_id: usize, cus_id: usize) -> Result<Spin> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    state::ensure(&tx, act_id, &[ActStatus::Drawing], "转盘抽奖")?;
+    let sectors = sectors(&tx, act_id)?;
+    if sectors.is_empty() {
+        return Err(BizError::Conflict(format!("活动{act_id}没有配置转盘")).into());
+    }
+    let exists: bool = tx.query_row(
+        "select exists(select 1 from ld_custom where cus_id=?)",
+        [cus_id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(BizError::NotFound(format!("客户{cus_id}不存在")).into());
+    }
+    let thanks = sectors
+        .iter()
+        .find(|sector| sector.act_seq.is_none())
+        .ok_or_else(|| BizError::Conflict(format!("活动{act_id}的转盘没有“谢谢参与”扇区")))?;
+
+    let seed = rng::new_seed()?;
+    let indexes = sectors.iter().map(|s| s.sector_idx).collect::<Vec<_>>();
+    let weights = sectors.iter().map(|s| s.weight).collect::<Vec<_>>();
+    let landed = engine::select(engine::WEIGHTED, &seed, &indexes, Some(&weights), 1)?
+        .first()
+        .copied()
+        .ok_or_else(|| BizError::Conflict(format!("活动{act_id}的转盘总权重为0")))?;
+
+    let mut sector = &sectors[landed];
+    if let Some(act_seq) = sector.act_seq {
+        let eligible = draw::eligibility(&tx, act_id, act_seq)?
+            .candidates
+            .iter()
+            .any(|c| c.cus_id == cus_id);
+        if sector.stock == Some(0) || !eligible {
+            info!(
+                "客户{cus_id}落到扇区{landed}，奖项{act_id}-{act_seq}剩余{:?}，符合条件: {eligible}",
+                sector.stock
+            );
+            sector = thanks;
+        }
+    }
+
+    let spin_time = time::OffsetDateTime::now_utc().unix_timestamp();
+    let win_id = match sector.act_seq {
+        Some(act_seq) => Some(winner::award(
+            &tx,
+            AuditEvent::Spin,
+            act_id,
+            act_seq,
+            cus_id,
+            &seed,
+            spin_time,
+        )?),
+        None => None,
+    };
+
+    let res = tx.execute(
+        "insert into ld_wheel_spin (act_id, cus_id, seed, weights, landed, sector_idx, act_seq,
+                                    win_id, spin_time)
+         values (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        (
+            act_id,
+            cus_id,
+            hex::encode(seed),
+            serde_json::to_string(&weights)?,
+            landed,
+            sector.sector_idx,
+            sector.act_seq,
+            win_id,
+            spin_time,
+        ),
+    );
+    match res {
+        Err(e) if e.sqlite_error_code() == Some(ErrorCode::ConstraintViolation) => {
+            return Err(BizError::Conflict(format!("客户{cus_id}已经转过转盘")).into());
+        }
+        res => res?,
+    };
+    let spin = get(&tx, tx.last_insert_rowid() as usize)?;
+    tx.commit()?;
+    info!(
+        "活动{act_id} 客户{cus_id}转盘结果: 扇区{} {:?}",
+        spin.sector_idx, spin.act_seq
+    );
+
+    Ok(spin)
+}
@@ -0,0 +1,348 @@
+use anyhow::{anyhow, Result};
+use arc_swap::access::Access;
+use r2d2_sqlite::rusqlite::{Connection, ErrorCode, OptionalExtension, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+
+use crate::activity;
+use crate::audit::{self, AuditEvent};
+use crate::config::{Config, GLOBAL_CONFIG};
+use crate::error::BizError;
+use crate::winner::WinStatus;
+
+/// 兑奖码字符集，去掉了容易混淆的 I、L、O、U
+const CODE_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// 兑奖码长度，12 位共 60 比特
+const CODE_LEN: usize = 12;
+
+/// ld_claim.claim_status 的取值
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ClaimStatus {
+    /// 0: 待通知
+    Pending = 0,
+    /// 1: 已通知中奖者
+    Notified = 1,
+    /// 2: 已凭兑奖码领奖
+    Claimed = 2,
+    /// 3: 奖品已寄出
+    Shipped = 3,
+    /// 4: 逾期未领奖
+    Expired = 4,
+}
+
+impl TryFrom<i64> for ClaimStatus {
+    type Error = BizError;
+
+    fn try_from(value: i64) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ClaimStatus::Pending),
+            1 => Ok(ClaimStatus::Notified),
+            2 => Ok(ClaimStatus::Claimed),
+            3 => Ok(ClaimStatus::Shipped),
+            4 => Ok(ClaimStatus::Expired),
+            _ => Err(BizError::Invalid(format!("未知的领奖状态: {value}"))),
+        }
+    }
+}
+
+/// 领奖信息，对应 ld_claim 的一行，关联了中奖记录
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Claim {
+    pub win_id: usize,
+    pub act_id: usize,
+    pub act_seq: usize,
+    pub act_prize: Option<String>,
+    pub cus_id: usize,
+    pub cus_nickname: String,
+    pub win_status: WinStatus,
+    pub redeem_code: String,
+    pub claim_status: ClaimStatus,
+    /// 领奖截止时间
+    pub deadline: i64,
+    pub notify_time: Option<i64>,
+    pub claim_time: Option<i64>,
+    /// 核销兑奖码的工作人员
+    pub claim_user: Option<usize>,
+    pub ship_time: Option<i64>,
+    /// 快递单号等寄送信息
+    pub ship_note: Option<String>,
+}
+
+const SELECT_CLAIM: &str = "select l.win_id,w.act_id,w.act_seq,p.act_prize,w.cus_id,c.cus_nickname,
+                                   w.win_status,l.redeem_code,l.claim_status,l.deadline,
+                                   l.notify_time,l.claim_time,l.claim_user,l.ship_time,l.ship_note
+                              from ld_claim l
+                              join ld_win_list w on w.win_id=l.win_id
+                              join ld_custom c on c.cus_id=w.cus_id
+                              left join ld_plan p on p.act_id=w.act_id and p.act_seq=w.act_seq";
+
+impl Claim {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Claim {
+            win_id: row.get(0)?,
+            act_id: row.get(1)?,
+            act_seq: row.get(2)?,
+            act_prize: row.get(3)?,
+            cus_id: row.get(4)?,
+            cus_nickname: row.get(5)?,
+            win_status: WinStatus::try_from(row.get::<_, i64>(6)?)?,
+            redeem_code: row.get(7)?,
+            claim_status: ClaimStatus::try_from(row.get::<_, i64>(8)?)?,
+            deadline: row.get(9)?,
+            notify_time: row.get(10)?,
+            claim_time: row.get(11)?,
+            claim_user: row.get(12)?,
+            ship_time: row.get(13)?,
+            ship_note: row.get(14)?,
+        })
+    }
+}
+
+fn new_code() -> Result<String> {
+    let mut bytes = [0u8; CODE_LEN];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow!("生成兑奖码失败: {e}"))?;
+    Ok(bytes
+        .iter()
+        .map(|b| CODE_ALPHABET[(b & 0x1f) as usize] as char)
+        .collect())
+}
+
+/// 用户输入的兑奖码忽略大小写、空格和连字符
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// 为新的中奖记录生成兑奖码，在调用方的事务中执行
+pub(crate) fn issue(tx: &Connection, win_id: usize, win_time: i64) -> Result<()> {
+    let deadline_days = *GLOBAL_CONFIG
+        .map(|cfg: &Config| &cfg.claim.deadline_days)
+        .load();
+    let deadline = win_time + deadline_days * 86400;
+
+    //碰撞的概率极低，重试几次即可
+    for _ in 0..5 {
+        let res = tx.execute(
+            "insert into ld_claim (win_id, redeem_code, claim_status, deadline) values (?, ?, ?, ?)",
+            (win_id, new_code()?, ClaimStatus::Pending as i64, deadline),
+        );
+        match res {
+            Err(e) if e.sqlite_error_code() == Some(ErrorCode::ConstraintViolation) => continue,
+            res => {
+                res?;
+                return Ok(());
+            }
+        }
+    }
+    Err(anyhow!("中奖记录{win_id}生成兑奖码失败"))
+}
+
+pub(crate) fn get(conn: &Connection, win_id: usize) -> Result<Claim> {
+    let sql = format!("{SELECT_CLAIM} where l.win_id=?");
+    let claim = conn
+        .query_row(&sql, [win_id], |row| Ok(Claim::from_row(row)))
+        .optional()?;
+
+    match claim {
+        Some(claim) => claim,
+        None => Err(BizError::NotFound(format!("中奖记录{win_id}没有领奖信息")).into()),
+    }
+}
+
+/// 活动的领奖信息，可以按领奖状态过滤
+pub(crate) fn list(
+    conn: &Connection,
+    act_id: usize,
+    status: Option<ClaimStatus>,
+) -> Result<Vec<Claim>> {
+    activity::get(conn, act_id)?;
+    let sql = format!(
+        "{SELECT_CLAIM} where w.act_id=?1 and (?2 is null or l.claim_status=?2)
+          order by w.act_seq,l.win_id"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query((act_id, status.map(|status| status as i64)))?;
+
+    let mut claims = Vec::new();
+    while let Some(row) = rows.next()? {
+        claims.push(Claim::from_row(row)?);
+    }
+
+    Ok(claims)
+}
+
+/// 检查中奖记录有效，且当前领奖状态允许该操作
+fn ensure(claim: &Claim, allowed: &[ClaimStatus], action: &str) -> Result<()> {
+    if claim.win_status != WinStatus::Won {
+        return Err(BizError::Conflict(format!("中奖记录{}已经无效", claim.win_id)).into());
+    }
+    if !allowed.contains(&claim.claim_status) {
+        return Err(BizError::Conflict(format!(
+            "中奖记录{}当前的领奖状态不能{action}",
+            claim.win_id
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// 标记已通知中奖者，可以同时调整领奖截止时间
+pub(crate) fn notify(conn: &mut Connection, win_id: usize, deadline: Option<i64>) -> Result<Claim> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let claim = get(&tx, win_id)?;
+    ensure(
+        &claim,
+        &[ClaimStatus::Pending, ClaimStatus::Notified],
+        "通知",
+    )?;
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    let deadline = deadline.unwrap_or(claim.deadline);
+    tx.execute(
+        "update ld_claim set claim_status=?, notify_time=?, deadline=? where win_id=?",
+        (ClaimStatus::Notified as i64, now, deadline, win_id),
+    )?;
+    audit::append(
+        &tx,
+        AuditEvent::ClaimNotify,
+        claim.act_id,
+        &[win_id],
+        json!({ "deadline": deadline }),
+    )?;
+    let claim = get(&tx, win_id)?;
+    tx.commit()?;
+    info!("中奖记录{win_id}已通知");
+
+    Ok(claim)
+}
+
+/// 工作人员扫码或输入兑奖码核销
+pub(crate) fn redeem(conn: &mut Connection, code: &str, user_id: usize) -> Result<Claim> {
+    let code = normalize_code(code);
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let sql = format!("{SELECT_CLAIM} where l.redeem_code=?");
+    let claim = tx
+        .query_row(&sql, [&code], |row| Ok(Claim::from_row(row)))
+        .optional()?
+        .transpose()?
+        .ok_or_else(|| BizError::NotFound("兑奖码不存在".to_owned()))?;
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    let unclaimed = matches!(
+        claim.claim_status,
+        ClaimStatus::Pending | ClaimStatus::Notified
+    );
+    if claim.claim_status == ClaimStatus::Expired || (unclaimed && claim.deadline < now) {
+        return Err(BizError::Conflict(format!("中奖记录{}已过领奖期限", claim.win_id)).into());
+    }
+    ensure(
+        &claim,
+        &[ClaimStatus::Pending, ClaimStatus::Notified],
+        "兑奖",
+    )?;
+
+    tx.execute(
+        "update ld_claim set claim_status=?, claim_time=?, claim_user=? where win_id=?",
+        (ClaimStatus::Claimed as i64, now, user_id, claim.win_id),
+    )?;
+    audit::append(
+        &tx,
+        AuditEvent::ClaimRedeem,
+        claim.act_id,
+        &[claim.win_id],
+        json!({ "user_id": user_id }),
+    )?;
+    let claim = get(&tx, claim.win_id)?;
+    tx.commit()?;
+    info!(
+        "中奖记录{} 客户{} 兑奖，核销人{user_id}",
+        claim.win_id, claim.cus_id
+    );
+
+    Ok(claim)
+}
+
+/// 登记奖品已寄出
+pub(crate) fn ship(conn: &mut Connection, win_id: usize, note: Option<&str>) -> Result<Claim> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let claim = get(&tx, win_id)?;
+    ensure(&claim, &[ClaimStatus::Claimed], "寄出")?;
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    tx.execute(
+        "update ld_claim set claim_status=?, ship_time=?, ship_note=? where win_id=?",
+        (ClaimStatus::Shipped as i64, now, note, win_id),
+    )?;
+    audit::append(
+        &tx,
+        AuditEvent::ClaimShip,
+        claim.act_id,
+        &[win_id],
+        json!({ "note": note }),
+    )?;
+    let claim = get(&tx, win_id)?;
+    tx.commit()?;
+    info!("中奖记录{win_id}奖品已寄出");
+
+    Ok(claim)
+}
+
+/// 把逾期未领奖的记录标记为过期，按配置同时作废中奖记录，返回过期的条数
+///
+/// 作废后的名额可以通过递补或重抽重新分配。
+pub(crate) fn expire(conn: &mut Connection, now: i64) -> Result<usize> {
+    let forfeit_expired = *GLOBAL_CONFIG
+        .map(|cfg: &Config| &cfg.claim.forfeit_expired)
+        .load();
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let expired = {
+        let mut stmt = tx.prepare(
+            "select l.win_id,w.act_id from ld_claim l join ld_win_list w on w.win_id=l.win_id
+              where l.claim_status in (?1, ?2) and l.deadline<?3 and w.win_status=?4",
+        )?;
+        let ids = stmt
+            .query_map(
+                (
+                    ClaimStatus::Pending as i64,
+                    ClaimStatus::Notified as i64,
+                    now,
+                    WinStatus::Won as i64,
+                ),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?
+            .collect::<Result<Vec<(usize, usize)>, _>>()?;
+        ids
+    };
+
+    for (win_id, act_id) in &expired {
+        tx.execute(
+            "update ld_claim set claim_status=? where win_id=?",
+            (ClaimStatus::Expired as i64, win_id),
+        )?;
+        if forfeit_expired {
+            tx.execute(
+                "update ld_win_list set win_status=?, forfeit_reason='逾期未领奖', forfeit_time=?
+                  where win_id=?",
+                (WinStatus::Forfeited as i64, now, win_id),
+            )?;
+        }
+        audit::append(
+            &tx,
+            AuditEvent::ClaimExpire,
+            *act_id,
+            &[*win_id],
+            json!({ "forfeited": forfeit_expired }),
+        )?;
+    }
+    tx.commit()?;
+    if !expired.is_empty() {
+        let win_ids = expired.iter().map(|(win_id, _)| win_id).collect::<Vec<_>>();
+        info!("{}条中奖记录逾期未领奖: {win_ids:?}", expired.len());
+    }
+
+    Ok(expired.len())
+}
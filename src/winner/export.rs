@@ -0,0 +1,525 @@
+use std::fs::File;
+
+use anyhow::{Context, Result};
+use arc_swap::access::Access;
+use printpdf::{
+    IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Point,
+};
+use r2d2_sqlite::rusqlite::Connection;
+use rust_xlsxwriter::{Format, Workbook};
+use serde::Deserialize;
+use time::macros::format_description;
+use time::{OffsetDateTime, UtcOffset};
+use tracing::info;
+
+use crate::activity::{self, Activity};
+use crate::config::{Config, GLOBAL_CONFIG};
+use crate::draw::quota::{self, QuotaOutcome};
+use crate::draw::seed::{self, DrawRecord};
+use crate::partition::{self, Roster};
+use crate::winner::{self, WinStatus, Winner};
+
+/// 导出格式
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ExportFormat {
+    #[default]
+    Csv,
+    Xlsx,
+    Pdf,
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+            ExportFormat::Pdf => "pdf",
+        }
+    }
+
+    fn mime(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+            ExportFormat::Pdf => "application/pdf",
+        }
+    }
+}
+
+/// 导出的文件
+pub(crate) struct Export {
+    pub file_name: String,
+    pub mime: &'static str,
+    pub data: Vec<u8>,
+}
+
+/// 导出文件的表头信息和已脱敏的中奖名单
+struct Report {
+    activity: Activity,
+    /// 已开奖的批次
+    draws: Vec<DrawRecord>,
+    /// 配置了配额的奖项及各分组的执行情况
+    quotas: Vec<(usize, Vec<QuotaOutcome>)>,
+    winners: Vec<Winner>,
+    export_time: String,
+}
+
+const HEADERS: [&str; 12] = [
+    "奖项序号",
+    "奖项",
+    "昵称",
+    "姓名",
+    "手机号",
+    "证件号",
+    "标签",
+    "中奖时间",
+    "抽奖批次",
+    "种子哈希",
+    "红包金额",
+    "团体",
+];
+
+impl Report {
+    fn title(&self) -> String {
+        let act_name = self.activity.act_name.as_deref().unwrap_or_default();
+        format!("{act_name}中奖名单")
+    }
+
+    /// 每个分组一行配额执行情况
+    fn quota_lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.quotas.iter().flat_map(|(act_seq, outcomes)| {
+            outcomes.iter().map(move |outcome| {
+                let limit = match outcome.limit {
+                    Some(limit) => format!("{limit}人"),
+                    None => "不限".to_owned(),
+                };
+                format!(
+                    "奖项{act_seq} 标签{}: 中奖{}人 至少{}人 至多{limit} {}",
+                    outcome.cus_flag,
+                    outcome.won,
+                    outcome.min_count,
+                    if outcome.satisfied {
+                        "满足配额"
+                    } else {
+                        "未满足配额"
+                    }
+                )
+            })
+        })
+    }
+
+    fn rows(&self) -> impl Iterator<Item = [String; 12]> + '_ {
+        self.winners.iter().map(|w| {
+            [
+                w.act_seq.to_string(),
+                w.act_prize.clone().unwrap_or_default(),
+                w.cus_nickname.clone(),
+                w.cus_name.clone().unwrap_or_default(),
+                w.cus_phone.map(mask_phone).unwrap_or_default(),
+                w.cus_identity
+                    .as_deref()
+                    .map(mask_identity)
+                    .unwrap_or_default(),
+                w.cus_flag.clone().unwrap_or_default(),
+                w.win_time.map(format_time).unwrap_or_default(),
+                w.draw_id.map(|id| id.to_string()).unwrap_or_default(),
+                w.seed_hash.clone().unwrap_or_default(),
+                w.amount.map(format_amount).unwrap_or_default(),
+                w.team_name.clone().unwrap_or_default(),
+            ]
+        })
+    }
+}
+
+/// 导出有效的中奖名单，不包括作废和候补，手机号和证件号会脱敏
+pub(crate) fn export(
+    conn: &Connection,
+    act_id: usize,
+    act_seq: Option<usize>,
+    format: ExportFormat,
+) -> Result<Export> {
+    let activity = activity::get(conn, act_id)?;
+    let winners = winner::list(conn, act_id, act_seq)?
+        .into_iter()
+        .filter(|w| w.win_status == WinStatus::Won)
+        .collect();
+    let draws = seed::list(conn, act_id)?
+        .into_iter()
+        .filter(|d| d.draw_time.is_some() && act_seq.is_none_or(|seq| seq == d.act_seq))
+        .collect();
+    let quotas = {
+        let mut stmt = conn.prepare(
+            "select distinct act_seq from ld_plan_quota
+              where act_id=?1 and (?2 is null or act_seq=?2) order by act_seq",
+        )?;
+        let seqs = stmt
+            .query_map((act_id, act_seq), |row| row.get(0))?
+            .collect::<Result<Vec<usize>, _>>()?;
+        let mut quotas = Vec::with_capacity(seqs.len());
+        for seq in seqs {
+            quotas.push((seq, quota::outcome(conn, act_id, seq)?));
+        }
+        quotas
+    };
+    let report = Report {
+        activity,
+        draws,
+        quotas,
+        winners,
+        export_time: format_time(OffsetDateTime::now_utc().unix_timestamp()),
+    };
+    info!(
+        "导出活动{act_id}的中奖名单 {format:?} 共{}条",
+        report.winners.len()
+    );
+
+    let data = match format {
+        ExportFormat::Csv => to_csv(&report)?,
+        ExportFormat::Xlsx => to_xlsx(&report)?,
+        ExportFormat::Pdf => to_pdf(&report)?,
+    };
+    let file_name = match act_seq {
+        Some(act_seq) => format!("winners-{act_id}-{act_seq}.{}", format.extension()),
+        None => format!("winners-{act_id}.{}", format.extension()),
+    };
+
+    Ok(Export {
+        file_name,
+        mime: format.mime(),
+        data,
+    })
+}
+
+const ROSTER_HEADERS: [&str; 5] = ["组序号", "组", "昵称", "姓名", "标签"];
+
+fn roster_lines(roster: &Roster, export_time: &str) -> Vec<String> {
+    let partition = &roster.partition;
+    let mut lines = vec![format!("导出时间: {export_time}")];
+    match (partition.run_time, &partition.seed) {
+        (Some(run_time), Some(seed)) => lines.push(format!(
+            "分组时间: {} 算法: {} 种子: {seed}",
+            format_time(run_time),
+            partition.algorithm.as_deref().unwrap_or_default()
+        )),
+        _ => lines.push("尚未分组".to_owned()),
+    }
+    lines
+}
+
+fn roster_rows(roster: &Roster) -> impl Iterator<Item = [String; 5]> + '_ {
+    roster.buckets.iter().flat_map(|bucket| {
+        bucket.members.iter().map(move |seat| {
+            [
+                (bucket.bucket.bucket_idx + 1).to_string(),
+                bucket.bucket.bucket_name.clone(),
+                seat.cus_nickname.clone(),
+                seat.cus_name.clone().unwrap_or_default(),
+                seat.cus_flag.clone().unwrap_or_default(),
+            ]
+        })
+    })
+}
+
+/// 导出分组名单，按组排列，同组按 cus_id 排列
+pub(crate) fn roster(
+    conn: &Connection,
+    act_id: usize,
+    part_id: usize,
+    format: ExportFormat,
+) -> Result<Export> {
+    let activity = activity::get(conn, act_id)?;
+    let roster = partition::roster(conn, act_id, part_id)?;
+    let title = format!(
+        "{}{}分组名单",
+        activity.act_name.as_deref().unwrap_or_default(),
+        roster.partition.part_name
+    );
+    let lines = roster_lines(
+        &roster,
+        &format_time(OffsetDateTime::now_utc().unix_timestamp()),
+    );
+    info!(
+        "导出活动{act_id}的分组{part_id} {format:?} 共{}组",
+        roster.buckets.len()
+    );
+
+    let data = match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer("\u{feff}".as_bytes().to_vec());
+            writer.write_record(ROSTER_HEADERS)?;
+            for row in roster_rows(&roster) {
+                writer.write_record(row.map(escape_cell))?;
+            }
+            writer.into_inner()?
+        }
+        ExportFormat::Xlsx => {
+            let bold = Format::new().set_bold();
+            let mut workbook = Workbook::new();
+            let sheet = workbook.add_worksheet();
+            sheet.set_name("分组名单")?;
+
+            sheet.write_string_with_format(0, 0, &title, &bold)?;
+            let mut row = 0;
+            for line in lines {
+                row += 1;
+                sheet.write_string(row, 0, line)?;
+            }
+            row += 2;
+            for (col, header) in ROSTER_HEADERS.iter().enumerate() {
+                sheet.write_string_with_format(row, col as u16, *header, &bold)?;
+            }
+            sheet.set_freeze_panes(row + 1, 0)?;
+            for values in roster_rows(&roster) {
+                row += 1;
+                for (col, value) in values.into_iter().enumerate() {
+                    sheet.write_string(row, col as u16, escape_cell(value))?;
+                }
+            }
+            for (col, width) in [8, 16, 16, 10, 16].into_iter().enumerate() {
+                sheet.set_column_width(col as u16, width)?;
+            }
+            workbook.save_to_buffer()?
+        }
+        ExportFormat::Pdf => roster_pdf(&roster, &title, &lines)?,
+    };
+
+    Ok(Export {
+        file_name: format!("roster-{act_id}-{part_id}.{}", format.extension()),
+        mime: format.mime(),
+        data,
+    })
+}
+
+/// 以 = + - @ 或制表符、回车开头的单元格会被表格软件当作公式执行，前面加 ' 作为文本
+fn escape_cell(value: String) -> String {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{value}")
+    } else {
+        value
+    }
+}
+
+/// 保留前 `head` 位和后 `tail` 位，其余用 * 代替
+fn mask(value: &str, head: usize, tail: usize) -> String {
+    let chars = value.chars().collect::<Vec<_>>();
+    if chars.len() <= head + tail {
+        return "*".repeat(chars.len());
+    }
+    chars[..head]
+        .iter()
+        .chain(std::iter::repeat_n(&'*', chars.len() - head - tail))
+        .chain(&chars[chars.len() - tail..])
+        .collect()
+}
+
+fn mask_phone(phone: i64) -> String {
+    mask(&phone.to_string(), 3, 4)
+}
+
+fn mask_identity(identity: &str) -> String {
+    mask(identity, 6, 4)
+}
+
+/// 金额从分转换为元
+fn format_amount(amount: i64) -> String {
+    format!("{}.{:02}", amount / 100, amount % 100)
+}
+
+/// 与日志一致，按东八区显示
+fn format_time(timestamp: i64) -> String {
+    let format = format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+    OffsetDateTime::from_unix_timestamp(timestamp)
+        .ok()
+        .and_then(|t| {
+            t.to_offset(UtcOffset::from_hms(8, 0, 0).ok()?)
+                .format(format)
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+fn to_csv(report: &Report) -> Result<Vec<u8>> {
+    //带 BOM，Excel 才能正确识别 utf-8
+    let mut writer = csv::Writer::from_writer("\u{feff}".as_bytes().to_vec());
+    writer.write_record(HEADERS)?;
+    for row in report.rows() {
+        writer.write_record(row.map(escape_cell))?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+fn to_xlsx(report: &Report) -> Result<Vec<u8>> {
+    let bold = Format::new().set_bold();
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("中奖名单")?;
+
+    sheet.write_string_with_format(0, 0, report.title(), &bold)?;
+    sheet.write_string(1, 0, format!("导出时间: {}", report.export_time))?;
+    let mut row = 2;
+    for draw in &report.draws {
+        sheet.write_string(row, 0, draw_line(draw))?;
+        row += 1;
+    }
+    for line in report.quota_lines() {
+        sheet.write_string(row, 0, line)?;
+        row += 1;
+    }
+
+    row += 1;
+    for (col, header) in HEADERS.iter().enumerate() {
+        sheet.write_string_with_format(row, col as u16, *header, &bold)?;
+    }
+    sheet.set_freeze_panes(row + 1, 0)?;
+    for values in report.rows() {
+        row += 1;
+        for (col, value) in values.into_iter().enumerate() {
+            sheet.write_string(row, col as u16, escape_cell(value))?;
+        }
+    }
+    for (col, width) in [8, 16, 16, 10, 14, 22, 16, 20, 8, 66]
+        .into_iter()
+        .enumerate()
+    {
+        sheet.set_column_width(col as u16, width)?;
+    }
+
+    Ok(workbook.save_to_buffer()?)
+}
+
+fn draw_line(draw: &DrawRecord) -> String {
+    format!(
+        "抽奖批次{} 奖项{} 开奖时间: {} 种子哈希: {}",
+        draw.draw_id,
+        draw.act_seq,
+        draw.draw_time.map(format_time).unwrap_or_default(),
+        draw.seed_hash
+    )
+}
+
+/// A4 横向
+const PAGE_WIDTH: f32 = 297.0;
+const PAGE_HEIGHT: f32 = 210.0;
+const MARGIN: f32 = 15.0;
+const ROW_HEIGHT: f32 = 7.0;
+/// pdf 中输出的列在 HEADERS 中的下标，种子哈希太长，只在表头的批次信息中列出
+const PDF_FIELDS: [usize; 11] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 10, 11];
+/// pdf 中各列的起始位置
+const PDF_COLUMNS: [f32; 11] = [
+    0.0, 14.0, 40.0, 66.0, 88.0, 112.0, 148.0, 172.0, 208.0, 222.0, 240.0,
+];
+
+/// 取出 pdf 中输出的列
+fn pdf_fields<S: AsRef<str>>(cells: &[S]) -> [&str; 11] {
+    PDF_FIELDS.map(|index| cells[index].as_ref())
+}
+
+fn to_pdf(report: &Report) -> Result<Vec<u8>> {
+    let font_path = GLOBAL_CONFIG.map(|cfg: &Config| &cfg.export.font).load();
+    let font_file =
+        File::open(&*font_path).with_context(|| format!("打开字体文件{}失败", *font_path))?;
+
+    let title = report.title();
+    let (doc, page, layer) = PdfDocument::new(&title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "中奖名单");
+    let font = doc.add_external_font(font_file)?;
+    let mut layer = doc.get_page(page).get_layer(layer);
+
+    let mut y = PAGE_HEIGHT - MARGIN;
+    layer.use_text(&title, 16.0, Mm(MARGIN), Mm(y), &font);
+    y -= ROW_HEIGHT + 2.0;
+    layer.use_text(
+        format!("导出时间: {}", report.export_time),
+        10.0,
+        Mm(MARGIN),
+        Mm(y),
+        &font,
+    );
+    //批次和配额较多时表头也会跨页
+    for line in report
+        .draws
+        .iter()
+        .map(draw_line)
+        .chain(report.quota_lines())
+    {
+        advance(&doc, &mut layer, &mut y, ROW_HEIGHT - 1.0, "中奖名单");
+        layer.use_text(line, 9.0, Mm(MARGIN), Mm(y), &font);
+    }
+    advance(&doc, &mut layer, &mut y, ROW_HEIGHT + 2.0, "中奖名单");
+    pdf_row(&layer, &font, y, &pdf_fields(&HEADERS));
+
+    for row in report.rows() {
+        if advance(&doc, &mut layer, &mut y, ROW_HEIGHT, "中奖名单") {
+            pdf_row(&layer, &font, y, &pdf_fields(&HEADERS));
+            y -= ROW_HEIGHT;
+        }
+        pdf_row(&layer, &font, y, &pdf_fields(&row));
+    }
+
+    Ok(doc.save_to_bytes()?)
+}
+
+fn roster_pdf(roster: &Roster, title: &str, lines: &[String]) -> Result<Vec<u8>> {
+    let font_path = GLOBAL_CONFIG.map(|cfg: &Config| &cfg.export.font).load();
+    let font_file =
+        File::open(&*font_path).with_context(|| format!("打开字体文件{}失败", *font_path))?;
+
+    let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "分组名单");
+    let font = doc.add_external_font(font_file)?;
+    let mut layer = doc.get_page(page).get_layer(layer);
+
+    let mut y = PAGE_HEIGHT - MARGIN;
+    layer.use_text(title, 16.0, Mm(MARGIN), Mm(y), &font);
+    y -= 2.0;
+    for line in lines {
+        advance(&doc, &mut layer, &mut y, ROW_HEIGHT - 1.0, "分组名单");
+        layer.use_text(line, 9.0, Mm(MARGIN), Mm(y), &font);
+    }
+    advance(&doc, &mut layer, &mut y, ROW_HEIGHT + 2.0, "分组名单");
+    pdf_row(&layer, &font, y, &ROSTER_HEADERS);
+
+    for row in roster_rows(roster) {
+        if advance(&doc, &mut layer, &mut y, ROW_HEIGHT, "分组名单") {
+            pdf_row(&layer, &font, y, &ROSTER_HEADERS);
+            y -= ROW_HEIGHT;
+        }
+        pdf_row(&layer, &font, y, &row);
+    }
+
+    Ok(doc.save_to_bytes()?)
+}
+
+/// 下移 `step` 毫米，低于页边距时换到新一页的顶部，换页时返回 true
+fn advance(
+    doc: &PdfDocumentReference,
+    layer: &mut PdfLayerReference,
+    y: &mut f32,
+    step: f32,
+    name: &str,
+) -> bool {
+    *y -= step;
+    if *y >= MARGIN {
+        return false;
+    }
+    let (page, index) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), name);
+    *layer = doc.get_page(page).get_layer(index);
+    *y = PAGE_HEIGHT - MARGIN;
+    true
+}
+
+/// 输出一行文字，并在下方画分隔线
+fn pdf_row<S: AsRef<str>>(layer: &PdfLayerReference, font: &IndirectFontRef, y: f32, cells: &[S]) {
+    for (x, cell) in PDF_COLUMNS.iter().zip(cells) {
+        layer.use_text(cell.as_ref(), 9.0, Mm(MARGIN + x), Mm(y), font);
+    }
+    let line_y = Mm(y - 2.0);
+    layer.add_line(Line {
+        points: vec![
+            (Point::new(Mm(MARGIN), line_y), false),
+            (Point::new(Mm(PAGE_WIDTH - MARGIN), line_y), false),
+        ],
+        is_closed: false,
+    });
+}
@@ -0,0 +1,196 @@
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{Connection, OptionalExtension, Row};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::activity;
+use crate::audit::{self, AuditEvent};
+use crate::draw::engine::{self, Split};
+use crate::draw::{self, count_winners, load_plan};
+use crate::error::BizError;
+
+pub(crate) mod claim;
+pub(crate) mod export;
+pub(crate) mod replace;
+
+/// ld_win_list.win_status 的取值
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum WinStatus {
+    /// 0: 中奖
+    Won = 0,
+    /// 1: 已作废，如未到场或不符合条件
+    Forfeited = 1,
+    /// 2: 候补，作废后可以递补
+    Alternate = 2,
+}
+
+impl TryFrom<i64> for WinStatus {
+    type Error = BizError;
+
+    fn try_from(value: i64) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(WinStatus::Won),
+            1 => Ok(WinStatus::Forfeited),
+            2 => Ok(WinStatus::Alternate),
+            _ => Err(BizError::Invalid(format!("未知的中奖状态: {value}"))),
+        }
+    }
+}
+
+/// 中奖记录，关联了客户、奖项和抽奖批次
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Winner {
+    pub win_id: usize,
+    pub act_id: usize,
+    pub act_seq: usize,
+    pub act_prize: Option<String>,
+    pub cus_id: usize,
+    pub cus_nickname: String,
+    pub cus_name: Option<String>,
+    pub cus_phone: Option<i64>,
+    pub cus_identity: Option<String>,
+    pub cus_flag: Option<String>,
+    pub win_time: Option<i64>,
+    pub draw_id: Option<usize>,
+    pub seed_hash: Option<String>,
+    pub win_status: WinStatus,
+    pub forfeit_reason: Option<String>,
+    pub forfeit_time: Option<i64>,
+    pub forfeit_user: Option<usize>,
+    /// 递补或重抽时被替换的 win_id
+    pub replaces: Option<usize>,
+    /// 红包金额（分），其他奖品类型为空
+    pub amount: Option<i64>,
+    /// 团体奖项中所属的团体
+    pub team_id: Option<usize>,
+    pub team_name: Option<String>,
+}
+
+const SELECT_WINNER: &str =
+    "select w.win_id,w.act_id,w.act_seq,p.act_prize,w.cus_id,c.cus_nickname,
+                                    c.cus_name,c.cus_phone,c.cus_identity,c.cus_flag,w.win_time,
+                                    w.draw_id,d.seed_hash,w.win_status,w.forfeit_reason,
+                                    w.forfeit_time,w.forfeit_user,w.replaces,w.amount,
+                                    w.team_id,t.team_name
+                               from ld_win_list w
+                               join ld_custom c on c.cus_id=w.cus_id
+                               left join ld_plan p on p.act_id=w.act_id and p.act_seq=w.act_seq
+                               left join ld_draw d on d.draw_id=w.draw_id
+                               left join ld_team t on t.team_id=w.team_id";
+
+impl Winner {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Winner {
+            win_id: row.get(0)?,
+            act_id: row.get(1)?,
+            act_seq: row.get(2)?,
+            act_prize: row.get(3)?,
+            cus_id: row.get(4)?,
+            cus_nickname: row.get(5)?,
+            cus_name: row.get(6)?,
+            cus_phone: row.get(7)?,
+            cus_identity: row.get(8)?,
+            cus_flag: row.get(9)?,
+            win_time: row.get(10)?,
+            draw_id: row.get(11)?,
+            seed_hash: row.get(12)?,
+            win_status: WinStatus::try_from(row.get::<_, i64>(13)?)?,
+            forfeit_reason: row.get(14)?,
+            forfeit_time: row.get(15)?,
+            forfeit_user: row.get(16)?,
+            replaces: row.get(17)?,
+            amount: row.get(18)?,
+            team_id: row.get(19)?,
+            team_name: row.get(20)?,
+        })
+    }
+}
+
+/// 活动的中奖记录，包括作废和候补，`act_seq` 为空时包括所有奖项
+pub(crate) fn list(
+    conn: &Connection,
+    act_id: usize,
+    act_seq: Option<usize>,
+) -> Result<Vec<Winner>> {
+    activity::get(conn, act_id)?;
+
+    let sql = format!(
+        "{SELECT_WINNER} where w.act_id=?1 and (?2 is null or w.act_seq=?2)
+          order by w.act_seq,w.win_id"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query((act_id, act_seq))?;
+
+    let mut winners = Vec::new();
+    while let Some(row) = rows.next()? {
+        winners.push(Winner::from_row(row)?);
+    }
+
+    Ok(winners)
+}
+
+pub(crate) fn get(conn: &Connection, win_id: usize) -> Result<Winner> {
+    let sql = format!("{SELECT_WINNER} where w.win_id=?");
+    let winner = conn
+        .query_row(&sql, [win_id], |row| Ok(Winner::from_row(row)))
+        .optional()?;
+
+    match winner {
+        Some(winner) => winner,
+        None => Err(BizError::NotFound(format!("中奖记录{win_id}不存在")).into()),
+    }
+}
+
+/// 转盘、刮刮卡等即时玩法直接写入一条中奖记录，在调用方的事务中执行
+///
+/// 调用方负责检查库存和参与资格。红包奖项用本次的种子把剩余金额拆给剩余名额并取第一份。
+pub(crate) fn award(
+    tx: &Connection,
+    event: AuditEvent,
+    act_id: usize,
+    act_seq: usize,
+    cus_id: usize,
+    seed: &[u8],
+    win_time: i64,
+) -> Result<usize> {
+    let plan = load_plan(tx, act_id, act_seq)?;
+    let amount = match &plan.envelope {
+        Some(envelope) => {
+            let split = Split {
+                budget: envelope.budget - draw::awarded_amount(tx, act_id, act_seq)?,
+                shares: plan
+                    .prize_amount
+                    .saturating_sub(count_winners(tx, act_id, act_seq)?),
+                share_min: envelope.share_min,
+                share_max: envelope.share_max,
+            };
+            engine::split(seed, &split)?.first().copied()
+        }
+        None => None,
+    };
+
+    tx.execute(
+        "insert into ld_win_list (act_id, act_seq, cus_id, win_time, win_status, amount)
+         values (?, ?, ?, ?, ?, ?)",
+        (
+            act_id,
+            act_seq,
+            cus_id,
+            win_time,
+            WinStatus::Won as i64,
+            amount,
+        ),
+    )?;
+    let win_id = tx.last_insert_rowid() as usize;
+    claim::issue(tx, win_id, win_time)?;
+    audit::append(
+        tx,
+        event,
+        act_id,
+        &[win_id],
+        json!({ "act_seq": act_seq, "seed": hex::encode(seed) }),
+    )?;
+
+    Ok(win_id)
+}
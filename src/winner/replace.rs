@@ -0,0 +1,298 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::{Connection, OptionalExtension, Transaction, TransactionBehavior};
+use serde_json::json;
+use tracing::info;
+
+use crate::activity::state::{self, ActStatus};
+use crate::audit::{self, AuditEvent};
+use crate::draw::{self, engine, quota, DrawResult, DrawUnit};
+use crate::error::BizError;
+use crate::winner::{self, claim, WinStatus, Winner};
+
+/// 作废、递补只能在抽奖中或抽奖结束后进行
+const REPLACEABLE: [ActStatus; 2] = [ActStatus::Drawing, ActStatus::Closed];
+
+/// 作废一条中奖记录，原记录保留
+pub(crate) fn forfeit(
+    conn: &mut Connection,
+    win_id: usize,
+    reason: &str,
+    user_id: usize,
+) -> Result<Winner> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err(BizError::Invalid("作废原因不能为空".to_owned()).into());
+    }
+
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let winner = winner::get(&tx, win_id)?;
+    state::ensure(&tx, winner.act_id, &REPLACEABLE, "作废中奖")?;
+    if winner.win_status != WinStatus::Won {
+        return Err(BizError::Conflict(format!("中奖记录{win_id}不是有效的中奖")).into());
+    }
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    tx.execute(
+        "update ld_win_list set win_status=?, forfeit_reason=?, forfeit_time=?, forfeit_user=?
+          where win_id=?",
+        (WinStatus::Forfeited as i64, reason, now, user_id, win_id),
+    )?;
+    audit::append(
+        &tx,
+        AuditEvent::Forfeit,
+        winner.act_id,
+        &[win_id],
+        json!({ "reason": reason, "user_id": user_id }),
+    )?;
+    let winner = winner::get(&tx, win_id)?;
+    tx.commit()?;
+    info!(
+        "奖项{}-{} 客户{} 作废: {reason}",
+        winner.act_id, winner.act_seq, winner.cus_id
+    );
+
+    Ok(winner)
+}
+
+/// 检查作废记录还没有被替换，并且奖项还有空出的名额
+fn ensure_vacant(conn: &Connection, forfeited: &Winner) -> Result<()> {
+    let win_id = forfeited.win_id;
+    if forfeited.win_status != WinStatus::Forfeited {
+        return Err(BizError::Conflict(format!("中奖记录{win_id}没有作废")).into());
+    }
+
+    let replaced_by: Option<usize> = conn
+        .query_row(
+            "select win_id from ld_win_list where replaces=?",
+            [win_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(replaced_by) = replaced_by {
+        return Err(BizError::Conflict(format!("中奖记录{win_id}已被{replaced_by}替换")).into());
+    }
+
+    //团体奖项要整队作废后才空出名额
+    if let Some(team_id) = forfeited.team_id {
+        let active: bool = conn.query_row(
+            "select exists(select 1 from ld_win_list
+                            where act_id=? and act_seq=? and team_id=? and win_status=?)",
+            (
+                forfeited.act_id,
+                forfeited.act_seq,
+                team_id,
+                WinStatus::Won as i64,
+            ),
+            |row| row.get(0),
+        )?;
+        if active {
+            return Err(BizError::Conflict(format!(
+                "团体{team_id}还有成员的中奖记录有效，整队作废后才能替换"
+            ))
+            .into());
+        }
+    }
+
+    let plan = draw::load_plan(conn, forfeited.act_id, forfeited.act_seq)?;
+    if draw::count_winners(conn, forfeited.act_id, forfeited.act_seq)? >= plan.prize_amount {
+        return Err(BizError::Conflict(format!(
+            "奖项{}-{}已没有空出的名额",
+            forfeited.act_id, forfeited.act_seq
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+/// 用候补递补作废的名额，`alternate` 为空时按抽取顺序取第一个候补
+///
+/// 候补按当前的规则和中奖限制重新计算资格，抽奖后不再符合条件的候补（团体奖项为整个团体）会被跳过。
+/// 奖项配置了分组配额时，指定的候补必须满足配额，否则跳过不满足配额的候补。
+/// 团体奖项递补指定候补所在的整个团体，返回其中第一条记录。
+/// 红包奖项的候补接手作废记录的金额。
+pub(crate) fn promote(
+    conn: &mut Connection,
+    win_id: usize,
+    alternate: Option<usize>,
+) -> Result<Winner> {
+    let mut tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let forfeited = winner::get(&tx, win_id)?;
+    state::ensure(&tx, forfeited.act_id, &REPLACEABLE, "递补")?;
+    ensure_vacant(&tx, &forfeited)?;
+
+    let alternates = match alternate {
+        Some(alternate_id) => {
+            let alternate = winner::get(&tx, alternate_id)?;
+            if alternate.act_id != forfeited.act_id
+                || alternate.act_seq != forfeited.act_seq
+                || alternate.win_status != WinStatus::Alternate
+            {
+                return Err(
+                    BizError::Invalid(format!("中奖记录{alternate_id}不是该奖项的候补")).into(),
+                );
+            }
+            vec![alternate]
+        }
+        None => winner::list(&tx, forfeited.act_id, Some(forfeited.act_seq))?
+            .into_iter()
+            .filter(|w| w.win_status == WinStatus::Alternate)
+            .collect(),
+    };
+    if alternates.is_empty() {
+        return Err(BizError::Conflict(format!(
+            "奖项{}-{}没有候补",
+            forfeited.act_id, forfeited.act_seq
+        ))
+        .into());
+    }
+
+    let plan = draw::load_plan(&tx, forfeited.act_id, forfeited.act_seq)?;
+    let eligible = eligible_alternates(&mut tx, forfeited.act_id, forfeited.act_seq)?;
+    let alternates = alternates
+        .into_iter()
+        .filter(|w| match plan.draw_unit {
+            DrawUnit::Customer => eligible.contains(&w.cus_id),
+            DrawUnit::Team => w.team_id.is_some_and(|team_id| eligible.contains(&team_id)),
+        })
+        .collect::<Vec<_>>();
+    if alternates.is_empty() {
+        return Err(BizError::Conflict(match alternate {
+            Some(alternate_id) => format!("候补{alternate_id}已不符合参与条件"),
+            None => format!(
+                "奖项{}-{}没有仍符合参与条件的候补",
+                forfeited.act_id, forfeited.act_seq
+            ),
+        })
+        .into());
+    }
+    let promoted_ids = match plan.draw_unit {
+        //团体奖项整队递补，候补团体的全部成员一起中奖
+        DrawUnit::Team => {
+            let team_id = alternates[0].team_id;
+            winner::list(&tx, forfeited.act_id, Some(forfeited.act_seq))?
+                .into_iter()
+                .filter(|w| w.win_status == WinStatus::Alternate && w.team_id == team_id)
+                .map(|w| w.win_id)
+                .collect()
+        }
+        //配置了配额时取第一个递补后仍满足配额的候补
+        DrawUnit::Customer => {
+            let members = alternates
+                .iter()
+                .map(|w| (w.cus_id, w.cus_flag.as_deref()))
+                .collect::<Vec<_>>();
+            match quota::strata(&tx, &plan, &members, 1)? {
+                Some(strata) => {
+                    let order = alternates.iter().map(|w| w.cus_id).collect::<Vec<_>>();
+                    let picked = engine::stratify(&order, 1, &strata);
+                    match alternates.iter().find(|w| picked.contains(&w.cus_id)) {
+                        Some(alternate) => vec![alternate.win_id],
+                        None => {
+                            return Err(BizError::Conflict(format!(
+                                "候补递补后不满足配额，{}",
+                                quota::explain(&strata, &picked)
+                            ))
+                            .into())
+                        }
+                    }
+                }
+                None => vec![alternates[0].win_id],
+            }
+        }
+    };
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    for &alternate_id in &promoted_ids {
+        tx.execute(
+            "update ld_win_list set win_status=?, win_time=?, replaces=?, amount=? where win_id=?",
+            (
+                WinStatus::Won as i64,
+                now,
+                win_id,
+                forfeited.amount,
+                alternate_id,
+            ),
+        )?;
+        claim::issue(&tx, alternate_id, now)?;
+    }
+    audit::append(
+        &tx,
+        AuditEvent::Promote,
+        forfeited.act_id,
+        &[&[win_id], promoted_ids.as_slice()].concat(),
+        json!({ "forfeited": win_id, "promoted": promoted_ids }),
+    )?;
+    let promoted = winner::get(&tx, promoted_ids[0])?;
+    tx.commit()?;
+    info!(
+        "奖项{}-{} 候补客户{:?}递补作废的中奖记录{win_id}",
+        promoted.act_id, promoted.act_seq, promoted_ids
+    );
+
+    Ok(promoted)
+}
+
+/// 按当前的规则和中奖限制，本奖项候补中仍有资格中奖的 cus_id，团体奖项为 team_id
+///
+/// 候补自己的记录不能算作已中本奖项，在保存点中删掉本奖项的候补记录后计算资格，计算完回滚。
+fn eligible_alternates(
+    tx: &mut Transaction,
+    act_id: usize,
+    act_seq: usize,
+) -> Result<HashSet<usize>> {
+    let mut savepoint = tx.savepoint()?;
+    savepoint.execute(
+        "delete from ld_win_list where act_id=? and act_seq=? and win_status=?",
+        (act_id, act_seq, WinStatus::Alternate as i64),
+    )?;
+    let eligible = draw::eligibility(&savepoint, act_id, act_seq)?
+        .candidates
+        .iter()
+        .map(|c| c.team_id.unwrap_or(c.cus_id))
+        .collect();
+    savepoint.rollback()?;
+
+    Ok(eligible)
+}
+
+/// 为作废的名额单独重抽一人
+///
+/// 与普通抽奖一样需要先公布种子哈希，抽出的记录通过 replaces 关联到作废记录。
+pub(crate) fn redraw(conn: &mut Connection, win_id: usize) -> Result<DrawResult> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let forfeited = winner::get(&tx, win_id)?;
+    ensure_vacant(&tx, &forfeited)?;
+
+    let result = draw::draw_in(&tx, forfeited.act_id, forfeited.act_seq, Some(1), 0)?;
+    tx.execute(
+        "update ld_win_list set replaces=? where draw_id=? and win_status=?",
+        (win_id, result.seed.draw_id, WinStatus::Won as i64),
+    )?;
+    let mut win_ids = vec![win_id];
+    {
+        let mut stmt = tx.prepare("select win_id from ld_win_list where replaces=?")?;
+        let ids = stmt.query_map([win_id], |row| row.get(0))?;
+        for id in ids {
+            win_ids.push(id?);
+        }
+    }
+    audit::append(
+        &tx,
+        AuditEvent::Redraw,
+        forfeited.act_id,
+        &win_ids,
+        json!({ "forfeited": win_id, "draw_id": result.seed.draw_id }),
+    )?;
+    tx.commit()?;
+    info!(
+        "奖项{}-{} 重抽替换作废的中奖记录{win_id}: {:?}",
+        forfeited.act_id,
+        forfeited.act_seq,
+        result.winners.iter().map(|w| w.cus_id).collect::<Vec<_>>()
+    );
+
+    Ok(result)
+}